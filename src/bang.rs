@@ -0,0 +1,850 @@
+// The `!` parameters ("bangs") of a `debugchrome:` URL.
+//
+// Bangs are query parameters whose key starts with `!`: `https://example.com/?q=1&!id=7#top`
+// opens `https://example.com/?q=1#top` with `id=7`. The rest of the query and the fragment
+// are passed through untouched. Values are percent-decoded (`+` stays a plus), so a `#` or
+// `&` inside a value is written `%23` / `%26`. Unknown bangs and values of the wrong shape
+// are errors instead of being dropped.
+//
+// Bangs are parsed once into `BangOptions`. The same struct comes from `--<bang>` command-line
+// flags and, through serde, from the JSON body of the console's `POST /open`.
+use crate::cdp::{ImageFormat, WindowState};
+use crate::emulate::{ColorScheme, Device, Emulation, Geolocation, Throttle};
+use crate::selector::TabSelector;
+use crate::wait::WaitCondition;
+use percent_encoding::percent_decode_str;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+/// The shape of value a bang accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BangValue {
+    /// No value: `!close`.
+    Flag,
+    /// No value, or text: `!screenshot`, `!screenshot=docs/home.png`.
+    FlagOrText,
+    /// Any text, possibly empty: `!id=`.
+    Text,
+    /// Pixels or a percentage of the screen: `!w=800`, `!w=50%`.
+    Dimension,
+    /// A non-negative integer: `!timeout=10`.
+    Count,
+    /// A TCP port: `!port=9223`.
+    Port,
+    /// `true` or `false`.
+    Bool,
+    /// A tab selector: `!select=url:*github.com*`.
+    Selector,
+    /// `png`, `jpeg` or `webp`.
+    ImageFormat,
+    /// A whole number from 0 to 100: `!quality=80`.
+    Quality,
+    /// A paper name or a size in inches: `!paper=a4`, `!paper=8.5x11`.
+    Paper,
+    /// Margins in inches, one to four comma-separated like CSS: `!margin=0.5,0.25`.
+    Margins,
+    /// A wait condition: `!wait=networkidle`, `!wait=selector:#app`.
+    Wait,
+    /// A device preset: `!device=iphone-14`.
+    Device,
+    /// Latitude, longitude and optional accuracy in meters: `!geo=52.52,13.405`.
+    Geo,
+    /// A network speed preset or `<down>/<up>/<latency>`: `!throttle=3g`.
+    Throttle,
+    /// `light` or `dark`.
+    ColorScheme,
+    /// A window state: `!state=maximized`.
+    WindowState,
+}
+
+impl BangValue {
+    fn expected(self) -> &'static str {
+        match self {
+            BangValue::Flag => "no value",
+            BangValue::FlagOrText => "no value or a file path",
+            BangValue::Text => "text",
+            BangValue::Dimension => "pixels or a percentage, e.g. 800 or 50%",
+            BangValue::Count => "a non-negative whole number",
+            BangValue::Port => "a port number (0-65535)",
+            BangValue::Bool => "true or false",
+            BangValue::Selector => "a tab selector",
+            BangValue::ImageFormat => "png, jpeg or webp",
+            BangValue::Quality => "a whole number from 0 to 100",
+            BangValue::Paper => {
+                "letter, legal, tabloid, ledger, a3, a4, a5 or <width>x<height> in inches"
+            }
+            BangValue::Margins => "one to four comma-separated sizes in inches",
+            BangValue::Wait => {
+                "load, domcontentloaded, networkidle, selector:<css> or js:<expression>"
+            }
+            BangValue::Device => {
+                "iphone-se, iphone-14, iphone-14-pro-max, pixel-7, galaxy-s20, ipad-mini, \
+                 ipad-pro or galaxy-tab-s8"
+            }
+            BangValue::Geo => "<latitude>,<longitude>[,<accuracy in meters>]",
+            BangValue::Throttle => {
+                "slow-3g, 3g, 4g or <down kbit/s>/<up kbit/s>/<latency ms>, e.g. 1600/750/150"
+            }
+            BangValue::ColorScheme => "light or dark",
+            BangValue::WindowState => "normal, maximized, minimized or fullscreen",
+        }
+    }
+}
+
+/// Every bang `debugchrome` understands, with the value it takes.
+pub const BANGS: &[(&str, BangValue)] = &[
+    ("id", BangValue::Text),
+    ("x", BangValue::Dimension),
+    ("y", BangValue::Dimension),
+    ("w", BangValue::Dimension),
+    ("h", BangValue::Dimension),
+    ("monitor", BangValue::Count),
+    ("dpi", BangValue::Bool),
+    ("state", BangValue::WindowState),
+    ("timeout", BangValue::Count),
+    ("close", BangValue::Flag),
+    ("refresh", BangValue::Flag),
+    ("screenshot", BangValue::FlagOrText),
+    ("format", BangValue::ImageFormat),
+    ("quality", BangValue::Quality),
+    ("fullpage", BangValue::Flag),
+    ("clip", BangValue::Text),
+    ("noview", BangValue::Flag),
+    ("pdf", BangValue::FlagOrText),
+    ("paper", BangValue::Paper),
+    ("landscape", BangValue::Flag),
+    ("margin", BangValue::Margins),
+    ("background", BangValue::Flag),
+    ("header", BangValue::Text),
+    ("footer", BangValue::Text),
+    ("wait", BangValue::Wait),
+    ("wait_timeout", BangValue::Count),
+    ("inject", BangValue::Text),
+    ("tail", BangValue::Flag),
+    ("har", BangValue::FlagOrText),
+    ("intercept", BangValue::Text),
+    ("storage", BangValue::Text),
+    ("device", BangValue::Device),
+    ("ua", BangValue::Text),
+    ("geo", BangValue::Geo),
+    ("tz", BangValue::Text),
+    ("locale", BangValue::Text),
+    ("offline", BangValue::Flag),
+    ("throttle", BangValue::Throttle),
+    ("colorscheme", BangValue::ColorScheme),
+    ("keep_focus", BangValue::Flag),
+    ("openwindow", BangValue::Flag),
+    ("headless", BangValue::Flag),
+    ("profile", BangValue::Text),
+    ("host", BangValue::Text),
+    ("port", BangValue::Port),
+    ("select", BangValue::Selector),
+    ("open_if_missing", BangValue::Flag),
+];
+
+/// The kind of value bang `name` takes, if it is a bang.
+pub fn bang_value(name: &str) -> Option<BangValue> {
+    BANGS
+        .iter()
+        .find(|(known, _)| *known == name)
+        .map(|(_, value)| *value)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BangError {
+    /// A `!` with no name, e.g. `?!=1`.
+    MissingName(String),
+    Unknown(String),
+    Duplicate(String),
+    /// A `--<bang>` flag at the end of the command line without its value.
+    MissingValue(String),
+    /// The value is not valid percent-encoded UTF-8.
+    Encoding {
+        name: String,
+        value: String,
+    },
+    InvalidValue {
+        name: String,
+        value: String,
+        expected: &'static str,
+    },
+}
+
+impl fmt::Display for BangError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BangError::MissingName(segment) => write!(f, "bang {:?} has no name", segment),
+            BangError::Unknown(name) => {
+                write!(f, "unknown bang !{}; known bangs are", name)?;
+                for (known, _) in BANGS {
+                    write!(f, " !{}", known)?;
+                }
+                Ok(())
+            }
+            BangError::Duplicate(name) => write!(f, "!{} is given more than once", name),
+            BangError::MissingValue(name) => {
+                write!(f, "--{} needs a value", name.replace('_', "-"))
+            }
+            BangError::Encoding { name, value } => {
+                write!(f, "!{}={} is not valid percent-encoded UTF-8", name, value)
+            }
+            BangError::InvalidValue {
+                name,
+                value,
+                expected,
+            } => write!(f, "invalid !{}={:?}: expected {}", name, value, expected),
+        }
+    }
+}
+
+impl std::error::Error for BangError {}
+
+/// A window coordinate or size: pixels, or a percentage of the screen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dimension {
+    Pixels(i32),
+    Percent(f32),
+}
+
+impl Dimension {
+    /// Pixels, with a percentage taken of `max`.
+    pub fn resolve(self, max: i32) -> i32 {
+        match self {
+            Dimension::Pixels(pixels) => pixels,
+            Dimension::Percent(percent) => ((percent / 100.0) * max as f32).round() as i32,
+        }
+    }
+}
+
+impl FromStr for Dimension {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parsed = match s.strip_suffix('%') {
+            Some(percent) => percent
+                .parse::<f32>()
+                .ok()
+                .filter(|p| p.is_finite())
+                .map(Dimension::Percent),
+            None => s.parse().ok().map(Dimension::Pixels),
+        };
+        parsed.ok_or_else(|| {
+            format!(
+                "invalid dimension {:?}: expected {}",
+                s,
+                BangValue::Dimension.expected()
+            )
+        })
+    }
+}
+
+impl fmt::Display for Dimension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Dimension::Pixels(pixels) => write!(f, "{}", pixels),
+            Dimension::Percent(percent) => write!(f, "{}%", percent),
+        }
+    }
+}
+
+// JSON takes pixels as a number and percentages as a string: `"w": 800`, `"w": "50%"`.
+impl Serialize for Dimension {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Dimension::Pixels(pixels) => serializer.serialize_i32(*pixels),
+            Dimension::Percent(_) => serializer.collect_str(self),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Dimension {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Pixels(i32),
+            Text(String),
+        }
+        match Repr::deserialize(deserializer)? {
+            Repr::Pixels(pixels) => Ok(Dimension::Pixels(pixels)),
+            Repr::Text(text) => text.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+// Values written the same way in a URL, on the command line and as a JSON string.
+macro_rules! serde_as_str {
+    ($type:ty) => {
+        impl Serialize for $type {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $type {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                String::deserialize(deserializer)?
+                    .parse()
+                    .map_err(serde::de::Error::custom)
+            }
+        }
+    };
+}
+
+/// Named paper sizes for `!paper=`, in inches.
+pub const PAPER_SIZES: &[(&str, f64, f64)] = &[
+    ("letter", 8.5, 11.0),
+    ("legal", 8.5, 14.0),
+    ("tabloid", 11.0, 17.0),
+    ("ledger", 17.0, 11.0),
+    ("a3", 11.69, 16.54),
+    ("a4", 8.27, 11.69),
+    ("a5", 5.83, 8.27),
+];
+
+/// A PDF page size in inches.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PaperSize {
+    pub width: f64,
+    pub height: f64,
+}
+
+impl FromStr for PaperSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let named = PAPER_SIZES
+            .iter()
+            .find(|(name, _, _)| name.eq_ignore_ascii_case(s));
+        if let Some((_, width, height)) = named {
+            return Ok(PaperSize {
+                width: *width,
+                height: *height,
+            });
+        }
+        let size = s
+            .split_once(['x', 'X'])
+            .and_then(|(width, height)| Some((inches(width)?, inches(height)?)))
+            .filter(|(width, height)| *width > 0.0 && *height > 0.0);
+        match size {
+            Some((width, height)) => Ok(PaperSize { width, height }),
+            None => Err(format!(
+                "invalid paper size {:?}: expected {}",
+                s,
+                BangValue::Paper.expected()
+            )),
+        }
+    }
+}
+
+impl fmt::Display for PaperSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let named = PAPER_SIZES
+            .iter()
+            .find(|(_, width, height)| *width == self.width && *height == self.height);
+        match named {
+            Some((name, _, _)) => write!(f, "{}", name),
+            None => write!(f, "{}x{}", self.width, self.height),
+        }
+    }
+}
+
+serde_as_str!(PaperSize);
+
+/// PDF margins in inches.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Margins {
+    pub top: f64,
+    pub right: f64,
+    pub bottom: f64,
+    pub left: f64,
+}
+
+impl FromStr for Margins {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let sizes: Option<Vec<f64>> = s.split(',').map(inches).collect();
+        let margins = match sizes.as_deref() {
+            Some(&[all]) => Some((all, all, all, all)),
+            Some(&[vertical, horizontal]) => Some((vertical, horizontal, vertical, horizontal)),
+            Some(&[top, horizontal, bottom]) => Some((top, horizontal, bottom, horizontal)),
+            Some(&[top, right, bottom, left]) => Some((top, right, bottom, left)),
+            _ => None,
+        };
+        match margins {
+            Some((top, right, bottom, left)) => Ok(Margins {
+                top,
+                right,
+                bottom,
+                left,
+            }),
+            None => Err(format!(
+                "invalid margins {:?}: expected {}",
+                s,
+                BangValue::Margins.expected()
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Margins {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{},{},{},{}",
+            self.top, self.right, self.bottom, self.left
+        )
+    }
+}
+
+serde_as_str!(Margins);
+
+// A non-negative, finite size in inches.
+fn inches(text: &str) -> Option<f64> {
+    text.trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|inches| inches.is_finite() && *inches >= 0.0)
+}
+
+/// Everything the bangs of one link ask for.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BangOptions {
+    /// Tags the tab so later links find it; `Some("")` asks for a generated id.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub x: Option<Dimension>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub y: Option<Dimension>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub w: Option<Dimension>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub h: Option<Dimension>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub monitor: Option<usize>,
+    #[serde(skip_serializing_if = "is_false")]
+    pub dpi: bool,
+    /// Maximizes, minimizes or fullscreens the window after it is placed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<WindowState>,
+    /// Seconds until the tab is closed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    #[serde(skip_serializing_if = "is_false")]
+    pub close: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub refresh: bool,
+    /// Where to save a screenshot; `Some("")` means `debugchrome.<format>`.
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "flag_or_text::serialize",
+        deserialize_with = "flag_or_text::deserialize"
+    )]
+    pub screenshot: Option<String>,
+    /// Screenshot format; without it the file extension decides, then png.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<ImageFormat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality: Option<u8>,
+    /// Captures the whole page instead of the viewport.
+    #[serde(skip_serializing_if = "is_false")]
+    pub fullpage: bool,
+    /// CSS selector of the element to capture.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clip: Option<String>,
+    /// Saves the screenshot or PDF without opening it in a viewer.
+    #[serde(skip_serializing_if = "is_false")]
+    pub noview: bool,
+    /// Where to save a PDF of the page; `Some("")` means `debugchrome.pdf`.
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "flag_or_text::serialize",
+        deserialize_with = "flag_or_text::deserialize"
+    )]
+    pub pdf: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paper: Option<PaperSize>,
+    #[serde(skip_serializing_if = "is_false")]
+    pub landscape: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub margin: Option<Margins>,
+    /// Prints background colors and images.
+    #[serde(skip_serializing_if = "is_false")]
+    pub background: bool,
+    /// Header template, in `Page.printToPDF`'s HTML with `pageNumber`, `totalPages`, ... classes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub header: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub footer: Option<String>,
+    /// What to wait for before screenshots, PDFs and scripts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wait: Option<WaitCondition>,
+    /// Seconds to wait for `wait`, 30 by default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wait_timeout: Option<u64>,
+    /// JavaScript to run in the tab now and in every document it loads later.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inject: Option<String>,
+    /// Streams the tab's console, exceptions and log to stdout until it closes.
+    #[serde(skip_serializing_if = "is_false")]
+    pub tail: bool,
+    /// Where to write a HAR of the tab's network traffic when it closes; `Some("")` means
+    /// `debugchrome.har`.
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "flag_or_text::serialize",
+        deserialize_with = "flag_or_text::deserialize"
+    )]
+    pub har: Option<String>,
+    /// JSON rules file that blocks, rewrites, delays or fulfils the tab's requests.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub intercept: Option<String>,
+    /// JSON file of cookies and web storage to start the tab with.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub storage: Option<String>,
+    /// Phone or tablet viewport, device pixel ratio, user agent and touch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device: Option<Device>,
+    /// User agent, instead of the device's or the browser's own.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ua: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub geo: Option<Geolocation>,
+    /// IANA time zone: `Europe/Berlin`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tz: Option<String>,
+    /// `navigator.language`, `Intl` formatting and `Accept-Language`: `de-DE`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+    #[serde(skip_serializing_if = "is_false")]
+    pub offline: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub throttle: Option<Throttle>,
+    /// `prefers-color-scheme`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub colorscheme: Option<ColorScheme>,
+    #[serde(skip_serializing_if = "is_false")]
+    pub keep_focus: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub openwindow: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub headless: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub select: Option<String>,
+    /// Opens the URL when `select` matches no tab, instead of reporting it not found.
+    #[serde(skip_serializing_if = "is_false")]
+    pub open_if_missing: bool,
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+// A `FlagOrText` bang in JSON: `true` for the bare flag, a string for a value.
+mod flag_or_text {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        value: &Option<String>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value.as_deref() {
+            Some("") => serializer.serialize_bool(true),
+            Some(text) => serializer.serialize_str(text),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<String>, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Flag(bool),
+            Text(String),
+        }
+        Ok(match Option::<Repr>::deserialize(deserializer)? {
+            Some(Repr::Flag(true)) => Some(String::new()),
+            Some(Repr::Text(text)) => Some(text),
+            Some(Repr::Flag(false)) | None => None,
+        })
+    }
+}
+
+fn parse_value<T: FromStr>(name: &str, value: &str, kind: BangValue) -> Result<T, BangError> {
+    value.parse().map_err(|_| BangError::InvalidValue {
+        name: name.to_string(),
+        value: value.to_string(),
+        expected: kind.expected(),
+    })
+}
+
+impl BangOptions {
+    /// Sets bang `name` from its decoded text value, as written in a URL or on the command line.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), BangError> {
+        let kind = bang_value(name).ok_or_else(|| BangError::Unknown(name.to_string()))?;
+        let invalid = || BangError::InvalidValue {
+            name: name.to_string(),
+            value: value.to_string(),
+            expected: kind.expected(),
+        };
+        let flag = || value.is_empty().then_some(true).ok_or_else(invalid);
+        match name {
+            "id" => self.id = Some(value.to_string()),
+            "x" => self.x = Some(parse_value(name, value, kind)?),
+            "y" => self.y = Some(parse_value(name, value, kind)?),
+            "w" => self.w = Some(parse_value(name, value, kind)?),
+            "h" => self.h = Some(parse_value(name, value, kind)?),
+            "monitor" => self.monitor = Some(parse_value(name, value, kind)?),
+            "dpi" => {
+                self.dpi = match value.to_ascii_lowercase().as_str() {
+                    "true" => true,
+                    "false" => false,
+                    _ => return Err(invalid()),
+                }
+            }
+            "state" => self.state = Some(parse_value(name, value, kind)?),
+            "timeout" => self.timeout = Some(parse_value(name, value, kind)?),
+            "close" => self.close = flag()?,
+            "refresh" => self.refresh = flag()?,
+            "screenshot" => self.screenshot = Some(value.to_string()),
+            "format" => self.format = Some(parse_value(name, value, kind)?),
+            "quality" => {
+                self.quality = Some(
+                    parse_value(name, value, kind)
+                        .ok()
+                        .filter(|quality| *quality <= 100)
+                        .ok_or_else(invalid)?,
+                )
+            }
+            "fullpage" => self.fullpage = flag()?,
+            "clip" => self.clip = Some(value.to_string()),
+            "noview" => self.noview = flag()?,
+            "pdf" => self.pdf = Some(value.to_string()),
+            "paper" => self.paper = Some(parse_value(name, value, kind)?),
+            "landscape" => self.landscape = flag()?,
+            "margin" => self.margin = Some(parse_value(name, value, kind)?),
+            "background" => self.background = flag()?,
+            "header" => self.header = Some(value.to_string()),
+            "footer" => self.footer = Some(value.to_string()),
+            "wait" => self.wait = Some(parse_value(name, value, kind)?),
+            "wait_timeout" => self.wait_timeout = Some(parse_value(name, value, kind)?),
+            "inject" => self.inject = Some(value.to_string()),
+            "tail" => self.tail = flag()?,
+            "har" => self.har = Some(value.to_string()),
+            "intercept" => self.intercept = Some(value.to_string()),
+            "storage" => self.storage = Some(value.to_string()),
+            "device" => self.device = Some(parse_value(name, value, kind)?),
+            "ua" => self.ua = Some(value.to_string()),
+            "geo" => self.geo = Some(parse_value(name, value, kind)?),
+            "tz" => self.tz = Some(value.to_string()),
+            "locale" => self.locale = Some(value.to_string()),
+            "offline" => self.offline = flag()?,
+            "throttle" => self.throttle = Some(parse_value(name, value, kind)?),
+            "colorscheme" => self.colorscheme = Some(parse_value(name, value, kind)?),
+            "keep_focus" => self.keep_focus = flag()?,
+            "openwindow" => self.openwindow = flag()?,
+            "headless" => self.headless = flag()?,
+            "profile" => self.profile = Some(value.to_string()),
+            "host" => self.host = Some(value.to_string()),
+            "port" => self.port = Some(parse_value(name, value, kind)?),
+            "select" => {
+                value.parse::<TabSelector>().map_err(|_| invalid())?;
+                self.select = Some(value.to_string());
+            }
+            "open_if_missing" => self.open_if_missing = flag()?,
+            _ => return Err(BangError::Unknown(name.to_string())),
+        }
+        Ok(())
+    }
+
+    /// Applies `--<bang>` flags (`--keep-focus`, `--timeout 10`), which win over the URL's bangs.
+    /// Arguments that are not bangs are left for the caller.
+    pub fn apply_args(&mut self, args: &[String]) -> Result<(), BangError> {
+        let mut i = 0;
+        while i < args.len() {
+            let name = args[i]
+                .strip_prefix("--")
+                .map(|name| name.replace('-', "_"))
+                .unwrap_or_default();
+            match bang_value(&name) {
+                Some(BangValue::Flag) => {
+                    self.set(&name, "")?;
+                    i += 1;
+                }
+                // `--screenshot` alone, or `--screenshot out.png`
+                Some(BangValue::FlagOrText) => match args.get(i + 1) {
+                    Some(value) if !value.starts_with("--") => {
+                        self.set(&name, value)?;
+                        i += 2;
+                    }
+                    _ => {
+                        self.set(&name, "")?;
+                        i += 1;
+                    }
+                },
+                Some(_) => {
+                    let value = args
+                        .get(i + 1)
+                        .ok_or_else(|| BangError::MissingValue(name.clone()))?;
+                    self.set(&name, value)?;
+                    i += 2;
+                }
+                None => i += 1,
+            }
+        }
+        Ok(())
+    }
+
+    /// The command-line flags that [`BangOptions::apply_args`] turns back into these options.
+    pub fn to_args(&self) -> Vec<String> {
+        let Ok(serde_json::Value::Object(fields)) = serde_json::to_value(self) else {
+            return Vec::new();
+        };
+        let mut args = Vec::new();
+        for (name, value) in fields {
+            let flag = format!("--{}", name.replace('_', "-"));
+            match value {
+                serde_json::Value::Bool(false) => {}
+                serde_json::Value::Bool(true)
+                    if matches!(
+                        bang_value(&name),
+                        Some(BangValue::Flag | BangValue::FlagOrText)
+                    ) =>
+                {
+                    args.push(flag)
+                }
+                serde_json::Value::String(text) => args.extend([flag, text]),
+                other => args.extend([flag, other.to_string()]),
+            }
+        }
+        args
+    }
+
+    /// What `device`, `ua`, `geo`, `tz`, `locale`, `offline`, `throttle` and `colorscheme` ask
+    /// to emulate, if any of them is given.
+    pub fn emulation(&self) -> Option<Emulation> {
+        let emulation = Emulation {
+            device: self.device,
+            user_agent: self.ua.clone(),
+            geolocation: self.geo,
+            timezone: self.tz.clone(),
+            locale: self.locale.clone(),
+            offline: self.offline,
+            throttle: self.throttle,
+            color_scheme: self.colorscheme,
+        };
+        (!emulation.is_empty()).then_some(emulation)
+    }
+
+    /// `x`, `y`, `w` and `h` are all given.
+    pub fn has_bounds(&self) -> bool {
+        self.x.is_some() && self.y.is_some() && self.w.is_some() && self.h.is_some()
+    }
+}
+
+/// A URL split into the address to open and its bangs.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BangUrl {
+    /// The URL without its bangs; its own query and fragment are left as they were.
+    pub url: String,
+    #[serde(default)]
+    pub options: BangOptions,
+}
+
+impl BangUrl {
+    pub fn parse(raw: &str) -> Result<Self, BangError> {
+        let (before_fragment, fragment) = match raw.find('#') {
+            Some(at) => raw.split_at(at),
+            None => (raw, ""),
+        };
+        let (base, query) = match before_fragment.split_once('?') {
+            Some((base, query)) => (base, Some(query)),
+            // Older links put the bangs straight after the address: `https://example.com!id=1`.
+            None => match legacy_bang_start(before_fragment) {
+                Some(at) => (&before_fragment[..at], Some(&before_fragment[at..])),
+                None => (before_fragment, None),
+            },
+        };
+        let Some(query) = query else {
+            return Ok(BangUrl {
+                url: raw.to_string(),
+                options: BangOptions::default(),
+            });
+        };
+
+        let mut options = BangOptions::default();
+        let mut seen = HashSet::new();
+        let mut kept = Vec::new();
+        for segment in query.split('&') {
+            let Some(bang) = segment.strip_prefix('!') else {
+                kept.push(segment);
+                continue;
+            };
+            let (name, raw_value) = bang.split_once('=').unwrap_or((bang, ""));
+            if name.is_empty() {
+                return Err(BangError::MissingName(segment.to_string()));
+            }
+            if bang_value(name).is_none() {
+                return Err(BangError::Unknown(name.to_string()));
+            }
+            let value =
+                percent_decode_str(raw_value)
+                    .decode_utf8()
+                    .map_err(|_| BangError::Encoding {
+                        name: name.to_string(),
+                        value: raw_value.to_string(),
+                    })?;
+            options.set(name, &value)?;
+            if !seen.insert(name) {
+                return Err(BangError::Duplicate(name.to_string()));
+            }
+        }
+        if seen.is_empty() {
+            return Ok(BangUrl {
+                url: raw.to_string(),
+                options,
+            });
+        }
+        let mut url = base.to_string();
+        if !kept.is_empty() {
+            url.push('?');
+            url.push_str(&kept.join("&"));
+        }
+        url.push_str(fragment);
+        Ok(BangUrl { url, options })
+    }
+}
+
+impl FromStr for BangUrl {
+    type Err = BangError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        BangUrl::parse(s)
+    }
+}
+
+// Where a legacy `address!bang&!bang` tail starts: the first `!` after which every
+// `&`-separated part is a known bang. A `!` that is really part of the path never qualifies.
+fn legacy_bang_start(url: &str) -> Option<usize> {
+    url.match_indices('!').map(|(at, _)| at).find(|&at| {
+        url[at..].split('&').all(|part| {
+            part.strip_prefix('!')
+                .map(|bang| bang.split_once('=').map_or(bang, |(name, _)| name))
+                .is_some_and(|name| bang_value(name).is_some())
+        })
+    })
+}
@@ -0,0 +1,103 @@
+// Locating and launching a local Chrome/Chromium with remote debugging enabled.
+//
+// Windows looks in the `App Paths` registry key and then `PATH`; Linux searches
+// `PATH` for the usual Chrome and Chromium launcher names. Profiles live under
+// `%LOCALAPPDATA%` on Windows and under `$XDG_CONFIG_HOME` (default `~/.config`) on Linux.
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+
+/// Executable names tried through `PATH`, most specific first.
+#[cfg(target_os = "windows")]
+pub const CHROME_EXECUTABLES: &[&str] = &["chrome.exe"];
+#[cfg(not(target_os = "windows"))]
+pub const CHROME_EXECUTABLES: &[&str] = &[
+    "google-chrome",
+    "google-chrome-stable",
+    "chromium",
+    "chromium-browser",
+];
+
+#[cfg(target_os = "windows")]
+fn find_chrome_via_registry() -> Option<PathBuf> {
+    use winreg::RegKey;
+    use winreg::enums::{HKEY_LOCAL_MACHINE, KEY_READ, KEY_WOW64_64KEY};
+
+    let hk = RegKey::predef(HKEY_LOCAL_MACHINE)
+        .open_subkey_with_flags(
+            r"SOFTWARE\Microsoft\Windows\CurrentVersion\App Paths\chrome.exe",
+            KEY_READ | KEY_WOW64_64KEY,
+        )
+        .ok()?;
+    hk.get_value::<String, _>("").ok().map(PathBuf::from)
+}
+
+/// Finds the Chrome (or Chromium) executable to launch.
+pub fn find_chrome() -> Option<PathBuf> {
+    #[cfg(target_os = "windows")]
+    if let Some(path) = find_chrome_via_registry() {
+        return Some(path);
+    }
+    CHROME_EXECUTABLES
+        .iter()
+        .find_map(|name| which::which(name).ok())
+}
+
+/// Candidate user data directories for the installed browsers, in search order.
+pub fn user_data_dir_candidates() -> Vec<PathBuf> {
+    #[cfg(target_os = "windows")]
+    {
+        dirs::data_local_dir()
+            .map(|dir| vec![dir.join("Google").join("Chrome").join("User Data")])
+            .unwrap_or_default()
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        // dirs::config_dir honours $XDG_CONFIG_HOME and falls back to ~/.config.
+        dirs::config_dir()
+            .map(|dir| {
+                ["google-chrome", "google-chrome-beta", "chromium"]
+                    .iter()
+                    .map(|name| dir.join(name))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// The first existing user data directory of an installed browser.
+pub fn user_data_dir() -> Option<PathBuf> {
+    user_data_dir_candidates()
+        .into_iter()
+        .find(|dir| dir.is_dir())
+}
+
+/// Flag for Chrome's current headless mode (the full browser without a window).
+pub const HEADLESS_ARG: &str = "--headless=new";
+
+/// Command-line flags for a debug browser on `port` using `user_data_dir`.
+pub fn debug_args(port: u16, user_data_dir: &Path) -> Vec<String> {
+    vec![
+        format!("--remote-debugging-port={}", port),
+        "--enable-automation".to_string(),
+        "--no-first-run".to_string(),
+        format!("--user-data-dir={}", user_data_dir.display()),
+    ]
+}
+
+/// Starts `chrome` detached from our stdio with the debug flags plus `extra_args`.
+pub fn launch(
+    chrome: &Path,
+    port: u16,
+    user_data_dir: &Path,
+    extra_args: &[String],
+) -> io::Result<Child> {
+    log::debug!("Launching {} on port {}", chrome.display(), port);
+    Command::new(chrome)
+        .args(debug_args(port, user_data_dir))
+        .args(extra_args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+}
@@ -0,0 +1,973 @@
+// Typed Chrome DevTools Protocol client.
+//
+// A `CdpClient` owns a single WebSocket to either the browser endpoint
+// (`/json/version` -> webSocketDebuggerUrl) or a page endpoint
+// (`/devtools/page/<targetId>`). A background task reads the socket: replies
+// resolve the pending request with the same id, events are handed to every
+// subscriber whose method/sessionId filter matches. Clones share the socket,
+// so parallel calls no longer race each other for replies.
+use futures_util::stream::{SplitSink, SplitStream};
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, oneshot};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async, tungstenite::Message};
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+// Global atomic counter for unique IDs
+static COMMAND_ID_COUNTER: AtomicUsize = AtomicUsize::new(1);
+
+pub fn get_unique_id() -> usize {
+    COMMAND_ID_COUNTER.fetch_add(1, Ordering::SeqCst)
+}
+
+/// How long `CdpClient::call` waits for a reply before giving up.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+pub enum CdpError {
+    Http(reqwest::Error),
+    WebSocket(tokio_tungstenite::tungstenite::Error),
+    Json(serde_json::Error),
+    Base64(base64::DecodeError),
+    /// Chrome answered the request with an `error` object.
+    Protocol {
+        method: String,
+        code: i64,
+        message: String,
+    },
+    Timeout(String),
+    Closed,
+    MissingField(&'static str),
+}
+
+impl std::fmt::Display for CdpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CdpError::Http(e) => write!(f, "DevTools HTTP request failed: {}", e),
+            CdpError::WebSocket(e) => write!(f, "DevTools WebSocket error: {}", e),
+            CdpError::Json(e) => write!(f, "invalid DevTools JSON: {}", e),
+            CdpError::Base64(e) => write!(f, "invalid base64 payload: {}", e),
+            CdpError::Protocol {
+                method,
+                code,
+                message,
+            } => write!(f, "{} failed ({}): {}", method, code, message),
+            CdpError::Timeout(method) => write!(f, "timed out waiting for {}", method),
+            CdpError::Closed => write!(f, "DevTools WebSocket closed"),
+            CdpError::MissingField(field) => write!(f, "reply is missing `{}`", field),
+        }
+    }
+}
+
+impl std::error::Error for CdpError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CdpError::Http(e) => Some(e),
+            CdpError::WebSocket(e) => Some(e),
+            CdpError::Json(e) => Some(e),
+            CdpError::Base64(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for CdpError {
+    fn from(e: reqwest::Error) -> Self {
+        CdpError::Http(e)
+    }
+}
+
+impl From<tokio_tungstenite::tungstenite::Error> for CdpError {
+    fn from(e: tokio_tungstenite::tungstenite::Error) -> Self {
+        CdpError::WebSocket(e)
+    }
+}
+
+impl From<serde_json::Error> for CdpError {
+    fn from(e: serde_json::Error) -> Self {
+        CdpError::Json(e)
+    }
+}
+
+impl From<base64::DecodeError> for CdpError {
+    fn from(e: base64::DecodeError) -> Self {
+        CdpError::Base64(e)
+    }
+}
+
+pub type CdpResult<T> = Result<T, CdpError>;
+
+/// Endpoint used when nothing else is configured.
+pub const DEFAULT_ENDPOINT: &str = "localhost:9222";
+/// Environment variable holding a `host:port` DevTools endpoint.
+pub const ENDPOINT_ENV: &str = "DEBUGCHROME_CDP";
+
+/// `host:port` of a DevTools HTTP endpoint, e.g. `localhost:9222`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CdpEndpoint {
+    pub host: String,
+    pub port: u16,
+}
+
+impl CdpEndpoint {
+    pub fn new(host: impl Into<String>, port: u16) -> Self {
+        CdpEndpoint {
+            host: host.into(),
+            port,
+        }
+    }
+
+    /// `DEBUGCHROME_CDP` when it is set and valid, otherwise `localhost:9222`.
+    pub fn from_env() -> Self {
+        match std::env::var(ENDPOINT_ENV) {
+            Ok(value) => value.parse().unwrap_or_else(|e| {
+                log::warn!("Ignoring {}={}: {}", ENDPOINT_ENV, value, e);
+                CdpEndpoint::default()
+            }),
+            Err(_) => CdpEndpoint::default(),
+        }
+    }
+
+    /// Whether a browser for this endpoint can be launched on this machine.
+    pub fn is_local(&self) -> bool {
+        matches!(
+            self.host.as_str(),
+            "localhost" | "127.0.0.1" | "::1" | "[::1]" | "0.0.0.0"
+        )
+    }
+}
+
+impl Default for CdpEndpoint {
+    fn default() -> Self {
+        CdpEndpoint::new("localhost", 9222)
+    }
+}
+
+impl std::fmt::Display for CdpEndpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.host, self.port)
+    }
+}
+
+impl std::str::FromStr for CdpEndpoint {
+    type Err = String;
+
+    /// Accepts `host:port`, `host`, `port` or an `http://`/`ws://` URL.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let trimmed = s.trim();
+        let without_scheme = ["http://", "https://", "ws://", "wss://"]
+            .iter()
+            .find_map(|scheme| trimmed.strip_prefix(scheme))
+            .unwrap_or(trimmed);
+        let authority = without_scheme.split('/').next().unwrap_or_default();
+        if authority.is_empty() {
+            return Err(format!("invalid CDP endpoint '{}'", s));
+        }
+        let default = CdpEndpoint::default();
+        let parse_port = |port: &str| {
+            port.parse::<u16>()
+                .map_err(|_| format!("invalid port '{}' in CDP endpoint '{}'", port, s))
+        };
+        match authority.rsplit_once(':') {
+            // A bare IPv6 address such as `::1` has no port.
+            Some((host, _)) if host.contains(':') && !host.ends_with(']') => {
+                Ok(CdpEndpoint::new(authority, default.port))
+            }
+            Some((host, port)) => {
+                let host = if host.is_empty() { &default.host } else { host };
+                Ok(CdpEndpoint::new(host, parse_port(port)?))
+            }
+            None if authority.chars().all(|c| c.is_ascii_digit()) => {
+                Ok(CdpEndpoint::new(default.host, parse_port(authority)?))
+            }
+            None => Ok(CdpEndpoint::new(authority, default.port)),
+        }
+    }
+}
+
+/// One entry of the DevTools `/json` listing.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TargetInfo {
+    pub id: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub url: String,
+    #[serde(default, rename = "type")]
+    pub target_type: String,
+    #[serde(default)]
+    pub web_socket_debugger_url: Option<String>,
+}
+
+/// Fetches the `/json` target listing from `endpoint` (e.g. `localhost:9222`).
+pub async fn list_targets(endpoint: &str) -> CdpResult<Vec<TargetInfo>> {
+    let targets = reqwest::get(format!("http://{}/json", endpoint))
+        .await?
+        .json()
+        .await?;
+    Ok(targets)
+}
+
+/// Fetches `/json/version` and returns the browser-level WebSocket URL.
+pub async fn browser_ws_url(endpoint: &str) -> CdpResult<String> {
+    let version: Value = reqwest::get(format!("http://{}/json/version", endpoint))
+        .await?
+        .json()
+        .await?;
+    version["webSocketDebuggerUrl"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or(CdpError::MissingField("webSocketDebuggerUrl"))
+}
+
+/// Parameters for `Target.createTarget`.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateTarget {
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub browser_context_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub left: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<i32>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub new_window: bool,
+}
+
+/// Image format for `Page.captureScreenshot`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    #[default]
+    Png,
+    Jpeg,
+    Webp,
+}
+
+impl ImageFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Jpeg => "jpeg",
+            ImageFormat::Webp => "webp",
+        }
+    }
+
+    /// The format a file name's extension asks for, if it names one.
+    pub fn from_path(path: &str) -> Option<Self> {
+        let (_, extension) = path.rsplit_once('.')?;
+        extension.parse().ok()
+    }
+}
+
+impl std::str::FromStr for ImageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "png" => Ok(ImageFormat::Png),
+            "jpeg" | "jpg" => Ok(ImageFormat::Jpeg),
+            "webp" => Ok(ImageFormat::Webp),
+            _ => Err(format!(
+                "unknown image format {:?}; use png, jpeg or webp",
+                s
+            )),
+        }
+    }
+}
+
+/// A browser window's state, as `Browser.Bounds` has it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WindowState {
+    #[default]
+    Normal,
+    Minimized,
+    Maximized,
+    Fullscreen,
+}
+
+impl std::str::FromStr for WindowState {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "normal" => Ok(WindowState::Normal),
+            "minimized" => Ok(WindowState::Minimized),
+            "maximized" => Ok(WindowState::Maximized),
+            "fullscreen" => Ok(WindowState::Fullscreen),
+            _ => Err(format!(
+                "unknown window state {:?}; use normal, maximized, minimized or fullscreen",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for WindowState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            WindowState::Normal => "normal",
+            WindowState::Minimized => "minimized",
+            WindowState::Maximized => "maximized",
+            WindowState::Fullscreen => "fullscreen",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// `Browser.Bounds`: a window's position and size in screen pixels, and its state. Chrome
+/// only takes a position or size together with the `normal` state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowBounds {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub left: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window_state: Option<WindowState>,
+}
+
+/// A page area in CSS pixels, as `Page.captureScreenshot` takes for `clip`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Viewport {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    pub scale: f64,
+}
+
+/// Parameters for `Page.captureScreenshot`.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CaptureScreenshot {
+    pub format: ImageFormat,
+    /// 0-100; Chrome only uses it for jpeg and webp.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clip: Option<Viewport>,
+    /// Renders parts of the page outside the viewport, for full-page and off-screen clips.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub capture_beyond_viewport: bool,
+}
+
+/// Parameters for `Page.printToPDF`. Sizes and margins are in inches; unset fields take
+/// Chrome's defaults (US Letter, 0.4in margins).
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrintToPdf {
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub landscape: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub print_background: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paper_width: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paper_height: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub margin_top: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub margin_right: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub margin_bottom: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub margin_left: Option<f64>,
+    /// Set when a header or footer template is given.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub display_header_footer: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub header_template: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub footer_template: Option<String>,
+}
+
+/// `exceptionDetails` from a `Runtime.evaluate` reply.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExceptionDetails {
+    #[serde(default)]
+    pub text: String,
+    #[serde(default)]
+    pub line_number: i64,
+    #[serde(default)]
+    pub column_number: i64,
+    #[serde(default)]
+    pub exception: Option<Value>,
+}
+
+impl ExceptionDetails {
+    /// `Uncaught ReferenceError: boom is not defined (line 1, column 5)`, with 1-based
+    /// positions.
+    pub fn message(&self) -> String {
+        let description = self
+            .exception
+            .as_ref()
+            .and_then(|exception| exception["description"].as_str())
+            .and_then(|description| description.lines().next());
+        let text = match description {
+            Some(description) if self.text == "Uncaught" => format!("Uncaught {}", description),
+            Some(description) if self.text.is_empty() => description.to_string(),
+            _ => self.text.clone(),
+        };
+        format!(
+            "{} (line {}, column {})",
+            text,
+            self.line_number + 1,
+            self.column_number + 1
+        )
+    }
+}
+
+/// Result of `Runtime.evaluate`; `value` is only set when `returnByValue` was requested.
+#[derive(Debug, Clone, Default)]
+pub struct EvaluateResult {
+    pub value: Option<Value>,
+    pub description: Option<String>,
+    pub exception: Option<ExceptionDetails>,
+}
+
+impl EvaluateResult {
+    fn from_reply(reply: &Value) -> Self {
+        let remote = &reply["result"];
+        EvaluateResult {
+            value: remote.get("value").cloned(),
+            description: remote["description"].as_str().map(str::to_string),
+            exception: reply
+                .get("exceptionDetails")
+                .and_then(|e| serde_json::from_value(e.clone()).ok()),
+        }
+    }
+}
+
+/// An event (a message without an `id`) received on a DevTools connection.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CdpEvent {
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+}
+
+struct Subscriber {
+    // Exact method name, a `Domain.` prefix, or `None` for every event.
+    method: Option<String>,
+    session_id: Option<String>,
+    sender: mpsc::UnboundedSender<CdpEvent>,
+}
+
+impl Subscriber {
+    fn matches(&self, event: &CdpEvent) -> bool {
+        let method_matches = match &self.method {
+            None => true,
+            Some(filter) if filter.ends_with('.') => event.method.starts_with(filter.as_str()),
+            Some(filter) => *filter == event.method,
+        };
+        let session_matches = match &self.session_id {
+            None => true,
+            Some(filter) => event.session_id.as_deref() == Some(filter.as_str()),
+        };
+        method_matches && session_matches
+    }
+}
+
+// Both are `None` once the socket has closed, so registering and closing can't interleave.
+type Pending = Arc<Mutex<Option<HashMap<usize, oneshot::Sender<Value>>>>>;
+type Subscribers = Arc<Mutex<Option<Vec<Subscriber>>>>;
+
+struct Inner {
+    sink: tokio::sync::Mutex<SplitSink<Socket, Message>>,
+    pending: Pending,
+    subscribers: Subscribers,
+    reader: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+// Routes every incoming message until the socket closes. Dropping the pending
+// senders and subscribers on exit wakes their owners with `Closed`/`None`.
+async fn read_loop(mut stream: SplitStream<Socket>, pending: Pending, subscribers: Subscribers) {
+    while let Some(msg) = stream.next().await {
+        let txt = match msg {
+            Ok(Message::Text(txt)) => txt,
+            Ok(Message::Close(_)) => break,
+            Ok(_) => continue,
+            Err(e) => {
+                log::debug!("DevTools WebSocket error: {}", e);
+                break;
+            }
+        };
+        let json: Value = match serde_json::from_str(&txt) {
+            Ok(json) => json,
+            Err(e) => {
+                log::debug!("Ignoring unparsable DevTools message ({}): {}", e, txt);
+                continue;
+            }
+        };
+        if let Some(id) = json.get("id").and_then(Value::as_u64) {
+            let waiter = pending
+                .lock()
+                .unwrap()
+                .as_mut()
+                .and_then(|pending| pending.remove(&(id as usize)));
+            match waiter {
+                Some(waiter) => {
+                    let _ = waiter.send(json);
+                }
+                None => log::debug!("CDP <- (no waiter) {}", txt),
+            }
+        } else if let Ok(event) = serde_json::from_value::<CdpEvent>(json)
+            && let Some(subscribers) = subscribers.lock().unwrap().as_mut()
+        {
+            subscribers.retain(|subscriber| {
+                !subscriber.matches(&event) || subscriber.sender.send(event.clone()).is_ok()
+            });
+        }
+    }
+    *pending.lock().unwrap() = None;
+    *subscribers.lock().unwrap() = None;
+}
+
+/// Handle to one DevTools WebSocket; clones share the connection.
+#[derive(Clone)]
+pub struct CdpClient {
+    inner: Arc<Inner>,
+    timeout: Duration,
+}
+
+impl CdpClient {
+    /// Connects to an arbitrary DevTools WebSocket URL.
+    pub async fn connect(ws_url: &str) -> CdpResult<Self> {
+        let (socket, _) = connect_async(ws_url).await?;
+        log::debug!("Connected to DevTools WebSocket: {}", ws_url);
+        let (sink, stream) = socket.split();
+        let pending = Pending::new(Mutex::new(Some(HashMap::new())));
+        let subscribers = Subscribers::new(Mutex::new(Some(Vec::new())));
+        let reader = tokio::spawn(read_loop(stream, pending.clone(), subscribers.clone()));
+        Ok(CdpClient {
+            inner: Arc::new(Inner {
+                sink: tokio::sync::Mutex::new(sink),
+                pending,
+                subscribers,
+                reader,
+            }),
+            timeout: DEFAULT_TIMEOUT,
+        })
+    }
+
+    /// Connects to the browser-level endpoint advertised by `/json/version`.
+    pub async fn connect_browser(endpoint: &str) -> CdpResult<Self> {
+        let ws_url = browser_ws_url(endpoint).await?;
+        Self::connect(&ws_url).await
+    }
+
+    /// Connects directly to a page target.
+    pub async fn connect_page(endpoint: &str, target_id: &str) -> CdpResult<Self> {
+        Self::connect(&format!("ws://{}/devtools/page/{}", endpoint, target_id)).await
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Whether the socket is gone.
+    pub fn is_closed(&self) -> bool {
+        self.inner.subscribers.lock().unwrap().is_none()
+    }
+
+    /// Sends `method` and waits for the reply carrying the same id.
+    pub async fn call(&self, method: &str, params: Value) -> CdpResult<Value> {
+        self.call_with_session(None, method, params).await
+    }
+
+    /// Like `call`, but routed to a flattened session from `Target.attachToTarget`.
+    pub async fn call_with_session(
+        &self,
+        session_id: Option<&str>,
+        method: &str,
+        params: Value,
+    ) -> CdpResult<Value> {
+        let id = get_unique_id();
+        let mut request = json!({ "id": id, "method": method, "params": params });
+        if let Some(session_id) = session_id {
+            request["sessionId"] = json!(session_id);
+        }
+
+        let (sender, receiver) = oneshot::channel();
+        match self.inner.pending.lock().unwrap().as_mut() {
+            Some(pending) => pending.insert(id, sender),
+            None => return Err(CdpError::Closed),
+        };
+        log::debug!("CDP -> {}", request);
+        let sent = self
+            .inner
+            .sink
+            .lock()
+            .await
+            .send(Message::Text(request.to_string().into()))
+            .await;
+        if let Err(e) = sent {
+            self.forget(id);
+            return Err(e.into());
+        }
+
+        let reply = match tokio::time::timeout(self.timeout, receiver).await {
+            Ok(Ok(reply)) => reply,
+            Ok(Err(_)) => return Err(CdpError::Closed),
+            Err(_) => {
+                self.forget(id);
+                return Err(CdpError::Timeout(method.to_string()));
+            }
+        };
+
+        if let Some(error) = reply.get("error") {
+            return Err(CdpError::Protocol {
+                method: method.to_string(),
+                code: error["code"].as_i64().unwrap_or_default(),
+                message: error["message"].as_str().unwrap_or_default().to_string(),
+            });
+        }
+        Ok(reply["result"].clone())
+    }
+
+    // Drops the waiter of a call that gave up.
+    fn forget(&self, id: usize) {
+        if let Some(pending) = self.inner.pending.lock().unwrap().as_mut() {
+            pending.remove(&id);
+        }
+    }
+
+    /// Receives events whose method equals `method` (or starts with it when it
+    /// ends in `.`, e.g. `Network.`) and whose sessionId equals `session_id`.
+    /// `None` matches anything. The stream ends when the socket closes.
+    pub fn subscribe(
+        &self,
+        method: Option<&str>,
+        session_id: Option<&str>,
+    ) -> mpsc::UnboundedReceiver<CdpEvent> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        // Dropping `sender` on a closed socket ends the stream at once.
+        if let Some(subscribers) = self.inner.subscribers.lock().unwrap().as_mut() {
+            subscribers.push(Subscriber {
+                method: method.map(str::to_string),
+                session_id: session_id.map(str::to_string),
+                sender,
+            });
+        }
+        receiver
+    }
+
+    /// Resolves once the socket is gone, e.g. because its target was closed.
+    pub async fn closed(&self) {
+        let mut events = self.subscribe(None, None);
+        while events.recv().await.is_some() {}
+    }
+
+    /// Page-level commands against this connection (a page WebSocket).
+    pub fn page(&self) -> CdpSession {
+        CdpSession {
+            client: self.clone(),
+            session_id: None,
+        }
+    }
+
+    /// Page-level commands for an already attached flattened session.
+    pub fn session(&self, session_id: &str) -> CdpSession {
+        CdpSession {
+            client: self.clone(),
+            session_id: Some(session_id.to_string()),
+        }
+    }
+
+    /// Attaches to `target_id` with `flatten: true` and returns a session over this connection.
+    pub async fn attach(&self, target_id: &str) -> CdpResult<CdpSession> {
+        let result = self
+            .call(
+                "Target.attachToTarget",
+                json!({ "targetId": target_id, "flatten": true }),
+            )
+            .await?;
+        let session_id = result["sessionId"]
+            .as_str()
+            .ok_or(CdpError::MissingField("sessionId"))?;
+        Ok(self.session(session_id))
+    }
+
+    pub async fn create_browser_context(&self) -> CdpResult<String> {
+        let result = self.call("Target.createBrowserContext", json!({})).await?;
+        result["browserContextId"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or(CdpError::MissingField("browserContextId"))
+    }
+
+    /// Creates a target and returns its targetId.
+    pub async fn create_target(&self, params: &CreateTarget) -> CdpResult<String> {
+        let result = self
+            .call("Target.createTarget", serde_json::to_value(params)?)
+            .await?;
+        result["targetId"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or(CdpError::MissingField("targetId"))
+    }
+
+    pub async fn activate_target(&self, target_id: &str) -> CdpResult<()> {
+        self.call("Target.activateTarget", json!({ "targetId": target_id }))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn close_target(&self, target_id: &str) -> CdpResult<()> {
+        self.call("Target.closeTarget", json!({ "targetId": target_id }))
+            .await?;
+        Ok(())
+    }
+
+    /// The id and bounds of the window showing `target_id`.
+    pub async fn window_for_target(&self, target_id: &str) -> CdpResult<(i64, WindowBounds)> {
+        let result = self
+            .call(
+                "Browser.getWindowForTarget",
+                json!({ "targetId": target_id }),
+            )
+            .await?;
+        let window_id = result["windowId"]
+            .as_i64()
+            .ok_or(CdpError::MissingField("windowId"))?;
+        let bounds = result
+            .get("bounds")
+            .cloned()
+            .ok_or(CdpError::MissingField("bounds"))?;
+        Ok((window_id, serde_json::from_value(bounds)?))
+    }
+
+    pub async fn window_bounds(&self, window_id: i64) -> CdpResult<WindowBounds> {
+        let result = self
+            .call("Browser.getWindowBounds", json!({ "windowId": window_id }))
+            .await?;
+        let bounds = result
+            .get("bounds")
+            .cloned()
+            .ok_or(CdpError::MissingField("bounds"))?;
+        Ok(serde_json::from_value(bounds)?)
+    }
+
+    pub async fn set_window_bounds(&self, window_id: i64, bounds: &WindowBounds) -> CdpResult<()> {
+        self.call(
+            "Browser.setWindowBounds",
+            json!({ "windowId": window_id, "bounds": bounds }),
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn close_browser(&self) -> CdpResult<()> {
+        self.call("Browser.close", json!({})).await?;
+        Ok(())
+    }
+}
+
+/// Page-scoped commands, either on a page WebSocket or a flattened session.
+#[derive(Clone)]
+pub struct CdpSession {
+    client: CdpClient,
+    session_id: Option<String>,
+}
+
+impl CdpSession {
+    pub fn session_id(&self) -> Option<&str> {
+        self.session_id.as_deref()
+    }
+
+    pub fn client(&self) -> &CdpClient {
+        &self.client
+    }
+
+    pub async fn call(&self, method: &str, params: Value) -> CdpResult<Value> {
+        self.client
+            .call_with_session(self.session_id.as_deref(), method, params)
+            .await
+    }
+
+    /// Events for this session only; see `CdpClient::subscribe`.
+    pub fn subscribe(&self, method: Option<&str>) -> mpsc::UnboundedReceiver<CdpEvent> {
+        self.client.subscribe(method, self.session_id.as_deref())
+    }
+
+    pub async fn enable_page(&self) -> CdpResult<()> {
+        self.call("Page.enable", json!({})).await?;
+        Ok(())
+    }
+
+    pub async fn enable_runtime(&self) -> CdpResult<()> {
+        self.call("Runtime.enable", json!({})).await?;
+        Ok(())
+    }
+
+    /// Reloads the page and returns once the new document has committed, i.e. the main
+    /// frame's `Page.frameNavigated` has arrived. `Page.reload` itself answers before that,
+    /// while the old document is still in place.
+    pub async fn reload(&self, timeout: Duration) -> CdpResult<()> {
+        let mut navigated = self.subscribe(Some("Page.frameNavigated"));
+        self.enable_page().await?;
+        self.call("Page.reload", json!({})).await?;
+        let committed = async {
+            while let Some(event) = navigated.recv().await {
+                if event.params["frame"].get("parentId").is_none() {
+                    return Ok(());
+                }
+            }
+            Err(CdpError::Closed)
+        };
+        tokio::time::timeout(timeout, committed)
+            .await
+            .map_err(|_| CdpError::Timeout("Page.frameNavigated".to_string()))?
+    }
+
+    pub async fn navigate(&self, url: &str) -> CdpResult<()> {
+        self.call("Page.navigate", json!({ "url": url })).await?;
+        Ok(())
+    }
+
+    /// Overrides the viewport via `Emulation.setDeviceMetricsOverride`; it lasts while this
+    /// connection stays open.
+    pub async fn set_device_metrics(
+        &self,
+        width: i32,
+        height: i32,
+        device_scale_factor: f64,
+        mobile: bool,
+    ) -> CdpResult<()> {
+        self.call(
+            "Emulation.setDeviceMetricsOverride",
+            json!({
+                "width": width,
+                "height": height,
+                "deviceScaleFactor": device_scale_factor,
+                "mobile": mobile,
+            }),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Prints the page and returns the decoded PDF bytes.
+    pub async fn print_to_pdf(&self, params: &PrintToPdf) -> CdpResult<Vec<u8>> {
+        use base64::Engine;
+
+        let result = self
+            .call("Page.printToPDF", serde_json::to_value(params)?)
+            .await?;
+        let data = result["data"]
+            .as_str()
+            .ok_or(CdpError::MissingField("data"))?;
+        Ok(base64::engine::general_purpose::STANDARD.decode(data)?)
+    }
+
+    /// Registers `source` to run in every document the page loads from now on, before the
+    /// page's own scripts, and returns its identifier. Chrome drops the registration when
+    /// this connection closes.
+    pub async fn add_script_on_new_document(&self, source: &str) -> CdpResult<String> {
+        let result = self
+            .call(
+                "Page.addScriptToEvaluateOnNewDocument",
+                json!({ "source": source }),
+            )
+            .await?;
+        result["identifier"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or(CdpError::MissingField("identifier"))
+    }
+
+    /// Evaluates `expression`, waits for the promise it returns (if any) to settle and
+    /// returns the result by value.
+    pub async fn evaluate_awaiting(&self, expression: &str) -> CdpResult<EvaluateResult> {
+        let reply = self
+            .call(
+                "Runtime.evaluate",
+                json!({
+                    "expression": expression,
+                    "awaitPromise": true,
+                    "returnByValue": true,
+                }),
+            )
+            .await?;
+        Ok(EvaluateResult::from_reply(&reply))
+    }
+
+    /// The full size of the page's content in CSS pixels, from `Page.getLayoutMetrics`.
+    pub async fn content_size(&self) -> CdpResult<(f64, f64)> {
+        let metrics = self.call("Page.getLayoutMetrics", json!({})).await?;
+        // `cssContentSize` is in CSS pixels; older Chromes only send `contentSize`.
+        let size = match metrics.get("cssContentSize") {
+            Some(size) => size,
+            None => &metrics["contentSize"],
+        };
+        match (size["width"].as_f64(), size["height"].as_f64()) {
+            (Some(width), Some(height)) => Ok((width, height)),
+            _ => Err(CdpError::MissingField("contentSize")),
+        }
+    }
+
+    /// Evaluates `expression` in the page's main world.
+    pub async fn evaluate(
+        &self,
+        expression: &str,
+        return_by_value: bool,
+    ) -> CdpResult<EvaluateResult> {
+        let reply = self
+            .call(
+                "Runtime.evaluate",
+                json!({ "expression": expression, "returnByValue": return_by_value }),
+            )
+            .await?;
+        Ok(EvaluateResult::from_reply(&reply))
+    }
+
+    /// Captures the viewport and returns the decoded PNG bytes.
+    pub async fn capture_screenshot(&self) -> CdpResult<Vec<u8>> {
+        self.capture_screenshot_with(&CaptureScreenshot::default())
+            .await
+    }
+
+    /// Captures a screenshot as `params` describe and returns the decoded image bytes.
+    pub async fn capture_screenshot_with(&self, params: &CaptureScreenshot) -> CdpResult<Vec<u8>> {
+        use base64::Engine;
+
+        let result = self
+            .call("Page.captureScreenshot", serde_json::to_value(params)?)
+            .await?;
+        let data = result["data"]
+            .as_str()
+            .ok_or(CdpError::MissingField("data"))?;
+        Ok(base64::engine::general_purpose::STANDARD.decode(data)?)
+    }
+}
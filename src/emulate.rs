@@ -0,0 +1,435 @@
+// `!device=`, `!ua=`, `!geo=`, `!tz=`, `!locale=`, `!offline`, `!throttle=` and
+// `!colorscheme=`: the environment a tab is emulated in.
+//
+// Everything is applied with `Emulation.*` and `Network.*` commands on one page connection.
+// Chrome drops the overrides when that connection closes, so whoever applies them has to stay
+// connected for as long as they should hold.
+use crate::cdp::{CdpResult, CdpSession};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::json;
+use std::fmt;
+use std::str::FromStr;
+
+/// A phone or tablet to emulate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Device {
+    pub name: &'static str,
+    /// CSS pixels, portrait.
+    pub width: u32,
+    pub height: u32,
+    pub device_scale_factor: f64,
+    pub mobile: bool,
+    pub touch: bool,
+    pub user_agent: &'static str,
+}
+
+const IPHONE_UA: &str = "Mozilla/5.0 (iPhone; CPU iPhone OS 16_6 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/16.6 Mobile/15E148 Safari/604.1";
+const IPAD_UA: &str = "Mozilla/5.0 (iPad; CPU OS 16_6 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/16.6 Mobile/15E148 Safari/604.1";
+const PIXEL_UA: &str = "Mozilla/5.0 (Linux; Android 14; Pixel 7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Mobile Safari/537.36";
+const GALAXY_UA: &str = "Mozilla/5.0 (Linux; Android 13; SM-G981B) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Mobile Safari/537.36";
+const GALAXY_TAB_UA: &str = "Mozilla/5.0 (Linux; Android 13; SM-X700) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Safari/537.36";
+
+/// The `!device=` presets.
+pub const DEVICES: &[Device] = &[
+    Device {
+        name: "iphone-se",
+        width: 375,
+        height: 667,
+        device_scale_factor: 2.0,
+        mobile: true,
+        touch: true,
+        user_agent: IPHONE_UA,
+    },
+    Device {
+        name: "iphone-14",
+        width: 390,
+        height: 844,
+        device_scale_factor: 3.0,
+        mobile: true,
+        touch: true,
+        user_agent: IPHONE_UA,
+    },
+    Device {
+        name: "iphone-14-pro-max",
+        width: 430,
+        height: 932,
+        device_scale_factor: 3.0,
+        mobile: true,
+        touch: true,
+        user_agent: IPHONE_UA,
+    },
+    Device {
+        name: "pixel-7",
+        width: 412,
+        height: 915,
+        device_scale_factor: 2.625,
+        mobile: true,
+        touch: true,
+        user_agent: PIXEL_UA,
+    },
+    Device {
+        name: "galaxy-s20",
+        width: 360,
+        height: 800,
+        device_scale_factor: 3.0,
+        mobile: true,
+        touch: true,
+        user_agent: GALAXY_UA,
+    },
+    Device {
+        name: "ipad-mini",
+        width: 768,
+        height: 1024,
+        device_scale_factor: 2.0,
+        mobile: true,
+        touch: true,
+        user_agent: IPAD_UA,
+    },
+    Device {
+        name: "ipad-pro",
+        width: 1024,
+        height: 1366,
+        device_scale_factor: 2.0,
+        mobile: true,
+        touch: true,
+        user_agent: IPAD_UA,
+    },
+    Device {
+        name: "galaxy-tab-s8",
+        width: 800,
+        height: 1280,
+        device_scale_factor: 2.25,
+        mobile: true,
+        touch: true,
+        user_agent: GALAXY_TAB_UA,
+    },
+];
+
+impl FromStr for Device {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        DEVICES
+            .iter()
+            .find(|device| device.name.eq_ignore_ascii_case(s))
+            .copied()
+            .ok_or_else(|| {
+                let names: Vec<&str> = DEVICES.iter().map(|device| device.name).collect();
+                format!("unknown device {:?}; use {}", s, names.join(", "))
+            })
+    }
+}
+
+impl fmt::Display for Device {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// `!geo=<latitude>,<longitude>[,<accuracy in meters>]`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Geolocation {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub accuracy: f64,
+}
+
+impl FromStr for Geolocation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let numbers: Option<Vec<f64>> = s
+            .split(',')
+            .map(|part| part.trim().parse::<f64>().ok().filter(|n| n.is_finite()))
+            .collect();
+        let location = match numbers.as_deref() {
+            Some(&[latitude, longitude]) => Some((latitude, longitude, 100.0)),
+            Some(&[latitude, longitude, accuracy]) if accuracy >= 0.0 => {
+                Some((latitude, longitude, accuracy))
+            }
+            _ => None,
+        };
+        match location {
+            Some((latitude, longitude, accuracy))
+                if (-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude) =>
+            {
+                Ok(Geolocation {
+                    latitude,
+                    longitude,
+                    accuracy,
+                })
+            }
+            _ => Err(format!(
+                "invalid location {:?}: expected <latitude>,<longitude>[,<accuracy>]",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Geolocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{},{}", self.latitude, self.longitude, self.accuracy)
+    }
+}
+
+/// Named network speeds for `!throttle=`: latency in ms, download and upload in kbit/s.
+pub const THROTTLE_PRESETS: &[(&str, f64, f64, f64)] = &[
+    ("slow-3g", 2000.0, 400.0, 400.0),
+    ("3g", 562.5, 1440.0, 675.0),
+    ("4g", 165.0, 8100.0, 1350.0),
+];
+
+/// `!throttle=3g`, or `<download kbit/s>/<upload kbit/s>/<latency ms>`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Throttle {
+    pub latency_ms: f64,
+    pub download_kbps: f64,
+    pub upload_kbps: f64,
+}
+
+impl FromStr for Throttle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let preset = THROTTLE_PRESETS
+            .iter()
+            .find(|(name, ..)| name.eq_ignore_ascii_case(s));
+        if let Some((_, latency_ms, download_kbps, upload_kbps)) = preset {
+            return Ok(Throttle {
+                latency_ms: *latency_ms,
+                download_kbps: *download_kbps,
+                upload_kbps: *upload_kbps,
+            });
+        }
+        let numbers: Option<Vec<f64>> = s
+            .split('/')
+            .map(|part| part.trim().parse::<f64>().ok().filter(|n| *n >= 0.0))
+            .collect();
+        match numbers.as_deref() {
+            Some(&[download_kbps, upload_kbps, latency_ms]) => Ok(Throttle {
+                latency_ms,
+                download_kbps,
+                upload_kbps,
+            }),
+            _ => Err(format!(
+                "invalid throttle {:?}: expected slow-3g, 3g, 4g or <down kbps>/<up kbps>/<latency ms>",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Throttle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let preset = THROTTLE_PRESETS.iter().find(|(_, latency, down, up)| {
+            *latency == self.latency_ms && *down == self.download_kbps && *up == self.upload_kbps
+        });
+        match preset {
+            Some((name, ..)) => write!(f, "{}", name),
+            None => write!(
+                f,
+                "{}/{}/{}",
+                self.download_kbps, self.upload_kbps, self.latency_ms
+            ),
+        }
+    }
+}
+
+/// `prefers-color-scheme` for `!colorscheme=`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorScheme {
+    Light,
+    Dark,
+}
+
+impl FromStr for ColorScheme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "light" => Ok(ColorScheme::Light),
+            "dark" => Ok(ColorScheme::Dark),
+            _ => Err(format!("invalid color scheme {:?}: use light or dark", s)),
+        }
+    }
+}
+
+impl fmt::Display for ColorScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColorScheme::Light => write!(f, "light"),
+            ColorScheme::Dark => write!(f, "dark"),
+        }
+    }
+}
+
+// Written the same way in a URL, on the command line and as a JSON string.
+macro_rules! serde_as_str {
+    ($type:ty) => {
+        impl Serialize for $type {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $type {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                String::deserialize(deserializer)?
+                    .parse()
+                    .map_err(serde::de::Error::custom)
+            }
+        }
+    };
+}
+
+serde_as_str!(Device);
+serde_as_str!(Geolocation);
+serde_as_str!(Throttle);
+serde_as_str!(ColorScheme);
+
+/// Everything to emulate in one tab.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Emulation {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device: Option<Device>,
+    /// Overrides the device's user agent.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub geolocation: Option<Geolocation>,
+    /// IANA time zone, e.g. `Europe/Berlin`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    /// BCP 47 locale, e.g. `de-DE`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+    #[serde(skip_serializing_if = "is_false")]
+    pub offline: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub throttle: Option<Throttle>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color_scheme: Option<ColorScheme>,
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+impl Emulation {
+    pub fn is_empty(&self) -> bool {
+        *self == Emulation::default()
+    }
+
+    /// Sends the overrides to `page`. They hold while its connection stays open.
+    pub async fn apply(&self, page: &CdpSession) -> CdpResult<()> {
+        if let Some(device) = &self.device {
+            page.call(
+                "Emulation.setDeviceMetricsOverride",
+                json!({
+                    "width": device.width,
+                    "height": device.height,
+                    "deviceScaleFactor": device.device_scale_factor,
+                    "mobile": device.mobile,
+                    "screenWidth": device.width,
+                    "screenHeight": device.height,
+                }),
+            )
+            .await?;
+            page.call(
+                "Emulation.setTouchEmulationEnabled",
+                json!({ "enabled": device.touch, "maxTouchPoints": if device.touch { 5 } else { 0 } }),
+            )
+            .await?;
+        }
+        let user_agent = self
+            .user_agent
+            .clone()
+            .or_else(|| self.device.map(|device| device.user_agent.to_string()));
+        // `navigator.language` and `Accept-Language` come with the user agent override.
+        let user_agent = match (user_agent, &self.locale) {
+            (Some(user_agent), _) => Some(user_agent),
+            (None, Some(_)) => page
+                .call("Browser.getVersion", json!({}))
+                .await?
+                .get("userAgent")
+                .and_then(|user_agent| user_agent.as_str())
+                .map(str::to_string),
+            (None, None) => None,
+        };
+        if let Some(user_agent) = user_agent {
+            let mut params = json!({ "userAgent": user_agent });
+            if let Some(locale) = &self.locale {
+                params["acceptLanguage"] = json!(locale);
+            }
+            page.call("Emulation.setUserAgentOverride", params).await?;
+        }
+        if let Some(locale) = &self.locale {
+            page.call("Emulation.setLocaleOverride", json!({ "locale": locale }))
+                .await?;
+        }
+        if let Some(timezone) = &self.timezone {
+            page.call(
+                "Emulation.setTimezoneOverride",
+                json!({ "timezoneId": timezone }),
+            )
+            .await?;
+        }
+        if let Some(location) = &self.geolocation {
+            // Without the permission the page would still have to ask for the position.
+            if let Err(e) = page
+                .call(
+                    "Browser.grantPermissions",
+                    json!({ "permissions": ["geolocation"] }),
+                )
+                .await
+            {
+                log::debug!("Could not grant the geolocation permission: {}", e);
+            }
+            page.call(
+                "Emulation.setGeolocationOverride",
+                json!({
+                    "latitude": location.latitude,
+                    "longitude": location.longitude,
+                    "accuracy": location.accuracy,
+                }),
+            )
+            .await?;
+        }
+        if let Some(scheme) = &self.color_scheme {
+            page.call(
+                "Emulation.setEmulatedMedia",
+                json!({ "features": [{ "name": "prefers-color-scheme", "value": scheme.to_string() }] }),
+            )
+            .await?;
+        }
+        if self.offline || self.throttle.is_some() {
+            let throttle = self.throttle.unwrap_or(Throttle {
+                latency_ms: 0.0,
+                download_kbps: 0.0,
+                upload_kbps: 0.0,
+            });
+            // Throughput is in bytes per second; -1 turns throttling off.
+            let bytes_per_second = |kbps: f64| {
+                if kbps > 0.0 {
+                    kbps * 1000.0 / 8.0
+                } else {
+                    -1.0
+                }
+            };
+            page.call("Network.enable", json!({})).await?;
+            page.call(
+                "Network.emulateNetworkConditions",
+                json!({
+                    "offline": self.offline,
+                    "latency": throttle.latency_ms,
+                    "downloadThroughput": bytes_per_second(throttle.download_kbps),
+                    "uploadThroughput": bytes_per_second(throttle.upload_kbps),
+                }),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+}
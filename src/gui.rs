@@ -1,6 +1,8 @@
 #![allow(clippy::use_self)]
 
 use dashmap::DashMap;
+use debugchrome::bang::BangUrl;
+use debugchrome::{CdpClient, CdpEndpoint, CdpEvent};
 use eframe::{App, CreationContext};
 use egui::Color32;
 use futures_util::SinkExt;
@@ -9,19 +11,23 @@ use once_cell::sync::OnceCell;
 use salvo::prelude::*;
 use serde_json::json;
 use std::sync::Arc;
-use tokio::sync::mpsc;
 use tokio::sync::watch;
 use tokio_tungstenite::{connect_async, tungstenite::Message};
-use which::which;
 
+// The egui-snarl demo this console grew out of; none of it is wired up yet.
+#[allow(dead_code)]
 const STRING_COLOR: Color32 = Color32::from_rgb(0x00, 0xb0, 0x00);
+#[allow(dead_code)]
 const NUMBER_COLOR: Color32 = Color32::from_rgb(0xb0, 0x00, 0x00);
+#[allow(dead_code)]
 const IMAGE_COLOR: Color32 = Color32::from_rgb(0xb0, 0x00, 0xb0);
+#[allow(dead_code)]
 const UNTYPED_COLOR: Color32 = Color32::from_rgb(0xb0, 0xb0, 0xb0);
 
 #[cfg(feature = "uses_funny")]
 use crate::jokes;
 
+#[allow(dead_code, private_interfaces)]
 #[derive(Clone, serde::Serialize, serde::Deserialize, Debug)]
 pub enum DemoNode {
     /// Node with single input.
@@ -43,6 +49,7 @@ pub enum DemoNode {
     ExprNode(ExprNode),
 }
 
+#[allow(dead_code)]
 impl DemoNode {
     const fn name(&self) -> &str {
         match self {
@@ -100,6 +107,7 @@ impl DemoNode {
     }
 }
 
+#[allow(dead_code)]
 struct DemoViewer;
 
 // impl SnarlViewer<DemoNode> for DemoViewer {
@@ -647,6 +655,7 @@ struct DemoViewer;
 //     }
 // }
 
+#[allow(dead_code)]
 #[derive(Clone, serde::Serialize, serde::Deserialize, Debug)]
 struct ExprNode {
     text: String,
@@ -655,6 +664,7 @@ struct ExprNode {
     expr: Expr,
 }
 
+#[allow(dead_code)]
 impl ExprNode {
     fn new() -> Self {
         ExprNode {
@@ -670,12 +680,14 @@ impl ExprNode {
     }
 }
 
+#[allow(dead_code)]
 #[derive(Clone, Copy, serde::Serialize, serde::Deserialize, Debug)]
 enum UnOp {
     Pos,
     Neg,
 }
 
+#[allow(dead_code)]
 #[derive(Clone, Copy, serde::Serialize, serde::Deserialize, Debug)]
 enum BinOp {
     Add,
@@ -684,6 +696,7 @@ enum BinOp {
     Div,
 }
 
+#[allow(dead_code)]
 #[derive(Clone, serde::Serialize, serde::Deserialize, Debug)]
 enum Expr {
     Var(String),
@@ -699,6 +712,7 @@ enum Expr {
     },
 }
 
+#[allow(dead_code)]
 impl Expr {
     fn eval(&self, bindings: &[String], args: &[f64]) -> f64 {
         let binding_index =
@@ -823,6 +837,7 @@ impl syn::parse::Parse for Expr {
     }
 }
 
+#[allow(dead_code)]
 impl Expr {
     fn parse_with_unop(op: UnOp, input: syn::parse::ParseStream) -> syn::Result<Self> {
         let lookahead = input.lookahead1();
@@ -940,7 +955,7 @@ pub struct DemoApp {
 }
 
 impl DemoApp {
-    pub fn new(cx: &CreationContext) -> Self {
+    pub fn new(cx: &CreationContext, cdp_endpoint: CdpEndpoint) -> Self {
         println!("Initializing DemoApp...");
         // Set the default theme to dark
         // Set the default theme to dark if no theme is set in the context.
@@ -955,7 +970,10 @@ impl DemoApp {
         cx.egui_ctx.style_mut(|style| style.animation_time *= 10.0);
 
         // Initialize shared state
-        let shared_state = Arc::new(SharedState::default());
+        let shared_state = Arc::new(SharedState {
+            cdp_endpoint,
+            ..Default::default()
+        });
         // Retrieve the HWND after the GUI is initialized
         let hwnd = get_current_window_handle();
         if hwnd == 0 {
@@ -1019,7 +1037,7 @@ impl DemoApp {
         // Start the Salvo server in a separate task
         let server_state = shared_state.clone();
         tokio::spawn(async move {
-            start_server(server_state).await;
+            start_server(server_state, SERVER_ADDR).await;
         });
 
         // Create a channel to signal updates to the egui app
@@ -1074,6 +1092,7 @@ impl DemoApp {
         // Exit the application
         // std::process::exit(0);
     }
+    #[allow(dead_code)]
     pub fn set_update_receiver(&mut self, receiver: Receiver<()>) {
         self.update_receiver = Some(receiver);
     }
@@ -1100,7 +1119,7 @@ impl App for DemoApp {
                 .fixed_pos(egui::Pos2::ZERO) // Start at the top-left corner
                 .show(ctx, |ui| {
                     let screen_rect = ctx.screen_rect();
-                    ui.allocate_ui_at_rect(screen_rect, |ui| {
+                    ui.allocate_new_ui(egui::UiBuilder::new().max_rect(screen_rect), |ui| {
                         ui.painter().rect_filled(
                             screen_rect,
                             0.0,
@@ -1115,7 +1134,7 @@ impl App for DemoApp {
                 .show(ctx, |ui| {
                     egui::Frame::window(&ctx.style())
                         .fill(egui::Color32::from_rgb(240, 240, 240)) // Modal background color
-                        .rounding(10.0) // Rounded corners
+                        .corner_radius(10.0) // Rounded corners
                         .show(ui, |ui| {
                             ui.vertical_centered(|ui| {
                                 ui.heading("Chrome Disconnected");
@@ -1162,7 +1181,7 @@ impl App for DemoApp {
             egui::menu::bar(ui, |ui| {
                 #[cfg(not(target_arch = "wasm32"))]
                 {
-                    ui.menu_button("Quit", |ui| {
+                    ui.menu_button("Quit", |_ui| {
                         // if ui.button("Quit").clicked() {
                         ctx.send_viewport_cmd(egui::ViewportCommand::Close);
                         // }
@@ -1206,7 +1225,12 @@ impl App for DemoApp {
                             let mut connected = false;
 
                             while attempts < max_attempts {
-                                match reqwest::get("http://localhost:9222/json").await {
+                                match reqwest::get(format!(
+                                    "http://{}/json",
+                                    shared_state.cdp_endpoint
+                                ))
+                                .await
+                                {
                                     Ok(response) => {
                                         if response.status().is_success() {
                                             println!("Connected to Chrome DevTools Protocol.");
@@ -1272,34 +1296,30 @@ impl App for DemoApp {
                         ui.separator();
 
                         // Button to start monitoring
-                        if ui.button("Start Monitoring").clicked() {
-                            if !is_running {
-                                let stop_monitoring_tx = self.stop_monitoring.clone();
-                                let shared_state = self.shared_state.clone();
-                                let ctx_clone = ctx.clone();
-
-                                tokio::spawn(async move {
-                                    shared_state.monitoring_state.set_running(true);
-                                    if let Err(err) = monitor_chrome_and_update_shared_state(
-                                        shared_state,
-                                        stop_monitoring_tx.subscribe(),
-                                        &ctx_clone,
-                                    )
-                                    .await
-                                    {
-                                        eprintln!("Error starting monitoring: {:?}", err);
-                                    }
-                                });
-                            }
+                        if ui.button("Start Monitoring").clicked() && !is_running {
+                            let stop_monitoring_tx = self.stop_monitoring.clone();
+                            let shared_state = self.shared_state.clone();
+                            let ctx_clone = ctx.clone();
+
+                            tokio::spawn(async move {
+                                shared_state.monitoring_state.set_running(true);
+                                if let Err(err) = monitor_chrome_and_update_shared_state(
+                                    shared_state,
+                                    stop_monitoring_tx.subscribe(),
+                                    &ctx_clone,
+                                )
+                                .await
+                                {
+                                    eprintln!("Error starting monitoring: {:?}", err);
+                                }
+                            });
                         }
 
                         // Button to stop monitoring
-                        if ui.button("Stop Monitoring").clicked() {
-                            if is_running {
-                                self.shared_state.monitoring_state.set_running(false);
-                                if let Err(err) = self.stop_monitoring.send(true) {
-                                    eprintln!("Failed to send stop signal: {:?}", err);
-                                }
+                        if ui.button("Stop Monitoring").clicked() && is_running {
+                            self.shared_state.monitoring_state.set_running(false);
+                            if let Err(err) = self.stop_monitoring.send(true) {
+                                eprintln!("Failed to send stop signal: {:?}", err);
                             }
                         }
                     });
@@ -1411,7 +1431,7 @@ impl App for DemoApp {
         // });
 
         // Right panel for port number, messages, and tabs
-        let shared_port = {
+        let _shared_port = {
             self.shared_state.port.try_lock() // Use blocking_lock to avoid async context
         };
         egui::SidePanel::right("right_panel")
@@ -1455,9 +1475,9 @@ impl App for DemoApp {
                             // tabs_to_remove.push(tab.key().clone());
                             let target_id = tab.target_id.clone();
                             let url = tab.url.clone();
-                            let ws_stream = self.shared_state.clone(); // Clone shared state for async task
+                            let endpoint = self.shared_state.cdp_endpoint.clone();
                             tokio::spawn(async move {
-                                if let Err(err) = close_target(&target_id, &url).await {
+                                if let Err(err) = close_target(&endpoint, &target_id, &url).await {
                                     eprintln!("Failed to close target {}: {:?}", target_id, err);
                                 } else {
                                     println!("Target {} closed successfully", target_id);
@@ -1524,10 +1544,9 @@ impl App for DemoApp {
             if ui.button("debugchrome://http://127.0.0.1:5800").clicked() {
                 // Launch debugchrome programmatically
                 let url = "debugchrome://http://127.0.0.1:5800";
-                if let Err(err) = std::process::Command::new("debugchrome.exe")
-                    .arg(url)
-                    .spawn()
-                {
+                let exe = std::env::current_exe()
+                    .unwrap_or_else(|_| std::path::PathBuf::from("debugchrome"));
+                if let Err(err) = std::process::Command::new(exe).arg(url).spawn() {
                     eprintln!("Failed to launch DebugChrome: {}", err);
                 }
             }
@@ -1566,7 +1585,7 @@ impl App for DemoApp {
         });
     }
 
-    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+    fn save(&mut self, _storage: &mut dyn eframe::Storage) {
         println!("Saving state...");
     }
 }
@@ -1575,6 +1594,7 @@ impl App for DemoApp {
 //#[cfg(not(target_arch = "wasm32"))]
 
 //#[cfg(not(target_arch = "wasm32"))]
+#[allow(dead_code)]
 #[tokio::main]
 async fn main() -> eframe::Result<()> {
     let native_options = eframe::NativeOptions {
@@ -1587,7 +1607,7 @@ async fn main() -> eframe::Result<()> {
     eframe::run_native(
         "egui-snarl demo",
         native_options,
-        Box::new(|cx| Ok(Box::new(DemoApp::new(cx)))),
+        Box::new(|cx| Ok(Box::new(DemoApp::new(cx, CdpEndpoint::from_env())))),
     )
 }
 
@@ -1612,13 +1632,14 @@ fn main() {
             .start(
                 canvas,
                 web_options,
-                Box::new(|cx| Ok(Box::new(DemoApp::new(cx)))),
+                Box::new(|cx| Ok(Box::new(DemoApp::new(cx, CdpEndpoint::from_env())))),
             )
             .await
             .expect("failed to start eframe");
     });
 }
 
+#[allow(dead_code)]
 fn format_float(v: f64) -> String {
     let v = (v * 1000.0).round() / 1000.0;
     format!("{v}")
@@ -1650,6 +1671,7 @@ pub struct SharedState {
     pub show_modal_disconnect: AtomicBool, // Atomic flag for modal dialog
     pub shutdown_signal: watch::Sender<bool>, // Add shutdown signal
     pub monitoring_state: MonitoringState,
+    pub cdp_endpoint: CdpEndpoint, // DevTools endpoint the console monitors
 }
 
 impl Default for SharedState {
@@ -1664,6 +1686,7 @@ impl Default for SharedState {
             show_modal_disconnect: AtomicBool::new(false),
             shutdown_signal,
             monitoring_state: MonitoringState::new(),
+            cdp_endpoint: CdpEndpoint::from_env(),
         }
     }
 }
@@ -1714,8 +1737,107 @@ async fn update_tabs_handler(req: &mut Request, depot: &mut Depot) -> String {
     "Tabs updated!".to_string()
 }
 
+// Handler to open a link the way `debugchrome <url>` would. The body is a `BangUrl`:
+// `{"url": "https://example.com", "options": {"id": "7", "w": "50%", "keep_focus": true}}`.
+// The options go to a `debugchrome --json` child as `--<bang>` flags and its report is returned.
+#[handler]
+async fn open_handler(req: &mut Request, depot: &mut Depot, res: &mut Response) {
+    let open: BangUrl = match req.parse_json().await {
+        Ok(open) => open,
+        Err(e) => {
+            res.status_code(StatusCode::BAD_REQUEST);
+            res.render(Json(
+                json!({ "status": "error", "errors": [e.to_string()] }),
+            ));
+            return;
+        }
+    };
+    // The url is the child's first argument; a flag such as `--unregister` there would run a
+    // management command instead of opening anything.
+    if !is_openable(&open.url) {
+        res.status_code(StatusCode::BAD_REQUEST);
+        res.render(Json(json!({
+            "status": "error",
+            "errors": [format!("not an http(s), file or debugchrome url: {}", open.url)],
+        })));
+        return;
+    }
+    // The child would only answer once the tab closed.
+    if open.options.tail {
+        res.status_code(StatusCode::BAD_REQUEST);
+        res.render(Json(
+            json!({ "status": "error", "errors": ["tail is not supported by POST /open"] }),
+        ));
+        return;
+    }
+    let exe = std::env::current_exe().unwrap_or_else(|_| std::path::PathBuf::from("debugchrome"));
+    let mut args = vec![open.url];
+    args.extend(open.options.to_args());
+    args.push("--json".to_string());
+    if let Ok(state) = depot.obtain::<Arc<SharedState>>() {
+        args.push("--cdp-endpoint".to_string());
+        args.push(state.cdp_endpoint.to_string());
+    }
+    let output =
+        tokio::task::spawn_blocking(move || std::process::Command::new(exe).args(args).output())
+            .await;
+    match output {
+        Ok(Ok(output)) => match serde_json::from_slice::<serde_json::Value>(&output.stdout) {
+            Ok(report) => {
+                if !output.status.success() {
+                    res.status_code(StatusCode::UNPROCESSABLE_ENTITY);
+                }
+                res.render(Json(report));
+            }
+            Err(e) => {
+                res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+                res.render(Json(json!({
+                    "status": "error",
+                    "errors": [format!("unreadable report: {}", e)],
+                    "stderr": String::from_utf8_lossy(&output.stderr),
+                })));
+            }
+        },
+        Ok(Err(e)) => {
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(
+                json!({ "status": "error", "errors": [e.to_string()] }),
+            ));
+        }
+        Err(e) => {
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(
+                json!({ "status": "error", "errors": [e.to_string()] }),
+            ));
+        }
+    }
+}
+
+// Whether `url` opens a page: http(s) or file, optionally behind `debugchrome:`. A bare
+// `debugchrome:` would start another console.
+fn is_openable(url: &str) -> bool {
+    let inner = url
+        .strip_prefix("debugchrome:")
+        .map(|rest| rest.strip_prefix("//").unwrap_or(rest))
+        .unwrap_or(url);
+    url::Url::parse(inner).is_ok_and(|parsed| matches!(parsed.scheme(), "http" | "https" | "file"))
+}
+
+/// Where the HTTP API listens by default. `POST /open` drives the browser without any
+/// authentication, so it is only reachable from this machine unless `--serve <addr>` says so.
+pub const SERVER_ADDR: &str = "127.0.0.1:5800";
+
+/// Runs the HTTP API without the console window, for `debugchrome --serve [addr]`.
+pub async fn serve(cdp_endpoint: CdpEndpoint, addr: &str) {
+    let shared_state = Arc::new(SharedState {
+        cdp_endpoint,
+        ..Default::default()
+    });
+    start_server(shared_state, addr).await;
+}
+
 // Function to start the Salvo server
-pub async fn start_server(shared_state: Arc<SharedState>) {
+pub async fn start_server(shared_state: Arc<SharedState>, addr: &str) {
     let mut shutdown_signal = shared_state.shutdown_signal.subscribe(); // Subscribe to the shutdown signal
 
     let router = Router::new()
@@ -1723,10 +1845,11 @@ pub async fn start_server(shared_state: Arc<SharedState>) {
         .get(hello_handler)
         .push(Router::with_path("tabs").get(get_tabs_handler))
         .push(Router::with_path("tabs").post(update_tabs_handler))
+        .push(Router::with_path("open").post(open_handler))
         .push(Router::with_path("hello").get(hello_handler));
 
-    let acceptor = TcpListener::new("0.0.0.0:5800").bind().await;
-    println!("Salvo server running at http://127.0.0.1:5800");
+    let acceptor = TcpListener::new(addr.to_string()).bind().await;
+    println!("Salvo server running at http://{}", addr);
 
     let server = Server::new(acceptor);
 
@@ -1844,11 +1967,11 @@ pub async fn monitor_chrome_and_update_shared_state(
 
     loop {
         println!("Attempting to connect to Chrome DevTools Protocol...");
-        match reqwest::get("http://localhost:9222/json").await {
+        match reqwest::get(format!("http://{}/json", shared_state.cdp_endpoint)).await {
             Ok(response) => {
                 let tabs_json: Vec<serde_json::Value> = response.json().await?;
                 if let Some(url) = tabs_json
-                    .get(0)
+                    .first()
                     .and_then(|tab| tab.get("webSocketDebuggerUrl"))
                     .and_then(|url| url.as_str())
                 {
@@ -1884,98 +2007,62 @@ async fn monitor_chrome(
 ) -> Result<(), Box<dyn std::error::Error>> {
     // Initialize the shared state with the current tabs
     shared_state.tabs.clear();
-    let response = reqwest::get("http://localhost:9222/json").await?;
+    let response = reqwest::get(format!("http://{}/json", shared_state.cdp_endpoint)).await?;
     let tabs_json: Vec<serde_json::Value> = response.json().await?;
     for tab in &tabs_json {
-        if let Some(_target_id) = tab.get("id").and_then(|id| id.as_str()) {
-            if let Some(_url) = tab.get("url").and_then(|u| u.as_str()) {
-                let title = tab
-                    .get("title")
-                    .and_then(|t| t.as_str())
-                    .unwrap_or("")
-                    .to_string();
-                // shared_state.tabs.insert(
-                //     target_id.to_string(),
-                //     Tab {
-                //         target_id: target_id.to_string(),
-                //         url: url.to_string(),
-                //         bang_id: String::new(),
-                //         title: title1
-                //     },
-                // );
-            }
+        if let Some(_target_id) = tab.get("id").and_then(|id| id.as_str())
+            && let Some(_url) = tab.get("url").and_then(|u| u.as_str())
+        {
+            let _title = tab
+                .get("title")
+                .and_then(|t| t.as_str())
+                .unwrap_or("")
+                .to_string();
+            // shared_state.tabs.insert(
+            //     target_id.to_string(),
+            //     Tab {
+            //         target_id: target_id.to_string(),
+            //         url: url.to_string(),
+            //         bang_id: String::new(),
+            //         title: title1
+            //     },
+            // );
         }
     }
 
     // Connect to the WebSocket URL
     println!("Connecting to Chrome WebSocket: {}", browser_ws_url);
-    let (mut ws_stream, _) = connect_async(&browser_ws_url).await?;
+    let client = CdpClient::connect(&browser_ws_url).await?;
     println!("Connected to Chrome WebSocket: {}", browser_ws_url);
     #[cfg(target_os = "windows")]
     println!(
         "\n\n\nhwnd {:?}\n\n\n",
         crate::find_chrome_with_debug_port()
     );
-    // Enable target discovery
-    let enable_discovery = json!({
-        "id": 1,
-        "method": "Target.setDiscoverTargets",
-        "params": { "discover": true }
-    });
-    ws_stream
-        .send(Message::Text(enable_discovery.to_string().into()))
+    // Subscribe before enabling discovery so no targetCreated event is missed
+    let mut events = client.subscribe(None, None);
+    client
+        .call("Target.setDiscoverTargets", json!({ "discover": true }))
         .await?;
 
-    // Create a channel to handle incoming messages
-    let (tx, mut rx) = mpsc::unbounded_channel();
-
-    // Clone `shared_state` for the WebSocket task
-    let shared_state_ws = shared_state.clone();
-    let ws_stream = Arc::new(tokio::sync::Mutex::new(ws_stream));
-    let ws_stream_clone = ws_stream.clone();
-    let ctx_clone = ctx.clone();
-    tokio::spawn(async move {
-        let mut ws_stream = ws_stream_clone.lock().await;
-        while let Some(msg) = ws_stream.next().await {
-            match msg {
-                Ok(Message::Text(text)) => {
-                    println!("Received WebSocket message: {}", &text);
-                    process_cdp(
-                        &shared_state_ws,
-                        text.to_owned(),
-                        &ctx_clone,
-                        &mut ws_stream,
-                    )
-                    .await;
-                    if let Err(err) = tx.send(text) {
-                        eprintln!("Failed to send message to channel: {:?}", err);
-                    }
+    // Process incoming events and listen for stop signal
+    let mut stop_signal = stop_signal;
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Some(event) => process_cdp(&shared_state, &client, event, ctx).await,
+                None => {
+                    println!("Chrome WebSocket closed.");
+                    break;
                 }
-                Ok(_) => {}
-                Err(err) => {
-                    eprintln!("WebSocket error: {:?}", err);
-                    if let tokio_tungstenite::tungstenite::Error::Protocol(protocol_error) = &err {
-                        if matches!(
-                            protocol_error,
-                            tokio_tungstenite::tungstenite::error::ProtocolError::ResetWithoutClosingHandshake
-                        ) {
-                            println!("\n\n\nHandling WebSocket Protocol(ResetWithoutClosingHandshake) error\n\n\n");
-                            //shared_state_ws.show_modal_disconnect.store(true, Ordering::SeqCst);
-                        }
-                    }
+            },
+            changed = stop_signal.changed() => {
+                if changed.is_err() || *stop_signal.borrow() {
+                    println!("Stopping Chrome monitoring...");
                     break;
                 }
             }
         }
-    });
-
-    // Process incoming messages and listen for stop signal
-    while let Some(message) = rx.recv().await {
-        if *stop_signal.borrow() {
-            println!("Stopping Chrome monitoring...");
-            break;
-        }
-        process_cdp(&shared_state, message, &ctx, &mut ws_stream.lock().await).await;
     }
 
     Ok(())
@@ -1983,351 +2070,292 @@ async fn monitor_chrome(
 
 async fn process_cdp(
     shared_state: &Arc<SharedState>,
-    message: tokio_tungstenite::tungstenite::Utf8Bytes,
+    client: &CdpClient,
+    event: CdpEvent,
     ctx: &egui::Context,
-    ws_stream: &mut tokio::sync::MutexGuard<
-        '_,
-        tokio_tungstenite::WebSocketStream<
-            tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
-        >,
-    >,
 ) {
-    println!("Processing CDP message: {}", &message);
-
-    if let Ok(event) = serde_json::from_str::<serde_json::Value>(&message) {
-        println!("Parsed event: {:?}", event);
+    println!("Processing CDP event: {:?}", event);
+
+    let method = event.method.as_str();
+    // Events without params carry `null`; treat that the same as a missing field.
+    let params = (!event.params.is_null()).then(|| event.params.clone());
+    match method {
+        "Target.detachedFromTarget" => {
+            println!("Handling Target.detachedFromTarget event");
+
+            if let Some(params) = params.clone()
+                && let Some(browser_context_id) = params
+                    .get("browserContextId")
+                    .and_then(|b| b.as_str())
+                    .map(String::from)
+            {
+                println!("BrowserContextId: {}", browser_context_id);
+
+                // Remove all tabs associated with this browserContextId
+                let tabs_to_remove: Vec<String> = shared_state
+                    .tabs
+                    .iter()
+                    .filter_map(|entry| {
+                        if entry.value().browser_context_id.as_ref() == Some(&browser_context_id) {
+                            Some(entry.key().clone())
+                        } else {
+                            None
+                        }
+                    })
+                    .collect();
 
-        if let Some(method) = event.get("method").and_then(|m| m.as_str()) {
-            println!("Detected method: {}", method);
+                for target_id in tabs_to_remove {
+                    shared_state.tabs.remove(&target_id);
+                    println!("Removed tab with Target ID: {}", target_id);
+                }
+            }
+        }
+        "Inspector.detached" => {
+            println!("unHandling Inspector.detached event");
+            // Check if the modal is already shown
+            if shared_state.show_modal_disconnect.load(Ordering::SeqCst) {
+                println!("Disconnect modal already shown. Ignoring subsequent events.");
+                return;
+            }
+            let response = reqwest::get(format!("http://{}/json", shared_state.cdp_endpoint)).await;
+            if response.is_err() || !response.unwrap().status().is_success() {
+                println!("Chrome DevTools server is down. Setting disconnect modal.");
+                shared_state
+                    .show_modal_disconnect
+                    .store(true, Ordering::SeqCst);
+            }
+            //shared_state.show_modal_disconnect.store(true, Ordering::SeqCst);
+        }
+        "Target.targetCreated" => {
+            println!("Handling Target.targetCreated event");
 
-            match method {
-                "Target.detachedFromTarget" => {
-                    println!("Handling Target.detachedFromTarget event");
+            if let Some(params) = params.clone() {
+                println!("Params: {:?}", params);
 
-                    if let Some(params) = event.get("params").cloned() {
-                        if let Some(browser_context_id) = params
+                if let Some(target_info) = params.get("targetInfo") {
+                    if let Some(target_id) = target_info
+                        .get("targetId")
+                        .and_then(|t| t.as_str())
+                        .map(String::from)
+                    {
+                        println!("Target ID: {}", target_id);
+
+                        let url = target_info
+                            .get("url")
+                            .and_then(|u| u.as_str())
+                            .unwrap_or("")
+                            .to_string();
+                        println!("URL: {}", url);
+                        let title = target_info
+                            .get("title")
+                            .and_then(|u| u.as_str())
+                            .unwrap_or("")
+                            .to_string();
+                        println!("title: {}", url);
+
+                        println!(
+                            "Target ID: {} {:?}",
+                            target_id,
+                            target_info
+                                .get("type")
+                                .and_then(|t| t.as_str())
+                                .unwrap_or("")
+                        );
+                        if target_info.get("type").and_then(|t| t.as_str()) != Some("page") {
+                            println!("Skipping target of type other than 'page'");
+                            return;
+                        }
+                        if target_info.get("attached").and_then(|a| a.as_bool()) == Some(false) {
+                            println!("Skipping target with 'attached' set to false");
+                            return;
+                        }
+                        let browser_context_id = target_info
                             .get("browserContextId")
                             .and_then(|b| b.as_str())
-                            .map(String::from)
-                        {
-                            println!("BrowserContextId: {}", browser_context_id);
-
-                            // Remove all tabs associated with this browserContextId
-                            let tabs_to_remove: Vec<String> = shared_state
-                                .tabs
-                                .iter()
-                                .filter_map(|entry| {
-                                    if entry.value().browser_context_id.as_ref()
-                                        == Some(&browser_context_id)
-                                    {
-                                        Some(entry.key().clone())
-                                    } else {
-                                        None
-                                    }
-                                })
-                                .collect();
-
-                            for target_id in tabs_to_remove {
-                                shared_state.tabs.remove(&target_id);
-                                println!("Removed tab with Target ID: {}", target_id);
-                            }
-                        }
-                    }
-                }
-                "Inspector.detached" => {
-                    println!("unHandling Inspector.detached event");
-                    // Check if the modal is already shown
-                    if shared_state.show_modal_disconnect.load(Ordering::SeqCst) {
-                        println!("Disconnect modal already shown. Ignoring subsequent events.");
-                        return;
-                    }
-                    let response = reqwest::get("http://localhost:9222/json").await;
-                    if response.is_err() || !response.unwrap().status().is_success() {
-                        println!("Chrome DevTools server is down. Setting disconnect modal.");
-                        shared_state
-                            .show_modal_disconnect
-                            .store(true, Ordering::SeqCst);
+                            .map(String::from);
+                        shared_state.tabs.insert(
+                            target_id.clone(),
+                            Tab {
+                                target_id,
+                                url,
+                                bang_id: String::new(),
+                                title, // Initialize with an empty title
+                                browser_context_id,
+                            },
+                        );
+                        println!("Target created and added to shared state");
+                    } else {
+                        println!("No targetId found in targetInfo");
                     }
-                    //shared_state.show_modal_disconnect.store(true, Ordering::SeqCst);
+                } else {
+                    println!("No targetInfo found in params");
                 }
-                "Target.targetCreated" => {
-                    println!("Handling Target.targetCreated event");
+            } else {
+                println!("No params found in event");
+            }
+        }
+        "Target.targetDestroyed" => {
+            println!("Handling Target.targetDestroyed event");
 
-                    if let Some(params) = event.get("params").cloned() {
-                        println!("Params: {:?}", params);
+            if let Some(params) = params.clone() {
+                println!("Params: {:?}", params);
 
-                        if let Some(target_info) = params.get("targetInfo") {
-                            if let Some(target_id) = target_info
-                                .get("targetId")
-                                .and_then(|t| t.as_str())
-                                .map(String::from)
-                            {
-                                println!("Target ID: {}", target_id);
-
-                                let url = target_info
-                                    .get("url")
-                                    .and_then(|u| u.as_str())
-                                    .unwrap_or("")
-                                    .to_string();
-                                println!("URL: {}", url);
-                                let title = target_info
-                                    .get("title")
-                                    .and_then(|u| u.as_str())
-                                    .unwrap_or("")
-                                    .to_string();
-                                println!("title: {}", url);
+                if let Some(target_id) = params
+                    .get("targetId")
+                    .and_then(|t| t.as_str())
+                    .map(String::from)
+                {
+                    println!("Target ID: {}", target_id);
 
-                                println!(
-                                    "Target ID: {} {:?}",
-                                    target_id,
-                                    target_info
-                                        .get("type")
-                                        .and_then(|t| t.as_str())
-                                        .unwrap_or("")
-                                );
-                                if target_info.get("type").and_then(|t| t.as_str()) != Some("page")
-                                {
-                                    println!("Skipping target of type other than 'page'");
-                                    return;
-                                }
-                                if target_info.get("attached").and_then(|a| a.as_bool())
-                                    == Some(false)
-                                {
-                                    println!("Skipping target with 'attached' set to false");
-                                    return;
-                                }
-                                let browser_context_id = target_info
-                                    .get("browserContextId")
-                                    .and_then(|b| b.as_str())
-                                    .map(String::from);
-                                shared_state.tabs.insert(
-                                    target_id.clone(),
-                                    Tab {
-                                        target_id,
-                                        url,
-                                        bang_id: String::new(),
-                                        title: title, // Initialize with an empty title
-                                        browser_context_id,
-                                    },
-                                );
-                                println!("Target created and added to shared state");
-                            } else {
-                                println!("No targetId found in targetInfo");
-                            }
-                        } else {
-                            println!("No targetInfo found in params");
-                        }
+                    if shared_state.tabs.contains_key(&target_id) {
+                        println!("Target ID found in shared state, removing it");
+                        shared_state.tabs.remove(&target_id);
+                        println!("Target destroyed and removed from shared state");
                     } else {
-                        println!("No params found in event");
+                        println!("Target ID not found in shared state, nothing to remove");
                     }
+                } else {
+                    println!("No targetId found in params");
                 }
-                "Target.targetDestroyed" => {
-                    println!("Handling Target.targetDestroyed event");
-
-                    if let Some(params) = event.get("params").cloned() {
-                        println!("Params: {:?}", params);
-
-                        if let Some(target_id) = params
-                            .get("targetId")
-                            .and_then(|t| t.as_str())
-                            .map(String::from)
-                        {
-                            println!("Target ID: {}", target_id);
-
-                            if shared_state.tabs.contains_key(&target_id) {
-                                println!("Target ID found in shared state, removing it");
-                                shared_state.tabs.remove(&target_id);
-                                println!("Target destroyed and removed from shared state");
-                            } else {
-                                println!("Target ID not found in shared state, nothing to remove");
-                            }
-                        } else {
-                            println!("No targetId found in params");
+            } else {
+                println!("No params found in event");
+            }
+        }
+        "Target.targetInfoChanged" => {
+            println!("Handling Target.targetInfoChanged event");
+
+            if let Some(params) = params.clone() {
+                if let Some(target_info) = params.get("targetInfo") {
+                    if let Some(target_id) = target_info
+                        .get("targetId")
+                        .and_then(|t| t.as_str())
+                        .map(String::from)
+                    {
+                        println!(
+                            "Target ID: {} {:?}",
+                            target_id,
+                            target_info
+                                .get("type")
+                                .and_then(|t| t.as_str())
+                                .unwrap_or("")
+                        );
+                        if target_info.get("type").and_then(|t| t.as_str()) != Some("page") {
+                            println!("Skipping target of type other than 'page'");
+                            return;
+                        }
+                        if target_info.get("attached").and_then(|a| a.as_bool()) == Some(false) {
+                            println!("Skipping target with 'attached' set to false");
+                            return;
                         }
-                    } else {
-                        println!("No params found in event");
-                    }
-                }
-                "Target.targetInfoChanged" => {
-                    println!("Handling Target.targetInfoChanged event");
 
-                    if let Some(params) = event.get("params").cloned() {
-                        if let Some(target_info) = params.get("targetInfo") {
-                            if let Some(target_id) = target_info
-                                .get("targetId")
+                        let url = target_info
+                            .get("url")
+                            .and_then(|u| u.as_str())
+                            .unwrap_or("")
+                            .to_string();
+                        let title = match query_target_title(client, &target_id).await {
+                            Ok(queried_title) => queried_title,
+                            Err(_) => target_info
+                                .get("title")
                                 .and_then(|t| t.as_str())
-                                .map(String::from)
-                            {
-                                println!(
-                                    "Target ID: {} {:?}",
-                                    target_id,
-                                    target_info
-                                        .get("type")
-                                        .and_then(|t| t.as_str())
-                                        .unwrap_or("")
-                                );
-                                if target_info.get("type").and_then(|t| t.as_str()) != Some("page")
-                                {
-                                    println!("Skipping target of type other than 'page'");
-                                    return;
-                                }
-                                if target_info.get("attached").and_then(|a| a.as_bool())
-                                    == Some(false)
-                                {
-                                    println!("Skipping target with 'attached' set to false");
-                                    return;
-                                }
-
-                                let url = target_info
-                                    .get("url")
-                                    .and_then(|u| u.as_str())
-                                    .unwrap_or("")
-                                    .to_string();
-                                let title = match query_target_title(ws_stream, &target_id).await {
-                                    Ok(queried_title) => queried_title,
-                                    Err(_) => target_info
-                                        .get("title")
-                                        .and_then(|t| t.as_str())
-                                        .unwrap_or("Unknown Title")
-                                        .to_string(),
-                                };
-
-                                println!("Target ID: {}", target_id);
-                                println!("Updated URL: {}", url);
-                                println!("Queried Title: {}", title);
-                                let browser_context_id = target_info
-                                    .get("browserContextId")
-                                    .and_then(|b| b.as_str())
-                                    .map(String::from);
-                                // Update the tab in shared_state.tabs
-                                if let Some(mut tab) = shared_state.tabs.get_mut(&target_id) {
-                                    tab.url = url;
-                                    tab.bang_id = title;
-                                    println!("Target info updated in shared state");
-                                } else {
-                                    println!(
-                                        "Target ID not found in shared state, adding new entry"
-                                    );
-                                    shared_state.tabs.insert(
-                                        target_id.clone(),
-                                        Tab {
-                                            target_id,
-                                            url,
-                                            bang_id: String::new(),
-                                            title: title.clone(), // Initialize with the queried title
-                                            browser_context_id,
-                                        },
-                                    );
-                                }
-
-                                // Request a repaint to update the UI
-                                ctx.request_repaint();
-                            } else {
-                                println!("No targetId found in targetInfo");
-                            }
+                                .unwrap_or("Unknown Title")
+                                .to_string(),
+                        };
+
+                        println!("Target ID: {}", target_id);
+                        println!("Updated URL: {}", url);
+                        println!("Queried Title: {}", title);
+                        let browser_context_id = target_info
+                            .get("browserContextId")
+                            .and_then(|b| b.as_str())
+                            .map(String::from);
+                        // Update the tab in shared_state.tabs
+                        if let Some(mut tab) = shared_state.tabs.get_mut(&target_id) {
+                            tab.url = url;
+                            tab.bang_id = title;
+                            println!("Target info updated in shared state");
                         } else {
-                            println!("No targetInfo found in params");
+                            println!("Target ID not found in shared state, adding new entry");
+                            shared_state.tabs.insert(
+                                target_id.clone(),
+                                Tab {
+                                    target_id,
+                                    url,
+                                    bang_id: String::new(),
+                                    title: title.clone(), // Initialize with the queried title
+                                    browser_context_id,
+                                },
+                            );
                         }
+
+                        // Request a repaint to update the UI
+                        ctx.request_repaint();
                     } else {
-                        println!("No params found in event");
+                        println!("No targetId found in targetInfo");
                     }
+                } else {
+                    println!("No targetInfo found in params");
                 }
-                _ => {
-                    println!("Unhandled method: {}", method);
-                }
+            } else {
+                println!("No params found in event");
             }
-        } else {
-            println!("No method found in event");
         }
-    } else {
-        println!("Failed to parse message as JSON");
+        _ => {
+            println!("Unhandled method: {}", method);
+        }
     }
 }
 
 use std::io;
 use std::path::Path;
-use std::path::PathBuf;
-use std::process::Command;
-
 #[cfg(target_os = "windows")]
-use winreg::RegKey;
-#[cfg(target_os = "windows")]
-use winreg::enums::{HKEY_LOCAL_MACHINE, KEY_READ, KEY_WOW64_64KEY};
-#[cfg(target_os = "windows")]
-fn find_chrome_via_registry() -> Option<String> {
-    let hk = RegKey::predef(HKEY_LOCAL_MACHINE)
-        .open_subkey_with_flags(
-            r"SOFTWARE\Microsoft\Windows\CurrentVersion\App Paths\chrome.exe",
-            KEY_READ | KEY_WOW64_64KEY,
-        )
-        .ok()?;
-    hk.get_value("").ok()
-}
+use std::process::Command;
 
 fn launch_chrome(user_data_dir: &Path, shared_state: Arc<SharedState>) -> io::Result<()> {
+    let port = shared_state.cdp_endpoint.port;
     // Find the real path to the Chrome executable
-    #[cfg(target_os = "windows")]
-    let chrome_path = find_chrome_via_registry()
-        .map(PathBuf::from)
-        .or_else(|| which("chrome.exe").ok());
-
-    #[cfg(not(target_os = "windows"))]
-    let chrome_path = which("chrome").ok();
+    let chrome_path = debugchrome::browser::find_chrome();
 
     #[cfg(target_os = "windows")]
     let process = if let Some(chrome_path) = chrome_path {
         println!("Found Chrome executable at: {}", chrome_path.display());
-
-        Command::new(chrome_path)
-            .args([
-                "--remote-debugging-port=9222",
-                "--enable-automation",
-                "--no-first-run",
-                &format!("--user-data-dir={}", user_data_dir.display()),
-            ])
-            .spawn()?
+        debugchrome::browser::launch(&chrome_path, port, user_data_dir, &[])?
     } else {
         eprintln!(
             "Warning: Chrome executable not found in registry or PATH using start (no HWND lookup)"
         );
         Command::new("cmd")
-            .args([
-                "/C",
-                "start",
-                "chrome",
-                "--remote-debugging-port=9222",
-                "--enable-automation",
-                "--no-first-run",
-                &format!("--user-data-dir={}", user_data_dir.display()),
-            ])
+            .args(["/C", "start", "chrome"])
+            .args(debugchrome::browser::debug_args(port, user_data_dir))
             .spawn()?
     };
 
     #[cfg(not(target_os = "windows"))]
     let process = if let Some(chrome_path) = chrome_path {
-        Command::new(chrome_path)
-            .args([
-                "--remote-debugging-port=9222",
-                "--enable-automation",
-                "--no-first-run",
-                &format!("--user-data-dir={}", user_data_dir.display()),
-            ])
-            .spawn()?
+        println!("Found Chrome executable at: {}", chrome_path.display());
+        debugchrome::browser::launch(&chrome_path, port, user_data_dir, &[])?
     } else {
         return Err(io::Error::new(
             io::ErrorKind::NotFound,
-            "Chrome executable not found",
+            format!(
+                "Chrome executable not found; looked for {}",
+                debugchrome::browser::CHROME_EXECUTABLES.join(", ")
+            ),
         ));
     };
 
     let polling_shared_state = shared_state.clone();
     tokio::spawn(async move {
         tokio::time::sleep(std::time::Duration::from_secs(3)).await;
-        let hwnd = wait_for_browser_hwnd(process.id()).ok().unwrap();
-        println!("Retrieved HWND for browser: {}", hwnd);
-        polling_shared_state
-            .browser_hwnds
-            .insert(process.id().to_string(), hwnd);
+        match wait_for_browser_hwnd(process.id()) {
+            Ok(hwnd) => {
+                println!("Retrieved HWND for browser: {}", hwnd);
+                polling_shared_state
+                    .browser_hwnds
+                    .insert(process.id().to_string(), hwnd);
+            }
+            Err(e) => println!("No HWND for browser {}: {}", process.id(), e),
+        }
     });
     // Wait for the browser window to appear and retrieve its HWND
 
@@ -2337,63 +2365,37 @@ fn launch_chrome(user_data_dir: &Path, shared_state: Arc<SharedState>) -> io::Re
 }
 
 async fn query_target_title(
-    ws_stream: &mut tokio::sync::MutexGuard<
-        '_,
-        tokio_tungstenite::WebSocketStream<
-            tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
-        >,
-    >,
+    client: &CdpClient,
     target_id: &str,
 ) -> Result<String, Box<dyn std::error::Error>> {
-    // Attach to the target
-    let attach_message = json!({
-        "id": 1,
-        "method": "Target.attachToTarget",
-        "params": {
-            "targetId": target_id,
-            "flatten": true
-        }
-    });
-    ws_stream
-        .send(Message::Text(attach_message.to_string().into()))
-        .await?;
-
-    // Execute JavaScript to get the title
-    let eval_message = json!({
-        "id": 2,
-        "method": "Runtime.evaluate",
-        "params": {
-            "expression": "document.title",
-            "returnByValue": true
-        }
-    });
-    ws_stream
-        .send(Message::Text(eval_message.to_string().into()))
-        .await?;
-
-    // Wait for the response
-    while let Some(msg) = ws_stream.next().await {
-        if let Ok(Message::Text(response)) = msg {
-            if let Ok(json) = serde_json::from_str::<serde_json::Value>(&response) {
-                if json.get("id") == Some(&serde_json::Value::from(2)) {
-                    return json
-                        .get("result")
-                        .and_then(|result| result.get("value"))
-                        .and_then(|value| value.as_str())
-                        .map(String::from)
-                        .ok_or_else(|| "Failed to extract title from JSON response".into());
-                } else if json.get("error").is_some() {
-                    return Err("Error response received from Chrome DevTools".into());
-                }
-            }
-        }
+    // Attach to the target and evaluate in its own session, so the reply can't be
+    // confused with traffic for other targets on the browser socket.
+    let session = client.attach(target_id).await?;
+    let result = session.evaluate("document.title", true).await;
+
+    if let Some(session_id) = session.session_id() {
+        let _ = client
+            .call(
+                "Target.detachFromTarget",
+                json!({ "sessionId": session_id }),
+            )
+            .await;
     }
 
-    Ok("Failed to get target title".to_string())
+    let result = result?;
+    if let Some(exception) = result.exception {
+        return Err(exception.text.into());
+    }
+    result
+        .value
+        .as_ref()
+        .and_then(|value| value.as_str())
+        .map(String::from)
+        .ok_or_else(|| "Failed to extract title from evaluation result".into())
 }
 
 #[cfg(feature = "uses_gui")]
-pub async fn start_gui() -> eframe::Result<()> {
+pub async fn start_gui(cdp_endpoint: CdpEndpoint) -> eframe::Result<()> {
     use chrono::{NaiveDate, Utc};
 
     let native_options = eframe::NativeOptions {
@@ -2408,8 +2410,8 @@ pub async fn start_gui() -> eframe::Result<()> {
 
     // Split LAST_RELEASE into (date, sha, version)
     let parts: Vec<_> = LAST_REL.split('|').collect();
-    let (last_date, last_sha, last_version) = (
-        parts.get(0).unwrap_or(&"00/00/00"),
+    let (last_date, last_sha, _last_version) = (
+        parts.first().unwrap_or(&"00/00/00"),
         parts.get(1).unwrap_or(&"00000"),
         parts.get(2).unwrap_or(&"0.0.0"),
     );
@@ -2436,17 +2438,20 @@ pub async fn start_gui() -> eframe::Result<()> {
         VERSION, last_sha, custom_flag, BUILD_DATE, days_since_release
     );
 
-    let ret = eframe::run_native(
+    eframe::run_native(
         &title,
         native_options,
-        Box::new(|cc| Ok(Box::new(DemoApp::new(cc)))),
-    );
-    ret
+        Box::new(|cc| Ok(Box::new(DemoApp::new(cc, cdp_endpoint)))),
+    )
 }
 
-async fn close_target(target_id: &str, url: &str) -> Result<(), Box<dyn std::error::Error>> {
+async fn close_target(
+    endpoint: &CdpEndpoint,
+    target_id: &str,
+    url: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
     // Connect to the Chrome DevTools Protocol WebSocket
-    let response = reqwest::get("http://localhost:9222/json/version").await?;
+    let response = reqwest::get(format!("http://{}/json/version", endpoint)).await?;
     let version: serde_json::Value = response.json().await?;
     let ws_url = version["webSocketDebuggerUrl"]
         .as_str()
@@ -2517,6 +2522,7 @@ pub fn ensure_single_instance(hwnd: isize) -> Option<&'static File> {
         .read(true)
         .write(true)
         .create(true)
+        .truncate(false)
         .open(lock_file_path)
     {
         Ok(file) => file,
@@ -2592,6 +2598,7 @@ use winapi::um::processthreadsapi::OpenProcess;
 #[cfg(target_os = "windows")]
 use winapi::um::winnt::PROCESS_QUERY_INFORMATION;
 
+#[cfg_attr(not(target_os = "windows"), allow(unused_variables))]
 fn is_process_running(pid: u32) -> bool {
     #[cfg(target_os = "windows")]
     unsafe {
@@ -2632,7 +2639,7 @@ fn get_current_window_handle() -> isize {
         }
         Err(()) => {
             println!("Error occurred while getting the active window");
-            0 as isize
+            0_isize
         }
     };
     println!("Current window handle: {:?}", hwnd);
@@ -2662,7 +2669,7 @@ fn activate_existing_window(_pid: u32, _hwnd: usize) {
 
     // Use `wmctrl` to bring the existing window to the front
     Command::new("wmctrl")
-        .args(&["-a", "DebugChrome GUI"])
+        .args(["-a", "DebugChrome GUI"])
         .output()
         .expect("Failed to activate existing window");
 }
@@ -2702,6 +2709,7 @@ impl MonitoringState {
         }
     }
 
+    #[allow(dead_code)]
     pub fn set_connected(&self, connected: bool) {
         self.is_connected.store(connected, Ordering::SeqCst);
     }
@@ -2732,6 +2740,7 @@ fn wait_for_browser_hwnd(pid: u32) -> io::Result<isize> {
         "Failed to find browser HWND",
     ))
 }
+#[cfg_attr(not(target_os = "windows"), allow(unused_variables))]
 fn find_hwnd_by_pid(pid: u32) -> Option<isize> {
     #[cfg(target_os = "windows")]
     {
@@ -0,0 +1,504 @@
+// `--har` / `!har=`: a tab's network traffic as a HAR 1.2 file.
+//
+// `start` enables the `Network` and `Page` domains on a page connection; from then on every
+// `Network.*` event is folded into an entry per request (a redirect closes the entry it
+// replaces and starts a new one). `HarCapture::finish` keeps recording until the tab closes
+// and returns the document. Response bodies are not fetched, so `content` has a size and
+// MIME type but no text.
+use crate::cdp::{CdpEvent, CdpResult, CdpSession};
+use chrono::{TimeZone, Utc};
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use tokio::sync::mpsc;
+
+/// A HAR 1.2 document.
+#[derive(Debug, Clone, Serialize)]
+pub struct Har {
+    pub log: HarLog,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HarLog {
+    pub version: String,
+    pub creator: HarCreator,
+    pub pages: Vec<HarPage>,
+    pub entries: Vec<HarEntry>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HarCreator {
+    pub name: String,
+    pub version: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarPage {
+    pub started_date_time: String,
+    pub id: String,
+    pub title: String,
+    pub page_timings: HarPageTimings,
+}
+
+/// Milliseconds from the page's first request; -1 when the event never fired.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarPageTimings {
+    pub on_content_load: f64,
+    pub on_load: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarEntry {
+    pub pageref: String,
+    pub started_date_time: String,
+    /// Sum of the non-negative `timings`, in milliseconds.
+    pub time: f64,
+    pub request: HarRequest,
+    pub response: HarResponse,
+    pub cache: Value,
+    pub timings: HarTimings,
+    #[serde(rename = "serverIPAddress", skip_serializing_if = "Option::is_none")]
+    pub server_ip_address: Option<String>,
+    /// Chrome's resource type: `Document`, `Script`, `XHR`, ...
+    #[serde(rename = "_resourceType")]
+    pub resource_type: String,
+    /// Why the request failed, for requests that never got a response.
+    #[serde(rename = "_error", skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarRequest {
+    pub method: String,
+    pub url: String,
+    pub http_version: String,
+    pub cookies: Vec<HarNameValue>,
+    pub headers: Vec<HarNameValue>,
+    pub query_string: Vec<HarNameValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_data: Option<HarPostData>,
+    pub headers_size: i64,
+    pub body_size: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarPostData {
+    pub mime_type: String,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarResponse {
+    pub status: i64,
+    pub status_text: String,
+    pub http_version: String,
+    pub cookies: Vec<HarNameValue>,
+    pub headers: Vec<HarNameValue>,
+    pub content: HarContent,
+    #[serde(rename = "redirectURL")]
+    pub redirect_url: String,
+    pub headers_size: i64,
+    pub body_size: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarContent {
+    pub size: i64,
+    pub mime_type: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HarNameValue {
+    pub name: String,
+    pub value: String,
+}
+
+/// Milliseconds per phase; -1 for phases that did not happen (e.g. no DNS lookup).
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HarTimings {
+    pub blocked: f64,
+    pub dns: f64,
+    pub connect: f64,
+    pub send: f64,
+    pub wait: f64,
+    pub receive: f64,
+    pub ssl: f64,
+}
+
+// A request seen so far: the `Network` event params as they arrived.
+#[derive(Debug, Default)]
+struct Pending {
+    request: Value,
+    // Monotonic seconds and wall-clock seconds of `requestWillBeSent`.
+    timestamp: f64,
+    wall_time: f64,
+    resource_type: String,
+    response: Option<Value>,
+    response_timestamp: Option<f64>,
+    data_length: i64,
+    finished: Option<(f64, i64)>,
+    error: Option<String>,
+}
+
+/// Folds `Network.*` and `Page.*` events into HAR entries.
+#[derive(Debug)]
+pub struct HarRecorder {
+    page_id: String,
+    requests: HashMap<String, usize>,
+    pending: Vec<Pending>,
+    // Monotonic seconds of the `Page` events, to line them up with the first request.
+    content_loaded: Option<f64>,
+    loaded: Option<f64>,
+}
+
+impl HarRecorder {
+    /// `page_id` names the HAR page every entry refers to, e.g. the tab's bang id.
+    pub fn new(page_id: &str) -> Self {
+        HarRecorder {
+            page_id: page_id.to_string(),
+            requests: HashMap::new(),
+            pending: Vec::new(),
+            content_loaded: None,
+            loaded: None,
+        }
+    }
+
+    pub fn record(&mut self, event: &CdpEvent) {
+        let params = &event.params;
+        let timestamp = params["timestamp"].as_f64().unwrap_or_default();
+        let request_id = params["requestId"].as_str().unwrap_or_default();
+        match event.method.as_str() {
+            "Network.requestWillBeSent" => {
+                // A redirect reuses the request id: its response ends the previous hop.
+                if let Some(redirect) = params.get("redirectResponse")
+                    && let Some(previous) = self.entry(request_id)
+                {
+                    previous.response = Some(redirect.clone());
+                    previous.response_timestamp = Some(timestamp);
+                    previous.finished = Some((
+                        timestamp,
+                        redirect["encodedDataLength"].as_i64().unwrap_or_default(),
+                    ));
+                }
+                self.requests
+                    .insert(request_id.to_string(), self.pending.len());
+                self.pending.push(Pending {
+                    request: params["request"].clone(),
+                    timestamp,
+                    wall_time: params["wallTime"].as_f64().unwrap_or_default(),
+                    resource_type: params["type"].as_str().unwrap_or("Other").to_string(),
+                    ..Default::default()
+                });
+            }
+            "Network.responseReceived" => {
+                if let Some(entry) = self.entry(request_id) {
+                    entry.response = Some(params["response"].clone());
+                    entry.response_timestamp = Some(timestamp);
+                }
+            }
+            "Network.dataReceived" => {
+                if let Some(entry) = self.entry(request_id) {
+                    entry.data_length += params["dataLength"].as_i64().unwrap_or_default();
+                }
+            }
+            "Network.loadingFinished" => {
+                if let Some(entry) = self.entry(request_id) {
+                    let encoded = params["encodedDataLength"].as_i64().unwrap_or_default();
+                    entry.finished = Some((timestamp, encoded));
+                }
+            }
+            "Network.loadingFailed" => {
+                if let Some(entry) = self.entry(request_id) {
+                    entry.finished = Some((timestamp, 0));
+                    entry.error = params["errorText"].as_str().map(str::to_string);
+                }
+            }
+            "Page.domContentEventFired" => {
+                self.content_loaded.get_or_insert(timestamp);
+            }
+            "Page.loadEventFired" => {
+                self.loaded.get_or_insert(timestamp);
+            }
+            _ => {}
+        }
+    }
+
+    fn entry(&mut self, request_id: &str) -> Option<&mut Pending> {
+        let index = *self.requests.get(request_id)?;
+        self.pending.get_mut(index)
+    }
+
+    /// The HAR document for everything recorded so far.
+    pub fn to_har(&self) -> Har {
+        let first = self.pending.first();
+        let since_first = |at: Option<f64>| match (at, first) {
+            (Some(at), Some(first)) => ((at - first.timestamp) * 1000.0).max(0.0),
+            _ => -1.0,
+        };
+        let page = HarPage {
+            started_date_time: iso_time(first.map(|first| first.wall_time)),
+            id: self.page_id.clone(),
+            title: first
+                .and_then(|first| first.request["url"].as_str())
+                .unwrap_or_default()
+                .to_string(),
+            page_timings: HarPageTimings {
+                on_content_load: since_first(self.content_loaded),
+                on_load: since_first(self.loaded),
+            },
+        };
+        Har {
+            log: HarLog {
+                version: "1.2".to_string(),
+                creator: HarCreator {
+                    name: "debugchrome".to_string(),
+                    version: env!("CARGO_PKG_VERSION").to_string(),
+                },
+                pages: vec![page],
+                entries: self
+                    .pending
+                    .iter()
+                    .map(|pending| self.to_entry(pending))
+                    .collect(),
+            },
+        }
+    }
+
+    fn to_entry(&self, pending: &Pending) -> HarEntry {
+        let request = &pending.request;
+        let no_response = Value::Null;
+        let response = pending.response.as_ref().unwrap_or(&no_response);
+        let http_version = http_version(response["protocol"].as_str().unwrap_or_default());
+        let request_headers = headers(&request["headers"]);
+        let response_headers = headers(&response["headers"]);
+        let timings = timings(pending, response);
+        let post_data = request["postData"].as_str().map(|text| HarPostData {
+            mime_type: header(&request_headers, "content-type").unwrap_or_default(),
+            text: text.to_string(),
+        });
+        let (body_size, content_size) = match pending.finished {
+            Some((_, encoded)) if pending.response.is_some() => {
+                let headers = response["encodedDataLength"].as_i64().unwrap_or_default();
+                ((encoded - headers).max(0), pending.data_length)
+            }
+            _ => (-1, 0),
+        };
+        HarEntry {
+            pageref: self.page_id.clone(),
+            started_date_time: iso_time(Some(pending.wall_time)),
+            time: [
+                timings.blocked,
+                timings.dns,
+                timings.connect,
+                timings.send,
+                timings.wait,
+                timings.receive,
+            ]
+            .iter()
+            .filter(|ms| **ms > 0.0)
+            .sum(),
+            request: HarRequest {
+                method: request["method"].as_str().unwrap_or("GET").to_string(),
+                url: request["url"].as_str().unwrap_or_default().to_string(),
+                http_version: http_version.clone(),
+                cookies: header(&request_headers, "cookie")
+                    .map(|cookie| cookies(cookie.split(';')))
+                    .unwrap_or_default(),
+                query_string: query_string(request["url"].as_str().unwrap_or_default()),
+                body_size: post_data
+                    .as_ref()
+                    .map_or(0, |post_data| post_data.text.len() as i64),
+                post_data,
+                headers: request_headers,
+                headers_size: -1,
+            },
+            response: HarResponse {
+                status: response["status"].as_i64().unwrap_or_default(),
+                status_text: response["statusText"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+                http_version,
+                cookies: cookies(
+                    response_headers
+                        .iter()
+                        .filter(|header| header.name.eq_ignore_ascii_case("set-cookie"))
+                        .filter_map(|header| header.value.split(';').next()),
+                ),
+                redirect_url: header(&response_headers, "location").unwrap_or_default(),
+                headers: response_headers,
+                content: HarContent {
+                    size: content_size,
+                    mime_type: response["mimeType"]
+                        .as_str()
+                        .unwrap_or("x-unknown")
+                        .to_string(),
+                },
+                headers_size: -1,
+                body_size,
+            },
+            cache: json!({}),
+            timings,
+            server_ip_address: response["remoteIPAddress"]
+                .as_str()
+                .map(|ip| ip.trim_matches(['[', ']']).to_string()),
+            resource_type: pending.resource_type.clone(),
+            error: pending.error.clone(),
+        }
+    }
+}
+
+// Phase durations from `response.timing` (milliseconds relative to `requestTime`), falling
+// back to the event timestamps for responses without one (cache, data: URLs).
+fn timings(pending: &Pending, response: &Value) -> HarTimings {
+    let ms = |from: f64, to: f64| ((to - from) * 1000.0).max(0.0);
+    let end = pending.finished.map(|(at, _)| at);
+    let timing = &response["timing"];
+    let Some(request_time) = timing["requestTime"].as_f64() else {
+        let responded = pending.response_timestamp;
+        return HarTimings {
+            blocked: -1.0,
+            dns: -1.0,
+            connect: -1.0,
+            send: 0.0,
+            wait: responded.map_or(0.0, |at| ms(pending.timestamp, at)),
+            receive: match (responded, end) {
+                (Some(responded), Some(end)) => ms(responded, end),
+                _ => 0.0,
+            },
+            ssl: -1.0,
+        };
+    };
+    let phase = |start: &str, end: &str| {
+        let start = timing[start].as_f64().unwrap_or(-1.0);
+        let end = timing[end].as_f64().unwrap_or(-1.0);
+        if start >= 0.0 && end >= start {
+            end - start
+        } else {
+            -1.0
+        }
+    };
+    let offset = |name: &str| timing[name].as_f64().filter(|at| *at >= 0.0);
+    let send_start = offset("sendStart").unwrap_or(0.0);
+    let send_end = offset("sendEnd").unwrap_or(send_start);
+    let headers_end = offset("receiveHeadersEnd").unwrap_or(send_end);
+    // Queued in Chrome before the request got going.
+    let queued = ms(pending.timestamp, request_time);
+    let blocked = offset("dnsStart")
+        .or_else(|| offset("connectStart"))
+        .unwrap_or(send_start);
+    HarTimings {
+        blocked: queued + blocked,
+        dns: phase("dnsStart", "dnsEnd"),
+        connect: phase("connectStart", "connectEnd"),
+        send: send_end - send_start,
+        wait: headers_end - send_end,
+        receive: end.map_or(0.0, |end| (ms(request_time, end) - headers_end).max(0.0)),
+        ssl: phase("sslStart", "sslEnd"),
+    }
+}
+
+// `Network.Headers` as HAR pairs; Chrome joins repeated headers with newlines.
+fn headers(headers: &Value) -> Vec<HarNameValue> {
+    let Some(headers) = headers.as_object() else {
+        return Vec::new();
+    };
+    headers
+        .iter()
+        .flat_map(|(name, value)| {
+            value
+                .as_str()
+                .unwrap_or_default()
+                .split('\n')
+                .map(|value| HarNameValue {
+                    name: name.clone(),
+                    value: value.to_string(),
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+fn header(headers: &[HarNameValue], name: &str) -> Option<String> {
+    headers
+        .iter()
+        .find(|header| header.name.eq_ignore_ascii_case(name))
+        .map(|header| header.value.clone())
+}
+
+fn cookies<'a>(pairs: impl Iterator<Item = &'a str>) -> Vec<HarNameValue> {
+    pairs
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(name, value)| HarNameValue {
+            name: name.trim().to_string(),
+            value: value.trim().to_string(),
+        })
+        .collect()
+}
+
+fn query_string(url: &str) -> Vec<HarNameValue> {
+    url::Url::parse(url)
+        .map(|url| {
+            url.query_pairs()
+                .map(|(name, value)| HarNameValue {
+                    name: name.into_owned(),
+                    value: value.into_owned(),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn http_version(protocol: &str) -> String {
+    match protocol {
+        "h2" => "HTTP/2.0".to_string(),
+        "h3" => "HTTP/3.0".to_string(),
+        "" => String::new(),
+        other => other.to_ascii_uppercase(),
+    }
+}
+
+fn iso_time(wall_time: Option<f64>) -> String {
+    let at = wall_time
+        .and_then(|seconds| Utc.timestamp_millis_opt((seconds * 1000.0) as i64).single())
+        .unwrap_or_else(Utc::now);
+    at.to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
+/// A recording that has started; see [`start`].
+pub struct HarCapture {
+    recorder: HarRecorder,
+    events: mpsc::UnboundedReceiver<CdpEvent>,
+}
+
+/// Starts recording `page`. Requests the page sends from here on are captured, so navigate
+/// only after this returns.
+pub async fn start(page: &CdpSession, page_id: &str) -> CdpResult<HarCapture> {
+    let events = page.subscribe(None);
+    page.call("Network.enable", json!({})).await?;
+    page.enable_page().await?;
+    Ok(HarCapture {
+        recorder: HarRecorder::new(page_id),
+        events,
+    })
+}
+
+impl HarCapture {
+    /// Records until the tab closes, then returns the HAR.
+    pub async fn finish(mut self) -> Har {
+        while let Some(event) = self.events.recv().await {
+            if event.method == "Inspector.detached" {
+                break;
+            }
+            self.recorder.record(&event);
+        }
+        self.recorder.to_har()
+    }
+}
@@ -0,0 +1,414 @@
+// `--intercept` / `!intercept=`: block, rewrite, delay or fulfil a tab's requests.
+//
+// The rules file is a JSON array, tried in order; the first rule whose `url` glob (`*` and
+// `?`, case-insensitive, whole URL) and `method` match a request decides what happens to it:
+//
+//   [
+//     { "url": "*://*/ads/*", "block": true },
+//     { "url": "https://staging.example.com/api/*", "rewrite": { "url": "http://localhost:3000/api/$1" } },
+//     { "url": "*/api/config", "method": "GET", "fulfill": { "file": "fixtures/config.json" } },
+//     { "url": "*.woff2", "delay": 2000 }
+//   ]
+//
+// `block` fails the request (`true` or a `Network.ErrorReason` such as `"TimedOut"`),
+// `rewrite` continues it with another URL, method, headers or body (`$1`.. are what the
+// `*`s matched), and `fulfill` answers it with a status, headers and a `body` or a `file`
+// (relative to the rules file). `delay` (milliseconds) holds the request first, and on its
+// own lets it through unchanged. Requests no rule matches continue untouched.
+use crate::cdp::{CdpEvent, CdpResult, CdpSession};
+use base64::Engine;
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// One entry of the rules file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InterceptRule {
+    /// Glob matched against the whole request URL.
+    pub url: String,
+    /// Only requests with this method (case-insensitive).
+    #[serde(default)]
+    pub method: Option<String>,
+    /// Milliseconds to hold the request before the action.
+    #[serde(default)]
+    pub delay: Option<u64>,
+    /// The `Network.ErrorReason` to fail the request with.
+    #[serde(default, deserialize_with = "block_reason")]
+    pub block: Option<String>,
+    #[serde(default)]
+    pub rewrite: Option<Rewrite>,
+    #[serde(default)]
+    pub fulfill: Option<Fulfill>,
+    #[serde(skip)]
+    pattern: Option<Regex>,
+}
+
+/// Changes to a request that is let through.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rewrite {
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub method: Option<String>,
+    /// Added to, or replacing, the request's headers.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default)]
+    pub post_data: Option<String>,
+}
+
+/// A response served without touching the network.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Fulfill {
+    #[serde(default = "ok_status")]
+    pub status: u16,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default)]
+    pub body: Option<String>,
+    /// Served as the body; read on every request, so fixtures can be edited live.
+    #[serde(default)]
+    pub file: Option<PathBuf>,
+}
+
+fn ok_status() -> u16 {
+    200
+}
+
+// `"block": true` is `BlockedByClient`; a string names the reason; `false` is no block.
+fn block_reason<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Repr {
+        Flag(bool),
+        Reason(String),
+    }
+    Ok(match Option::<Repr>::deserialize(deserializer)? {
+        Some(Repr::Flag(true)) => Some("BlockedByClient".to_string()),
+        Some(Repr::Reason(reason)) => Some(reason),
+        Some(Repr::Flag(false)) | None => None,
+    })
+}
+
+#[derive(Debug)]
+pub enum InterceptError {
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    Json {
+        path: PathBuf,
+        error: serde_json::Error,
+    },
+    /// Rule `index` (0-based) is not usable.
+    InvalidRule { index: usize, reason: String },
+}
+
+impl fmt::Display for InterceptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InterceptError::Io { path, error } => {
+                write!(f, "cannot read {}: {}", path.display(), error)
+            }
+            InterceptError::Json { path, error } => {
+                write!(
+                    f,
+                    "{} is not a JSON array of rules: {}",
+                    path.display(),
+                    error
+                )
+            }
+            InterceptError::InvalidRule { index, reason } => {
+                write!(f, "intercept rule {}: {}", index + 1, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for InterceptError {}
+
+/// The rules of one rules file, ready to match.
+#[derive(Debug, Clone, Default)]
+pub struct InterceptRules {
+    rules: Vec<InterceptRule>,
+}
+
+impl InterceptRules {
+    /// Reads and checks the rules file at `path`.
+    pub fn load(path: &Path) -> Result<Self, InterceptError> {
+        let text = std::fs::read_to_string(path).map_err(|error| InterceptError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        let rules: Vec<InterceptRule> =
+            serde_json::from_str(&text).map_err(|error| InterceptError::Json {
+                path: path.to_path_buf(),
+                error,
+            })?;
+        Self::new(rules, path.parent().unwrap_or(Path::new("")))
+    }
+
+    /// Checks `rules` and resolves fulfil `file`s relative to `base`.
+    pub fn new(mut rules: Vec<InterceptRule>, base: &Path) -> Result<Self, InterceptError> {
+        for (index, rule) in rules.iter_mut().enumerate() {
+            let invalid = |reason: &str| InterceptError::InvalidRule {
+                index,
+                reason: reason.to_string(),
+            };
+            let actions = [
+                rule.block.is_some(),
+                rule.rewrite.is_some(),
+                rule.fulfill.is_some(),
+            ];
+            match actions.iter().filter(|set| **set).count() {
+                0 if rule.delay.is_none() => {
+                    return Err(invalid("needs one of block, rewrite, fulfill or delay"));
+                }
+                0 | 1 => {}
+                _ => return Err(invalid("block, rewrite and fulfill are exclusive")),
+            }
+            if let Some(fulfill) = &mut rule.fulfill {
+                if fulfill.body.is_some() && fulfill.file.is_some() {
+                    return Err(invalid("fulfill takes a body or a file, not both"));
+                }
+                if let Some(file) = &mut fulfill.file
+                    && file.is_relative()
+                {
+                    *file = base.join(&*file);
+                }
+            }
+            rule.pattern = Some(glob_regex(&rule.url));
+        }
+        Ok(InterceptRules { rules })
+    }
+
+    pub fn rules(&self) -> &[InterceptRule] {
+        &self.rules
+    }
+
+    /// The first rule matching the request, with what its `*`s matched.
+    pub fn find(&self, url: &str, method: &str) -> Option<(&InterceptRule, Vec<String>)> {
+        self.rules.iter().find_map(|rule| {
+            if let Some(wanted) = &rule.method
+                && !wanted.eq_ignore_ascii_case(method)
+            {
+                return None;
+            }
+            let captures = rule.pattern.as_ref()?.captures(url)?;
+            let matched = captures
+                .iter()
+                .skip(1)
+                .map(|capture| capture.map_or("", |c| c.as_str()).to_string())
+                .collect();
+            Some((rule, matched))
+        })
+    }
+}
+
+// `*` becomes a capture group so rewrites can reuse what it matched.
+fn glob_regex(glob: &str) -> Regex {
+    let mut pattern = String::from("(?is)^");
+    for c in glob.chars() {
+        match c {
+            '*' => pattern.push_str("(.*?)"),
+            '?' => pattern.push('.'),
+            c => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    pattern.push('$');
+    Regex::new(&pattern).expect("escaped glob is a valid regex")
+}
+
+// Replaces `$1`..`$9` with the matching capture; `$$` is a literal `$`.
+fn substitute(template: &str, captures: &[String]) -> String {
+    let mut out = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            out.push(c);
+            continue;
+        }
+        match chars.peek().copied() {
+            Some('$') => {
+                chars.next();
+                out.push('$');
+            }
+            Some(digit @ '1'..='9') => {
+                chars.next();
+                let index = digit as usize - '1' as usize;
+                out.push_str(captures.get(index).map_or("", String::as_str));
+            }
+            _ => out.push('$'),
+        }
+    }
+    out
+}
+
+fn header_entries(headers: impl IntoIterator<Item = (String, String)>) -> Vec<Value> {
+    headers
+        .into_iter()
+        .map(|(name, value)| json!({ "name": name, "value": value }))
+        .collect()
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase)
+        .as_deref()
+    {
+        Some("json") => "application/json",
+        Some("html" | "htm") => "text/html",
+        Some("js" | "mjs") => "text/javascript",
+        Some("css") => "text/css",
+        Some("txt") => "text/plain",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        _ => "application/octet-stream",
+    }
+}
+
+// Answers one `Fetch.requestPaused`.
+async fn handle(page: &CdpSession, rules: &InterceptRules, params: &Value) -> CdpResult<()> {
+    let request_id = params["requestId"].as_str().unwrap_or_default();
+    let request = &params["request"];
+    let url = request["url"].as_str().unwrap_or_default();
+    let method = request["method"].as_str().unwrap_or("GET");
+    let Some((rule, captures)) = rules.find(url, method) else {
+        page.call("Fetch.continueRequest", json!({ "requestId": request_id }))
+            .await?;
+        return Ok(());
+    };
+    log::debug!("Intercepted {} {} with rule {:?}", method, url, rule.url);
+    if let Some(delay) = rule.delay {
+        tokio::time::sleep(Duration::from_millis(delay)).await;
+    }
+    let base64 = &base64::engine::general_purpose::STANDARD;
+    if let Some(reason) = &rule.block {
+        page.call(
+            "Fetch.failRequest",
+            json!({ "requestId": request_id, "errorReason": reason }),
+        )
+        .await?;
+    } else if let Some(fulfill) = &rule.fulfill {
+        let mut headers = fulfill.headers.clone();
+        let body = match &fulfill.file {
+            Some(file) => match std::fs::read(file) {
+                Ok(bytes) => {
+                    if !headers
+                        .keys()
+                        .any(|n| n.eq_ignore_ascii_case("content-type"))
+                    {
+                        headers.insert("Content-Type".into(), content_type(file).into());
+                    }
+                    bytes
+                }
+                Err(e) => {
+                    log::debug!("Cannot read fixture {}: {}", file.display(), e);
+                    page.call(
+                        "Fetch.failRequest",
+                        json!({ "requestId": request_id, "errorReason": "FileNotFound" }),
+                    )
+                    .await?;
+                    return Ok(());
+                }
+            },
+            None => fulfill.body.clone().unwrap_or_default().into_bytes(),
+        };
+        page.call(
+            "Fetch.fulfillRequest",
+            json!({
+                "requestId": request_id,
+                "responseCode": fulfill.status,
+                "responseHeaders": header_entries(headers),
+                "body": base64.encode(body),
+            }),
+        )
+        .await?;
+    } else {
+        let mut params = json!({ "requestId": request_id });
+        if let Some(rewrite) = &rule.rewrite {
+            if let Some(url) = &rewrite.url {
+                params["url"] = json!(substitute(url, &captures));
+            }
+            if let Some(method) = &rewrite.method {
+                params["method"] = json!(method);
+            }
+            if let Some(post_data) = &rewrite.post_data {
+                params["postData"] = json!(base64.encode(post_data));
+            }
+            // Headers given to `continueRequest` replace all of them, so start from the originals.
+            if !rewrite.headers.is_empty() {
+                let mut headers: BTreeMap<String, String> = request["headers"]
+                    .as_object()
+                    .into_iter()
+                    .flatten()
+                    .filter(|(name, _)| {
+                        !rewrite.headers.keys().any(|n| n.eq_ignore_ascii_case(name))
+                    })
+                    .map(|(name, value)| (name.clone(), value.as_str().unwrap_or("").into()))
+                    .collect();
+                headers.extend(rewrite.headers.clone());
+                params["headers"] = json!(header_entries(headers));
+            }
+        }
+        page.call("Fetch.continueRequest", params).await?;
+    }
+    Ok(())
+}
+
+/// Interception that has started; see [`start`].
+pub struct Interceptor {
+    page: CdpSession,
+    rules: Arc<InterceptRules>,
+    events: mpsc::UnboundedReceiver<CdpEvent>,
+}
+
+/// Pauses every request of `page` for the rules to decide on. Requests sent from here on are
+/// intercepted, so navigate only after this returns.
+pub async fn start(page: &CdpSession, rules: InterceptRules) -> CdpResult<Interceptor> {
+    let events = page.subscribe(None);
+    page.call(
+        "Fetch.enable",
+        json!({ "patterns": [{ "urlPattern": "*", "requestStage": "Request" }] }),
+    )
+    .await?;
+    Ok(Interceptor {
+        page: page.clone(),
+        rules: Arc::new(rules),
+        events,
+    })
+}
+
+impl Interceptor {
+    /// Applies the rules until the tab closes. Each request is answered on its own task, so a
+    /// `delay` holds only the request it applies to.
+    pub async fn run(mut self) {
+        while let Some(event) = self.events.recv().await {
+            match event.method.as_str() {
+                "Inspector.detached" => break,
+                "Fetch.requestPaused" => {
+                    let page = self.page.clone();
+                    let rules = self.rules.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = handle(&page, &rules, &event.params).await {
+                            log::debug!("Failed to answer a paused request: {}", e);
+                        }
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+}
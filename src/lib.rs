@@ -0,0 +1,30 @@
+//! `debugchrome` library: a small, typed client for the Chrome DevTools Protocol.
+//!
+//! The `debugchrome` binary is built on top of this crate; other tools can use
+//! it directly instead of shelling out to `debugchrome.exe`.
+//! `browser` finds and launches a local Chrome/Chromium for the client to talk to,
+//! `profile` keeps the named user data directories it runs with, `selector` picks
+//! tabs by URL, title, target id, position or bang id, `bang` parses the `!`
+//! parameters of a `debugchrome:` URL, `wait` holds a page until it is ready,
+//! `tail` streams what a page logs, `har` records its network traffic, `intercept`
+//! blocks, rewrites or answers its requests, `emulate` makes it look like another
+//! device, place or network and `storage` carries its cookies and web storage over.
+//! `monitor` lists the screens and works out where a window goes on them.
+pub mod bang;
+pub mod browser;
+pub mod cdp;
+pub mod emulate;
+pub mod har;
+pub mod intercept;
+pub mod monitor;
+pub mod profile;
+pub mod selector;
+pub mod storage;
+pub mod tail;
+pub mod wait;
+
+pub use cdp::{
+    CaptureScreenshot, CdpClient, CdpEndpoint, CdpError, CdpEvent, CdpResult, CdpSession,
+    CreateTarget, EvaluateResult, ExceptionDetails, ImageFormat, PrintToPdf, TargetInfo, Viewport,
+    WindowBounds, WindowState, get_unique_id,
+};
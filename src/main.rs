@@ -1,163 +1,83 @@
 use base64::Engine;
-use serde_json::Value;
+use debugchrome::bang::{BangError, BangOptions, BangUrl};
+use debugchrome::browser;
+use debugchrome::cdp::{self, list_targets};
+use debugchrome::emulate::Emulation;
+use debugchrome::har;
+use debugchrome::intercept::{self, InterceptRules};
+use debugchrome::monitor;
+use debugchrome::profile::{self, ProfileStore};
+use debugchrome::selector::{TabSelector, select_tabs};
+use debugchrome::storage::{self, StorageState};
+use debugchrome::tail::{LogEntry, tail};
+use debugchrome::wait::{DEFAULT_WAIT_TIMEOUT, wait_for};
+use debugchrome::{
+    CaptureScreenshot, CdpClient, CdpEndpoint, CdpSession, CreateTarget, ImageFormat, PrintToPdf,
+    Viewport, WindowBounds, WindowState,
+};
+use fs2::FileExt;
 use std::fs::File;
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Duration;
 use std::{env, fs, io};
-use tungstenite::Message;
 
 use futures_util::TryFutureExt;
 #[cfg(target_os = "windows")]
 use std::os::windows::process::CommandExt;
 
-#[cfg(target_os = "windows")]
-struct MonitorInfo {
-    hmonitor: HMONITOR,
-    rect: RECT,
-    dpi_scaling: f32,
-}
-
+#[macro_use]
+mod report;
 #[cfg(feature = "uses_gui")]
 mod gui;
 #[cfg(feature = "uses_funny")]
 mod jokes;
+mod register;
 
-#[cfg(target_os = "windows")]
-impl std::fmt::Debug for MonitorInfo {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "MonitorInfo {{ hmonitor: {:?}, rect: {{ left: {}, top: {}, right: {}, bottom: {} }}, dpi_scaling: {} }}",
-            self.hmonitor,
-            self.rect.left,
-            self.rect.top,
-            self.rect.right,
-            self.rect.bottom,
-            self.dpi_scaling
-        )
+#[tokio::main]
+async fn main() {
+    let mut args: Vec<String> = env::args().collect();
+    // --json may appear anywhere; drop it so the positional checks below stay simple
+    if args.iter().any(|arg| arg == "--json") {
+        report::enable_json();
+        args.retain(|arg| arg != "--json");
     }
-}
-
-// Global atomic counter for unique IDs
-static COMMAND_ID_COUNTER: AtomicUsize = AtomicUsize::new(1);
-
-fn get_unique_id() -> usize {
-    COMMAND_ID_COUNTER.fetch_add(1, Ordering::SeqCst)
-}
-#[cfg(target_os = "windows")]
-#[allow(dead_code)]
-fn bring_chrome_to_front_and_resize_with_powershell(bounds: Option<(i32, i32, i32, i32)>) {
-    let base_script = r#"
-        $chrome = Get-Process chrome | Where-Object {
-            $_.MainWindowHandle -ne 0 -and $_.Path -like '*chrome.exe'
-        } | ForEach-Object {
-            $cmdline = (Get-CimInstance Win32_Process -Filter "ProcessId=$($_.Id)").CommandLine
-            if ($cmdline -like '*--remote-debugging-port=*') {
-                $_
-            }
-        } | Select-Object -First 1
-
-        if ($chrome) {
-            $sig = @"
-using System;
-using System.Runtime.InteropServices;
-public static class NativeMethods {
-    [DllImport("user32.dll")]
-    public static extern bool ShowWindowAsync(IntPtr hWnd, int nCmdShow);
-    [DllImport("user32.dll")]
-    public static extern bool SetForegroundWindow(IntPtr hWnd);
-    [DllImport("user32.dll")]
-    public static extern bool AttachThreadInput(uint idAttach, uint idAttachTo, bool fAttach);
-    [DllImport("user32.dll")]
-    public static extern uint GetWindowThreadProcessId(IntPtr hWnd, out uint lpdwProcessId);
-    [DllImport("user32.dll")]
-    public static extern bool MoveWindow(IntPtr hWnd, int X, int Y, int nWidth, int nHeight, bool bRepaint);
-}
-"@
-            Add-Type -TypeDefinition $sig -Language CSharp | Out-Null
-
-            $hWnd = $chrome.MainWindowHandle
-            $currentThreadId = [NativeMethods]::GetWindowThreadProcessId([System.Diagnostics.Process]::GetCurrentProcess().MainWindowHandle, [ref]0)
-            $chromeThreadId = [NativeMethods]::GetWindowThreadProcessId($hWnd, [ref]0)
-            [NativeMethods]::AttachThreadInput($currentThreadId, $chromeThreadId, $true) | Out-Null
-            [NativeMethods]::SetForegroundWindow($hWnd) | Out-Null
-            [NativeMethods]::ShowWindowAsync($hWnd, 9) | Out-Null
-            [NativeMethods]::AttachThreadInput($currentThreadId, $chromeThreadId, $false) | Out-Null
-        }
-    "#;
-
-    let resize_script = if let Some((x, y, w, h)) = bounds {
-        format!(
-            r#"
-            [NativeMethods]::MoveWindow($hWnd, {x}, {y}, {w}, {h}, $true) | Out-Null
-            "#,
-            x = x,
-            y = y,
-            w = w,
-            h = h
-        )
-    } else {
-        String::new()
-    };
-
-    let ps_script = format!("{}{}", base_script, resize_script);
-
-    let _ = Command::new("powershell")
-        .arg("-NoProfile")
-        .arg("-Command")
-        .arg(ps_script)
-        .status();
-}
-
-fn split_and_process_url(raw_url: &str) -> (String, std::collections::HashMap<String, String>) {
-    // Split the URL into the main part and the `!` parameters
-    let mut parts = raw_url.splitn(2, "!");
-    let base_url = parts.next().unwrap_or("").to_string();
-    let mut bang_params = std::collections::HashMap::new();
-
-    if let Some(bang_part) = parts.next() {
-        // Split the `!` parameters and process them
-        for param in bang_part.split('&') {
-            if let Some((key, value)) = param.split_once('=') {
-                bang_params.insert(key.trim_start_matches('!').to_string(), value.to_string());
-            } else {
-                // Handle flags like `!close` without `=`
-                bang_params.insert(param.trim_start_matches('!').to_string(), String::new());
-            }
-        }
+    // Script results and tailed logs are the output; keep progress lines off stdout for pipelines.
+    if args.iter().any(|arg| {
+        arg == "--script"
+            || arg == "--script-file"
+            || arg == "--tail"
+            || arg == "--record-har"
+            || arg == "--keep-intercepting"
+            || arg == "--keep-emulating"
+            || arg == "--keep-injected"
+    }) || args.get(1).is_some_and(|raw_url| {
+        BangUrl::parse(&strip_protocol(raw_url)).is_ok_and(|parsed| parsed.options.tail)
+    }) {
+        report::silence();
     }
+    let code = report::finish(run(args).await);
+    std::process::exit(code);
+}
 
-    (base_url, bang_params)
+// The url of a `debugchrome:` or `debugchrome://` link.
+fn strip_protocol(raw_url: &str) -> String {
+    raw_url
+        .replacen("debugchrome://", "", 1)
+        .replacen("debugchrome:", "", 1)
 }
 
-#[tokio::main]
-async fn main() -> std::io::Result<()> {
-    let args: Vec<String> = env::args().collect();
+async fn run(args: Vec<String>) -> std::io::Result<()> {
     let mut redirect_seconds: Option<u64> = None;
-    let mut use_direct = false;
-    let mut i = 2;
-    while i < args.len() {
-        if args[i] == "--direct" {
-            use_direct = true;
-            i += 1;
-        } else if args[i] == "--redirect-seconds" && i + 1 < args.len() {
-            if let Ok(val) = args[i + 1].parse::<u64>() {
-                redirect_seconds = Some(val);
-            }
-            i += 2;
-        } else {
-            i += 1;
-        }
-    }
+    // Relative paths on the command line are resolved against where we were started from
+    let invocation_dir = std::env::current_dir().ok();
     // Set the current working directory to the directory of the executing binary
-    if let Ok(exe_path) = std::env::current_exe() {
-        if let Some(exe_dir) = exe_path.parent() {
-            std::env::set_current_dir(exe_dir)?;
-            log::debug!("Working directory set to: {:?}", exe_dir);
-        }
+    if let Ok(exe_path) = std::env::current_exe()
+        && let Some(exe_dir) = exe_path.parent()
+    {
+        std::env::set_current_dir(exe_dir)?;
+        log::debug!("Working directory set to: {:?}", exe_dir);
     }
     let log_file_path = "debugchrome.log";
     let append_log = if let Ok(metadata) = fs::metadata(log_file_path) {
@@ -178,6 +98,11 @@ async fn main() -> std::io::Result<()> {
     log::debug!("DebugChrome started with args: {:?}", args);
     setup_panic_hook(); // must be done after logger initialization
 
+    let cdp_endpoint = resolve_cdp_endpoint(&args);
+    let endpoint = cdp_endpoint.to_string();
+    log::debug!("Using CDP endpoint: {}", endpoint);
+    report::endpoint(&endpoint);
+
     if args.len() > 1 {
         let raw_url = &args[1];
         if raw_url == "debugchrome:"
@@ -187,8 +112,8 @@ async fn main() -> std::io::Result<()> {
         {
             #[cfg(feature = "uses_gui")]
             {
-                println!("Starting GUI...");
-                if let Err(e) = gui::start_gui().await {
+                say!("Starting GUI...");
+                if let Err(e) = gui::start_gui(cdp_endpoint).await {
                     eprintln!("GUI error: {}", e);
                     log::error!("GUI error: {}", e);
                     std::process::exit(1);
@@ -205,18 +130,60 @@ async fn main() -> std::io::Result<()> {
         }
 
         log::debug!("Received URL: {}", raw_url);
-        println!("Received URL: {:?}", raw_url);
+        say!("Received URL: {:?}", raw_url);
     }
     let log_file_path = std::fs::canonicalize(log_file_path)?.display().to_string();
-    println!("Log file: {}", log_file_path);
-
+    say!("Log file: {}", log_file_path);
+
+    // Value of `flag` in the detached helper commands below.
+    let value = |flag: &str| {
+        args.iter()
+            .position(|arg| arg == flag)
+            .and_then(|at| args.get(at + 1))
+            .map(String::as_str)
+    };
+    if args.len() > 2 && args[1] == "--record-har" {
+        let target_id = &args[2];
+        report::command("record-har");
+        return record_har(
+            &endpoint,
+            target_id,
+            value("--har").unwrap_or("debugchrome.har"),
+            value("--page-id").unwrap_or(target_id),
+        )
+        .await
+        .map_err(|e| io::Error::other(e.to_string()));
+    }
+    if args.len() > 2 && args[1] == "--keep-intercepting" {
+        let target_id = &args[2];
+        report::command("keep-intercepting");
+        let rules = value("--intercept").unwrap_or_default();
+        return keep_intercepting(&endpoint, target_id, rules)
+            .await
+            .map_err(|e| io::Error::other(e.to_string()));
+    }
+    if args.len() > 2 && args[1] == "--keep-emulating" {
+        let target_id = &args[2];
+        report::command("keep-emulating");
+        let emulation = value("--emulation").unwrap_or("{}");
+        return keep_emulating(&endpoint, target_id, emulation)
+            .await
+            .map_err(|e| io::Error::other(e.to_string()));
+    }
+    if args.len() > 2 && args[1] == "--keep-injected" {
+        let target_id = &args[2];
+        report::command("keep-injected");
+        return keep_injected(&endpoint, target_id)
+            .await
+            .map_err(|e| io::Error::other(e.to_string()));
+    }
     if args.len() > 2 && args[1] == "--close-target" {
         let target_id = &args[2];
         let timeout_seconds: u64 = if let Some(arg) = args.get(4) {
             match arg.parse::<u64>() {
                 Ok(value) => value,
                 Err(_) => {
-                    println!("Invalid timeout value provided: {}", arg);
+                    say!("Invalid timeout value provided: {}", arg);
                     0
                 }
             }
@@ -231,99 +198,188 @@ async fn main() -> std::io::Result<()> {
         );
         std::thread::sleep(std::time::Duration::from_secs(timeout_seconds));
 
-        if let Err(e) = close_tab_by_target_id(target_id).await {
+        report::command("close-target");
+        let result = close_tab_by_target_id(&endpoint, target_id).await;
+        report::action(target_id, "close", &result);
+        if let Err(e) = result {
             log::debug!("Failed to close target {}: {}", target_id, e);
         } else {
             log::debug!("Successfully closed target {}", target_id);
         }
-        std::process::exit(0);
-    }
-    if args.len() > 1 && args[1] == "--register" {
-        let exe_path = std::env::current_exe()?
-            .to_str()
-            .unwrap()
-            .replace("\\", "\\\\");
-        let reg_content = format!(
-            "Windows Registry Editor Version 5.00\n\n\
-            [HKEY_CLASSES_ROOT\\debugchrome]\n\
-            @=\"URL:Debug Chrome Protocol\"\n\
-            \"URL Protocol\"=\"\"\n\
-            [HKEY_CLASSES_ROOT\\debugchrome\\shell\\open\\command]\n\
-            @=\"\\\"{}\\\" \\\"%1\\\"\"\n",
-            exe_path
-        );
-        let mut file = File::create("debugchrome.reg")?;
-        file.write_all(reg_content.as_bytes())?;
-        println!("Written debugchrome.reg with path: {}", exe_path);
-        if let Err(e) = Command::new("regedit")
-            .args(["/s", "debugchrome.reg"])
-            .spawn()
-            .and_then(|mut child| child.wait())
+        return Ok(());
+    }
+    if args.len() > 1 {
+        let registration = match args[1].as_str() {
+            "--register" => Some(register::register(args.iter().any(|arg| arg == "--user"))),
+            "--unregister" => Some(register::unregister()),
+            "--status" | "--doctor" => Some(register::status()),
+            _ => None,
+        };
+        if let Some(result) = registration {
+            report::command(args[1].trim_start_matches('-'));
+            return result;
+        }
+    }
+    if args.len() > 1 && args[1] == "--serve" {
+        report::command("serve");
+        #[cfg(feature = "uses_gui")]
         {
-            println!("Failed to register debugchrome protocol: {}", e);
-            println!("Press Enter to run an elevated powershell or Ctrl+C to exit.");
-            let mut input = String::new();
-            let _ = std::io::stdin().read_line(&mut input);
-            if let Err(e) = Command::new("powershell")
-                .args([
-                    "-Command",
-                    "Start-Process",
-                    "powershell",
-                    "-ArgumentList",
-                    &format!("'{}'", "regedit /s debugchrome.reg"),
-                    "-Verb",
-                    "runAs",
-                ])
-                .spawn()
-                .and_then(|mut child| child.wait())
-            {
-                println!("Failed to elevate and register debugchrome protocol: {}", e);
-            } else {
-                println!("Registered debugchrome protocol successfully with elevation.");
-            }
-        } else {
-            println!("Registered debugchrome protocol successfully.");
+            let addr = args
+                .get(2)
+                .filter(|addr| !addr.starts_with("--"))
+                .map_or(gui::SERVER_ADDR, String::as_str);
+            gui::serve(cdp_endpoint, addr).await;
+            return Ok(());
         }
-        return Ok(());
+        #[cfg(not(feature = "uses_gui"))]
+        return Err(io::Error::other(
+            "The HTTP API is not enabled. Rebuild with the `uses_gui` feature.",
+        ));
+    }
+    let profiles = resolve_profile_store(&args, invocation_dir.as_deref());
+    if args.len() > 1 && args[1] == "--profile" {
+        report::command("profile");
+        return run_profile_command(&profiles, &args[2..]);
     }
-    let mut keep_focus = false;
     // Capture the current focused window if !keep_focus is set
     #[cfg(target_os = "windows")]
     let previous_window = get_focused_window();
+    if args.len() > 2 && args[1] == "--tail" {
+        let selector: TabSelector = args[2]
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        report::command("tail");
+        let tabs = select_tabs(&endpoint, &selector).await.map_err(|e| {
+            log::debug!("Failed to search tabs: {}", e);
+            report::status(report::Status::CdpUnreachable);
+            io::Error::new(
+                io::ErrorKind::NotConnected,
+                format!("CDP endpoint {} is not reachable: {}", endpoint, e),
+            )
+        })?;
+        if tabs.is_empty() {
+            log::debug!("No tab matches {:?}", selector);
+            report::status(report::Status::NotFound);
+            return Ok(());
+        }
+        report::status(report::Status::Found);
+        for tab in &tabs {
+            report::tab(&tab.id, &tab.title, &tab.url);
+            if let TabSelector::Bang(bang_id) = &selector {
+                report::bang_id(&tab.id, bang_id);
+            }
+        }
+        let target_ids: Vec<String> = tabs.into_iter().map(|tab| tab.id).collect();
+        tail_tabs(&endpoint, &target_ids).await;
+        return Ok(());
+    }
+    if args.len() > 2 && (args[1] == "--export-storage" || args[1] == "--import-storage") {
+        let export = args[1] == "--export-storage";
+        report::command(args[1].trim_start_matches('-'));
+        let path = invocation_path(&args[2], invocation_dir.as_deref());
+        let targets = list_targets(&endpoint).await.map_err(|e| {
+            report::status(report::Status::CdpUnreachable);
+            io::Error::new(
+                io::ErrorKind::NotConnected,
+                format!("CDP endpoint {} is not reachable: {}", endpoint, e),
+            )
+        })?;
+        let tabs: Vec<cdp::TargetInfo> = targets
+            .into_iter()
+            .filter(|tab| tab.target_type == "page")
+            .collect();
+        let result = if export {
+            let origins: Vec<String> = args
+                .windows(2)
+                .filter(|pair| pair[0] == "--origin")
+                .map(|pair| pair[1].trim_end_matches('/').to_string())
+                .collect();
+            export_storage(&endpoint, &tabs, &path, &origins).await
+        } else {
+            import_storage(&endpoint, &tabs, &path).await
+        };
+        report::set("path", path.display().to_string());
+        return result.map_err(|e| io::Error::other(e.to_string()));
+    }
     if args.len() > 2 && args[1] == "--search" {
-        let search_id = &args[2];
-        let close_tab = args.get(3).map(|arg| arg == "--close").unwrap_or(false);
-
-        match search_tabs_for_bang_id(search_id).await {
-            Ok(Some((target_id, title, url))) => {
-                log::debug!("Found tab with bangId {}: {} ({})", search_id, title, url);
-
-                if close_tab {
-                    log::debug!("Closing tab with bangId {}...", search_id);
-                    if let Err(e) = close_tab_by_target_id(&target_id).await {
-                        log::debug!("Failed to close tab: {}", e);
-                    } else {
-                        log::debug!("Tab with bangId {} closed successfully.", search_id);
+        let selector: TabSelector = args[2]
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        // --close, --refresh and --screenshot [path] plus the screenshot options
+        let mut options = BangOptions::default();
+        options
+            .apply_args(&args[3..])
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        resolve_intercept_rules(&mut options, invocation_dir.as_deref())?;
+        resolve_storage_file(&mut options, invocation_dir.as_deref())?;
+        resolve_output_paths(&mut options, invocation_dir.as_deref());
+        let script = script_args(&args[3..], invocation_dir.as_deref(), &mut options)?;
+
+        report::command("search");
+        let mut failure = None;
+        match select_tabs(&endpoint, &selector).await {
+            Ok(tabs) => {
+                if tabs.is_empty() {
+                    log::debug!("No tab matches {:?}", selector);
+                    report::status(report::Status::NotFound);
+                } else {
+                    report::status(report::Status::Found);
+                }
+                for tab in &tabs {
+                    say!("{}\t{}\t{}", tab.id, tab.title, tab.url);
+                    report::tab(&tab.id, &tab.title, &tab.url);
+                    if let TabSelector::Bang(bang_id) = &selector {
+                        report::bang_id(&tab.id, bang_id);
+                    }
+                    prepare_open_tab(&endpoint, &tab.id, &options, tabs.len()).await;
+                    if let Some(e) =
+                        run_tab_actions(&endpoint, &tab.id, &options, script.as_ref(), tabs.len())
+                            .await
+                    {
+                        failure.get_or_insert(e);
+                    }
+                    if options.close {
+                        log::debug!("Closing tab {}...", tab.id);
+                        let result = close_tab_by_target_id(&endpoint, &tab.id).await;
+                        report::action(&tab.id, "close", &result);
+                        if let Err(e) = result {
+                            log::debug!("Failed to close tab: {}", e);
+                        } else {
+                            log::debug!("Tab {} closed successfully.", tab.id);
+                        }
                     }
                 }
-            }
-            Ok(None) => {
-                log::debug!("No tab found with bangId = {}", search_id);
+                if options.tail && !options.close {
+                    let target_ids: Vec<String> = tabs.into_iter().map(|tab| tab.id).collect();
+                    tail_tabs(&endpoint, &target_ids).await;
+                }
             }
             Err(e) => {
                 log::debug!("Failed to search tabs: {}", e);
+                report::status(report::Status::CdpUnreachable);
+                return Err(io::Error::new(
+                    io::ErrorKind::NotConnected,
+                    format!("CDP endpoint {} is not reachable: {}", endpoint, e),
+                ));
             }
         }
         #[cfg(target_os = "windows")]
-        finalize_actions(previous_window, keep_focus);
-        return Ok(());
+        finalize_actions(previous_window, false);
+        return failure.map_or(Ok(()), |e| Err(io::Error::other(e)));
     }
     let mut use_direct = false;
     if args.len() > 1 {
-        let raw_url = &args[1];
-        let translated = raw_url.replacen("debugchrome://", "", 1);
-        let translated = translated.replacen("debugchrome:", "", 1);
-        let (clean_url, mut bangs) = split_and_process_url(&translated);
+        let translated = strip_protocol(&args[1]);
+        let invalid_input = |e: BangError| io::Error::new(io::ErrorKind::InvalidInput, e);
+        let BangUrl {
+            url: clean_url,
+            mut options,
+        } = BangUrl::parse(&translated).map_err(invalid_input)?;
+        // --<bang> flags (--headless, --timeout 10, ...) override the URL's bangs
+        options.apply_args(&args[2..]).map_err(invalid_input)?;
+        resolve_intercept_rules(&mut options, invocation_dir.as_deref())?;
+        resolve_storage_file(&mut options, invocation_dir.as_deref())?;
+        resolve_output_paths(&mut options, invocation_dir.as_deref());
         let mut i = 2;
         while i < args.len() {
             if args[i] == "--direct" {
@@ -339,52 +395,82 @@ async fn main() -> std::io::Result<()> {
             }
         }
         // If !id is present and empty, assign a new one based on time
-        if bangs.contains_key("id") && bangs.get("id").map(|v| v.is_empty()).unwrap_or(false) {
+        if options.id.as_deref() == Some("") {
             let timestamp_id = chrono::Local::now().format("%Y%m%d%H%M%S%3f").to_string();
-            bangs.insert("id".to_string(), timestamp_id);
+            options.id = Some(timestamp_id);
         }
-        let user_data_dir = std::env::temp_dir().join("debugchrome");
+        // !profile=<name> launches with a persistent profile instead of a throwaway one
+        let profile_dir = match options.profile.as_deref().filter(|name| !name.is_empty()) {
+            Some(name) => Some(profiles.open(name)?),
+            None => None,
+        };
+        let user_data_dir = profile_dir
+            .clone()
+            .unwrap_or_else(|| std::env::temp_dir().join("debugchrome"));
+        // !host and !port override the endpoint for this URL only
+        let cdp_endpoint = apply_endpoint_bangs(cdp_endpoint, &options);
+        let endpoint = cdp_endpoint.to_string();
+        report::command("open");
+        report::endpoint(&endpoint);
         // Check if the !keep_focus parameter is present
-        keep_focus = bangs.get("keep_focus").is_some();
+        let keep_focus = options.keep_focus;
         log::debug!("keep_focus: {}", keep_focus);
+        let headless = options.headless;
+        let viewport = headless_viewport(&options);
+        log::debug!("headless: {} viewport: {:?}", headless, viewport);
 
-        // --- SCRIPT ARGUMENT HANDLING ---
-        let mut script_to_run: Option<String> = None;
-        let mut i = 2;
-        while i < args.len() {
-            if args[i] == "--script" && i + 1 < args.len() {
-                script_to_run = Some(args[i + 1].clone());
-                i += 2;
-            } else if args[i] == "--script-file" && i + 1 < args.len() {
-                let path = &args[i + 1];
-                match std::fs::read_to_string(path) {
-                    Ok(contents) => script_to_run = Some(contents),
-                    Err(e) => {
-                        println!("Failed to read script file '{}': {}", path, e);
-                        log::debug!("Failed to read script file '{}': {}", path, e);
-                    }
-                }
-                i += 2;
-            } else {
-                i += 1;
-            }
-        }
+        let script = script_args(&args[2..], invocation_dir.as_deref(), &mut options)?;
+        // The first action that failed; it fails the run once every action is done.
+        let mut failure: Option<String> = None;
 
         // Check if the CDP server is running
-        if !is_cdp_server_running().await {
+        if !is_cdp_server_running(&endpoint).await {
+            if !cdp_endpoint.is_local() {
+                say!("CDP endpoint {} is not reachable.", endpoint);
+                log::debug!("CDP endpoint {} is not reachable.", endpoint);
+                report::status(report::Status::CdpUnreachable);
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::NotConnected,
+                    format!("CDP endpoint {} is not reachable", endpoint),
+                ));
+            }
             log::debug!(
                 "CDP server is not running. Preparing Chrome profile and launching Chrome..."
             );
 
             // Prepare Chrome profile
-            let user_data_dir = prepare_chrome_profile(true)?;
-            log::debug!("User data cloned to: {}", user_data_dir.display());
+            let user_data_dir = match &profile_dir {
+                Some(dir) => dir.clone(),
+                None => prepare_chrome_profile(true)?,
+            };
+            log::debug!("Using user data dir: {}", user_data_dir.display());
 
             // Launch Chrome
-            launch_chrome(&user_data_dir)?;
+            launch_chrome(
+                &user_data_dir,
+                cdp_endpoint.port,
+                &headless_args(headless, viewport),
+            )?;
             log::debug!("Chrome launched successfully. Waiting for the CDP server to start...");
+            if !wait_for_cdp_server(&endpoint, Duration::from_secs(15)).await {
+                log::debug!("CDP server did not come up on {}", endpoint);
+                report::status(report::Status::CdpUnreachable);
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    format!("Chrome did not open DevTools on {}", endpoint),
+                ));
+            }
         } else {
             log::debug!("CDP server is already running.");
+            if headless {
+                log::debug!("!headless only applies to a browser debugchrome launches itself.");
+            }
+            if let Some(dir) = &profile_dir {
+                log::debug!(
+                    "!profile only applies to a browser debugchrome launches itself; {} is not used.",
+                    dir.display()
+                );
+            }
         }
 
         // Check if the bangId is already open
@@ -393,99 +479,83 @@ async fn main() -> std::io::Result<()> {
             Err(e) => {
                 log::debug!("Failed to parse URL: {}", e);
                 // sleep(std::time::Duration::from_secs(30)); // Ensure sleep even on error
-                return Err(std::io::Error::new(std::io::ErrorKind::Other, e));
+                return Err(std::io::Error::other(e));
             }
         };
-        let open_window = bangs.get("openwindow").is_some();
-        let close = bangs.get("close").is_some();
-        let refresh = bangs.get("refresh").is_some();
-        let screenshot = bangs.get("screenshot").is_some();
-        let timeout_seconds = bangs.get("timeout").and_then(|v| v.parse::<u64>().ok());
-        let monitor_index = bangs.get("monitor").and_then(|v| v.parse::<usize>().ok());
-        let dpi_scaling_enabled = bangs
-            .get("dpi")
-            .map(|v| v.eq_ignore_ascii_case("true"))
-            .unwrap_or(false);
-        log::debug!("DPI scaling enabled: {}", dpi_scaling_enabled);
+        let open_window = options.openwindow;
+        let close = options.close;
+        let timeout_seconds = options.timeout;
+        log::debug!("DPI scaling enabled: {}", options.dpi);
 
-        #[cfg(target_os = "windows")]
-        let bounds = get_screen_bounds(&bangs, monitor_index, dpi_scaling_enabled);
-        #[cfg(not(target_os = "windows"))]
-        let bounds: Option<(i32, i32, i32, i32)> = None;
-        log::debug!("bangs: {:?}", bangs);
+        let bounds = get_screen_bounds(&options);
+        log::debug!("options: {:?}", options);
         log::debug!("Parsed URL: {}", parsed_url);
-        if let Some(bang_id) = bangs.get("id").cloned() {
-            log::debug!("Searching for bangId: {}", bang_id);
-            if let Some((target_id, title, _url)) = search_tabs_for_bang_id(&bang_id)
-                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
-                .await?
-            {
-                log::debug!(
-                    "Tab with bangId {} title {} is already open: {}",
-                    bang_id,
-                    title,
-                    target_id
-                );
-
-                // Activate the tab
-                if let Err(e) = activate_tab(&target_id).await {
-                    log::debug!("Failed to activate tab: {}", e);
-                }
-                if let Some((x, y, w, h)) = bounds {
-                    println!("Setting window bounds: x={}, y={}, w={}, h={}", x, y, w, h);
-                    #[cfg(target_os = "windows")]
-                    set_window_bounds(&target_id, x, y, w, h).await.ok();
-                    #[cfg(target_os = "windows")]
-                    bring_chrome_to_front_and_resize_with_powershell(bounds);
+        // !select= picks existing tabs; otherwise !id= finds the tab it tagged before.
+        let selector = match &options.select {
+            Some(spec) => Some(
+                spec.parse::<TabSelector>()
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?,
+            ),
+            None => options.id.clone().map(TabSelector::Bang),
+        };
+        if let Some(selector) = selector {
+            log::debug!("Searching for tabs matching {:?}", selector);
+            let tabs = select_tabs(&endpoint, &selector)
+                .map_err(std::io::Error::other)
+                .await?;
+            #[cfg_attr(not(target_os = "windows"), allow(unused_variables))]
+            let bang_id = options.id.clone().unwrap_or_default();
+            for (index, tab) in tabs.iter().enumerate() {
+                let target_id = &tab.id;
+                log::debug!("Tab {} title {} is already open", target_id, tab.title);
+                report::status(report::Status::Found);
+                report::tab(target_id, &tab.title, &tab.url);
+                if let TabSelector::Bang(bang_id) = &selector {
+                    report::bang_id(target_id, bang_id);
                 }
 
-                // Execute script if provided
-                if let Some(ref script) = script_to_run {
-                    println!("Executing script on tab...");
-                    if let Err(e) = execute_script_on_tab(&target_id, script).await {
-                        println!("Failed to execute script: {}", e);
-                        log::debug!("Failed to execute script: {}", e);
+                // Only the first match is brought forward and placed; the actions below apply to all.
+                if index == 0 {
+                    let result = activate_tab(&endpoint, target_id).await;
+                    report::action(target_id, "activate", &result);
+                    if let Err(e) = result {
+                        log::debug!("Failed to activate tab: {}", e);
+                    }
+                    if !headless && (bounds.is_some() || options.state.is_some()) {
+                        let result =
+                            place_window(&endpoint, target_id, bounds, options.state).await;
+                        report_placement(target_id, &result);
                     }
                 }
 
-                if refresh {
-                    log::debug!("Refreshing tab with bangId {}: {}", bang_id, target_id);
-                    refresh_tab(&target_id).await.ok();
+                prepare_open_tab(&endpoint, target_id, &options, tabs.len()).await;
+                if let Some(e) =
+                    run_tab_actions(&endpoint, target_id, &options, script.as_ref(), tabs.len())
+                        .await
+                {
+                    failure.get_or_insert(e);
                 }
-                log::debug!(
-                    "Tab with bangId {} is already open, activating it.",
-                    bang_id
-                );
                 #[cfg(target_os = "windows")]
-                if let Some((target_id, title, page_url)) =
-                    search_tabs_for_bang_id(&bang_id).await.ok().flatten()
-                {
-                    println!("Found tab with bangId {}: {} {}", bang_id, target_id, title);
-                    if let Some(hwnd) = find_chrome_hwnd_by_title(&title, &bang_id) {
-                        println!(
-                            "HWND: {:?}\nPID: {:?}\nTITLE: {}\nTARGET: {}\nPAGE_URL: {}",
-                            hwnd,
-                            unsafe {
-                                let mut pid = 0;
-                                winapi::um::winuser::GetWindowThreadProcessId(hwnd, &mut pid);
-                                pid
-                            },
-                            title,
-                            target_id,
-                            page_url
-                        );
-                        log::debug!("Found HWND for tab '{}': {:?}", title, hwnd);
-                    } else {
-                        println!("No HWND found for tab '{}'", title);
-                        log::debug!("No HWND found for tab '{}'", title);
+                if !headless {
+                    match find_chrome_hwnd_by_title(&tab.title, &bang_id) {
+                        Some(hwnd) => {
+                            report_window(hwnd, target_id, &tab.title, &tab.url);
+                            log::debug!("Found HWND for tab '{}': {:?}", tab.title, hwnd);
+                        }
+                        None => {
+                            say!("No HWND found for tab '{}'", tab.title);
+                            log::debug!("No HWND found for tab '{}'", tab.title);
+                        }
                     }
                 }
                 if close {
-                    log::debug!("Closing tab with bangId {}...", target_id);
-                    if let Err(e) = close_tab_by_target_id(&target_id).await {
+                    log::debug!("Closing tab {}...", target_id);
+                    let result = close_tab_by_target_id(&endpoint, target_id).await;
+                    report::action(target_id, "close", &result);
+                    if let Err(e) = result {
                         log::debug!("Failed to close tab: {}", e);
                     } else {
-                        log::debug!("Tab with bangId {} closed successfully.", target_id);
+                        log::debug!("Tab {} closed successfully.", target_id);
                     }
                 }
 
@@ -495,8 +565,27 @@ async fn main() -> std::io::Result<()> {
                         timeout_seconds,
                         target_id
                     );
-                    spawn_timeout_closer(target_id.clone(), timeout_seconds).ok();
+                    let result =
+                        spawn_timeout_closer(&endpoint, target_id.clone(), timeout_seconds);
+                    report::action(target_id, "timeout", &result);
+                }
+            }
+            if !tabs.is_empty() {
+                #[cfg(target_os = "windows")]
+                finalize_actions(previous_window, keep_focus);
+                if options.tail && !close {
+                    let target_ids: Vec<String> = tabs.into_iter().map(|tab| tab.id).collect();
+                    tail_tabs(&endpoint, &target_ids).await;
                 }
+                return failure.map_or(Ok(()), |e| Err(io::Error::other(e)));
+            }
+            // Opening the URL would run the actions meant for the matches on a new tab.
+            if let Some(spec) = &options.select
+                && !options.open_if_missing
+            {
+                say!("No tab matches {}", spec);
+                log::debug!("No tab matches {:?}; not opening {}", selector, clean_url);
+                report::status(report::Status::NotFound);
                 #[cfg(target_os = "windows")]
                 finalize_actions(previous_window, keep_focus);
                 return Ok(());
@@ -504,51 +593,54 @@ async fn main() -> std::io::Result<()> {
         }
         log::debug!("{} not found, opening.", clean_url);
         let result = if open_window {
-            open_window_via_devtools(&clean_url, use_direct, redirect_seconds, &bangs).await
+            open_window_via_devtools(
+                &endpoint,
+                &clean_url,
+                use_direct,
+                redirect_seconds,
+                &options,
+            )
+            .await
         } else {
-            open_tab_via_devtools_and_return_id(&clean_url, &bangs).await
+            open_tab_via_devtools_and_return_id(&endpoint, &clean_url, &options).await
         };
         #[cfg(target_os = "windows")]
         finalize_actions(previous_window, keep_focus);
+        if let Err(e) = &result {
+            log::debug!("Failed to open {} via DevTools: {}", clean_url, e);
+            report::error(format!("DevTools could not open the tab: {}", e));
+        }
         if let Ok(target_id) = result {
-            // if let Some((x, y, w, h)) = bounds {
-            //     #[cfg(target_os = "windows")]
-            //     set_window_bounds(&target_id, x, y, w, h).await.ok();
-            //     #[cfg(target_os = "windows")]
-            //     bring_chrome_to_front_and_resize_with_powershell(bounds);
-            // }
+            report::status(report::Status::Opened);
+            report::tab(&target_id, "", &clean_url);
+            if let Some(bang_id) = &options.id {
+                report::bang_id(&target_id, bang_id);
+            }
+            if !headless && (bounds.is_some() || options.state.is_some()) {
+                let result = place_window(&endpoint, &target_id, bounds, options.state).await;
+                report_placement(&target_id, &result);
+            }
             // if let Some(hwnd) = find_chrome_hwnd_by_title(&target_id) {
             //     bring_hwnd_to_front(hwnd);
             // } else {
             //     log::debug!("Failed to find Chrome window with title '{}'.",&target_id);
             // }
-            if screenshot {
-                if let Err(e) = take_screenshot(&target_id).await {
-                    log::debug!("Failed to take screenshot: {}", e);
-                    // std::thread::sleep(std::time::Duration::from_secs(3)); // Ensure sleep even on error
-                    #[cfg(target_os = "windows")]
-                    finalize_actions(previous_window, keep_focus);
-                    return Err(std::io::Error::new(
-                        std::io::ErrorKind::Other,
-                        format!("{}", e),
-                    ));
-                }
-            }
-
-            // Execute script if provided
-            if let Some(ref script) = script_to_run {
-                println!("Executing script on tab...");
-                if let Err(e) = execute_script_on_tab(&target_id, script).await {
-                    println!("Failed to execute script: {}", e);
-                    log::debug!("Failed to execute script: {}", e);
-                }
+            if let Some(e) =
+                run_tab_actions(&endpoint, &target_id, &options, script.as_ref(), 1).await
+            {
+                failure.get_or_insert(e);
             }
 
             // Call set_bang_id to set the bangId in the tab
             log::debug!("Setting bangId in the tab...{}", &clean_url);
-            if let Err(e) =
-                set_bang_id_session(&target_id, &bangs.get("id").cloned().unwrap_or_default()).await
-            {
+            let result = set_bang_id_session(
+                &endpoint,
+                &target_id,
+                &options.id.clone().unwrap_or_default(),
+            )
+            .await;
+            report::action(&target_id, "set_bang_id", &result);
+            if let Err(e) = result {
                 log::debug!("Failed to set bangId: {}", e);
             }
             if let Some(timeout_seconds) = timeout_seconds {
@@ -557,106 +649,295 @@ async fn main() -> std::io::Result<()> {
                     timeout_seconds,
                     target_id
                 );
-                spawn_timeout_closer(target_id.clone(), timeout_seconds).ok();
+                let result = spawn_timeout_closer(&endpoint, target_id.clone(), timeout_seconds);
+                report::action(&target_id, "timeout", &result);
             }
-        } else {
-            let window_position = if let Some((x, y, _, _)) = bounds {
-                Some(format!("--window-position={},{}", x, y))
-            } else {
-                None
-            };
-
-            let window_size = if let Some((_, _, w, h)) = bounds {
-                Some(format!("--window-size={},{}", w, h))
-            } else {
-                None
-            };
-
-            let mut args = String::from(
-                "/C start  chrome.exe --remote-debugging-port=9222 --enable-automation --no-first-run",
-            );
-            args.push_str(&format!(
-                " --user-data-dir={} {}",
-                user_data_dir.display(),
-                clean_url
-            ));
-
-            if let Some(position) = window_position {
-                args.push_str(&format!(" {}", position));
+            // Last, since it only returns once the tab has closed.
+            if options.tail {
+                tail_tabs(&endpoint, std::slice::from_ref(&target_id)).await;
             }
-
-            if let Some(size) = window_size {
-                args.push_str(&format!(" {}", size));
+        } else {
+            let mut extra_args = headless_args(headless, viewport);
+            if let Some((x, y, w, h)) = bounds.filter(|_| !headless) {
+                extra_args.push(format!("--window-position={},{}", x, y));
+                extra_args.push(format!("--window-size={},{}", w, h));
             }
+            extra_args.push(clean_url.clone());
 
-            Command::new("cmd").args(args.split_whitespace()).spawn()?;
+            launch_chrome(&user_data_dir, cdp_endpoint.port, &extra_args)?;
         }
 
         log::debug!("Requested debug Chrome with URL: {}", translated);
+        #[cfg(target_os = "windows")]
+        finalize_actions(previous_window, keep_focus);
+        if let Some(e) = failure {
+            return Err(io::Error::other(e));
+        }
     } else {
-        println!("Usage:");
-        println!(
+        say!("Usage:");
+        say!(
             "  debugchrome.exe \"debugchrome:https://www.rust-lang.org?!x=0&!y=0&!w=800&!h=600&!id=123\""
         );
-        println!("  debugchrome.exe --search 123");
-        println!("  debugchrome.exe --register");
+        say!(
+            "  debugchrome.exe --search <123 | bang:<id> | url:<glob> | title:</regex/> | target:<id> | index:<n>> [--close] [--refresh] [--screenshot [path]] [--format png|jpeg|webp] [--quality n] [--fullpage] [--clip <css>] [--pdf [path]] [--noview] [--wait <condition>] [--wait-timeout <seconds>] [--inject-script <js>] [--script <js>] [--har [path]] [--intercept <rules.json>] [--storage <file>] [--tail]"
+        );
+        say!(
+            "  emulation: [--device <preset>] [--ua <agent>] [--geo <lat,lon>] [--tz <zone>] [--locale <tag>] [--offline] [--throttle slow-3g|3g|4g] [--colorscheme light|dark]"
+        );
+        say!("  debugchrome.exe --tail <selector>");
+        say!(
+            "  debugchrome.exe --export-storage <file> [--origin <origin>]... | --import-storage <file>"
+        );
+        say!(
+            "  debugchrome.exe --profile list | path <name> | clone <src|@system> <dst> | delete <name>"
+        );
+        say!("  debugchrome.exe --register [--user] | --unregister | --status | --doctor");
+        say!(
+            "  debugchrome.exe --serve [host:port] runs the HTTP API alone, default 127.0.0.1:5800"
+        );
+        say!("  --json prints one JSON document with the tabs found and actions run");
+        say!(
+            "  --cdp-endpoint <host:port> (or {}) selects the DevTools endpoint, default {}",
+            cdp::ENDPOINT_ENV,
+            cdp::DEFAULT_ENDPOINT
+        );
     }
-    #[cfg(target_os = "windows")]
-    finalize_actions(previous_window, keep_focus);
 
     Ok(())
 }
 
+// `--script` or `--script-file`, and how long its promise may take.
+struct TabScript {
+    source: String,
+    timeout: Duration,
+}
+
+// Reads `--script`, `--script-file`, `--script-timeout`, `--inject-script` and
+// `--inject-script-file` from `args`; the inject source goes into `options.inject`.
+fn script_args(
+    args: &[String],
+    invocation_dir: Option<&Path>,
+    options: &mut BangOptions,
+) -> io::Result<Option<TabScript>> {
+    let mut source = None;
+    let mut timeout = DEFAULT_SCRIPT_TIMEOUT;
+    let mut i = 0;
+    while i < args.len() {
+        if args[i] == "--script" && i + 1 < args.len() {
+            source = Some(args[i + 1].clone());
+            i += 2;
+        } else if args[i] == "--inject-script" && i + 1 < args.len() {
+            options.inject = Some(args[i + 1].clone());
+            i += 2;
+        } else if args[i] == "--inject-script-file" && i + 1 < args.len() {
+            let path = invocation_path(&args[i + 1], invocation_dir);
+            let contents = std::fs::read_to_string(&path).map_err(|e| {
+                io::Error::new(
+                    e.kind(),
+                    format!(
+                        "failed to read inject script file '{}': {}",
+                        path.display(),
+                        e
+                    ),
+                )
+            })?;
+            options.inject = Some(contents);
+            i += 2;
+        } else if args[i] == "--script-timeout" && i + 1 < args.len() {
+            let seconds = args[i + 1].parse().map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "invalid --script-timeout '{}': expected seconds",
+                        args[i + 1]
+                    ),
+                )
+            })?;
+            timeout = Duration::from_secs(seconds);
+            i += 2;
+        } else if args[i] == "--script-file" && i + 1 < args.len() {
+            let path = invocation_path(&args[i + 1], invocation_dir);
+            match std::fs::read_to_string(&path) {
+                Ok(contents) => source = Some(contents),
+                Err(e) => {
+                    log::debug!("Failed to read script file '{}': {}", path.display(), e);
+                    return Err(io::Error::new(
+                        e.kind(),
+                        format!("failed to read script file '{}': {}", path.display(), e),
+                    ));
+                }
+            }
+            i += 2;
+        } else {
+            i += 1;
+        }
+    }
+    Ok(source.map(|source| TabScript { source, timeout }))
+}
+
+// Checks the `!intercept=` rules before anything is opened, and makes their path absolute
+// for the detached interceptor, which runs from the binary's directory.
+fn resolve_intercept_rules(
+    options: &mut BangOptions,
+    invocation_dir: Option<&Path>,
+) -> io::Result<()> {
+    let Some(rules) = &options.intercept else {
+        return Ok(());
+    };
+    let path = invocation_path(rules, invocation_dir);
+    InterceptRules::load(&path)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+    options.intercept = Some(path.display().to_string());
+    Ok(())
+}
+
+// Checks the `!storage=` file before anything is opened, and makes its path absolute.
+fn resolve_storage_file(
+    options: &mut BangOptions,
+    invocation_dir: Option<&Path>,
+) -> io::Result<()> {
+    let Some(file) = &options.storage else {
+        return Ok(());
+    };
+    let path = invocation_path(file, invocation_dir);
+    StorageState::load(&path)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+    options.storage = Some(path.display().to_string());
+    Ok(())
+}
+
+// Makes the `!screenshot=`, `!pdf=` and `!har=` files relative to where debugchrome was
+// started, like the files it reads. The defaults stay next to the binary.
+fn resolve_output_paths(options: &mut BangOptions, invocation_dir: Option<&Path>) {
+    for path in [&mut options.screenshot, &mut options.pdf, &mut options.har] {
+        if let Some(requested) = path.as_mut().filter(|requested| !requested.is_empty()) {
+            *requested = invocation_path(requested, invocation_dir)
+                .display()
+                .to_string();
+        }
+    }
+}
+
+// `path` as given on the command line, relative to where debugchrome was started.
+fn invocation_path(path: &str, invocation_dir: Option<&Path>) -> PathBuf {
+    match invocation_dir {
+        Some(base) => base.join(path),
+        None => PathBuf::from(path),
+    }
+}
+
+// Resolves the profile data dir: `DEBUGCHROME_DATA_DIR`, overridden by `--data-dir <path>`.
+fn resolve_profile_store(args: &[String], invocation_dir: Option<&Path>) -> ProfileStore {
+    match args.iter().position(|arg| arg == "--data-dir") {
+        Some(pos) => match args.get(pos + 1) {
+            Some(dir) => {
+                let dir = Path::new(dir);
+                match invocation_dir {
+                    Some(base) if dir.is_relative() => ProfileStore::new(base.join(dir)),
+                    _ => ProfileStore::new(dir),
+                }
+            }
+            None => {
+                say!("--data-dir requires a <path> value");
+                ProfileStore::from_env()
+            }
+        },
+        None => ProfileStore::from_env(),
+    }
+}
+
+// `--profile list | path <name> | clone <src> <dst> | delete <name>`
+fn run_profile_command(profiles: &ProfileStore, args: &[String]) -> io::Result<()> {
+    let arg = |i: usize| {
+        args.get(i).map(String::as_str).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "usage: --profile list | path <name> | clone <src> <dst> | delete <name>",
+            )
+        })
+    };
+    match arg(0)? {
+        "list" => {
+            say!("Profiles in {}:", profiles.root().display());
+            let mut listed = Vec::new();
+            for name in profiles.list()? {
+                let in_use = profiles.is_in_use(&name);
+                say!("  {}{}", name, if in_use { " (in use)" } else { "" });
+                listed.push(serde_json::json!({
+                    "name": name,
+                    "path": profiles.path(&name)?,
+                    "in_use": in_use,
+                }));
+            }
+            report::set("root", profiles.root().display().to_string());
+            report::set("profiles", listed);
+        }
+        "path" => {
+            let path = profiles.path(arg(1)?)?;
+            say!("{}", path.display());
+            report::set("path", path.display().to_string());
+        }
+        "clone" => {
+            let path = profiles.clone_profile(arg(1)?, arg(2)?)?;
+            say!("Cloned {} to {}", arg(1)?, path.display());
+            report::set("path", path.display().to_string());
+        }
+        "delete" => {
+            profiles.delete(arg(1)?)?;
+            say!("Deleted profile {}", arg(1)?);
+        }
+        other => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unknown --profile command {:?}", other),
+            ));
+        }
+    }
+    Ok(())
+}
+
+// Resolves the DevTools endpoint: `DEBUGCHROME_CDP`, overridden by `--cdp-endpoint <host:port>`.
+fn resolve_cdp_endpoint(args: &[String]) -> CdpEndpoint {
+    let mut endpoint = CdpEndpoint::from_env();
+    if let Some(pos) = args.iter().position(|arg| arg == "--cdp-endpoint") {
+        match args.get(pos + 1).map(|value| value.parse::<CdpEndpoint>()) {
+            Some(Ok(parsed)) => endpoint = parsed,
+            Some(Err(e)) => {
+                say!("Ignoring --cdp-endpoint: {}", e);
+                log::debug!("Ignoring --cdp-endpoint: {}", e);
+            }
+            None => say!("--cdp-endpoint requires a <host:port> value"),
+        }
+    }
+    endpoint
+}
+
+// Applies `!host=` and `!port=` on top of the resolved endpoint.
+fn apply_endpoint_bangs(mut endpoint: CdpEndpoint, options: &BangOptions) -> CdpEndpoint {
+    if let Some(host) = options.host.as_ref().filter(|h| !h.is_empty()) {
+        endpoint.host = host.clone();
+    }
+    if let Some(port) = options.port {
+        endpoint.port = port;
+    }
+    endpoint
+}
+
 async fn open_window_via_devtools(
+    endpoint: &str,
     clean_url: &str,
     use_direct: bool,
     redirect_seconds: Option<u64>,
-    bangs: &std::collections::HashMap<String, String>,
+    options: &BangOptions,
 ) -> Result<String, Box<dyn std::error::Error>> {
-    let response = reqwest::get("http://localhost:9222/json/version").await?;
-    let version: serde_json::Value = response.json().await?;
-    let ws_url = version["webSocketDebuggerUrl"]
-        .as_str()
-        .ok_or("No WebSocket URL")?;
-    let (socket, _) = tokio_tungstenite::connect_async(ws_url).await?;
-    let mut socket = socket;
+    let client = CdpClient::connect_browser(endpoint).await?;
     // Step 1: Create a new browser context
-    let unique = get_unique_id();
-    let create_context = serde_json::json!({
-        "id": unique,
-        "method": "Target.createBrowserContext"
-    });
-    socket
-        .send(tungstenite::Message::Text(
-            create_context.to_string().into(),
-        ))
-        .await?;
-
-    let browser_context_id = match tokio::time::timeout(Duration::from_secs(5), socket.next()).await
-    {
-        Ok(Some(Ok(tungstenite::Message::Text(txt)))) => {
-            let json: serde_json::Value = serde_json::from_str(&txt)?;
-            json["result"]["browserContextId"]
-                .as_str()
-                .map(|s| s.to_string())
-        }
-        _ => None,
-    };
-    let monitor_index = bangs.get("monitor").and_then(|v| v.parse::<usize>().ok());
-    let dpi_scaling_enabled = bangs
-        .get("dpi")
-        .map(|v| v.eq_ignore_ascii_case("true"))
-        .unwrap_or(false);
-    let (left, top, width, height, include_bounds) =
-        if let Some(bounds) = get_screen_bounds(&bangs, monitor_index, dpi_scaling_enabled) {
-            (bounds.0, bounds.1, bounds.2, bounds.3, true)
-        } else {
-            (0, 0, 0, 0, false) // Indicate that bounds should not be included
-        };
+    let context_id = client.create_browser_context().await?;
+    let bounds = get_screen_bounds(options);
 
     use base64::engine::general_purpose::STANDARD as base64_engine;
 
-    let bang_id = bangs.get("id").cloned().unwrap_or_default();
+    let bang_id = options.id.clone().unwrap_or_default();
     // Use the value from DELAY_CELL initialized in main
     let delay = redirect_seconds.unwrap_or(0);
     let placeholder_url = if use_direct || delay == 0 {
@@ -669,208 +950,105 @@ async fn open_window_via_devtools(
         let encoded_html = base64_engine.encode(html_content);
         format!("data:text/html;base64,{}#{}", encoded_html, bang_id)
     };
-    println!(
-        "{:?} Bounds: left={}, top={}, width={}, height={}, include_bounds={}",
-        monitor_index, left, top, width, height, include_bounds
-    );
-    let unique = get_unique_id();
-    if let Some(context_id) = browser_context_id {
-        if include_bounds {
-            let create_target = serde_json::json!({
-                "id": unique,
-                "method": "Target.createTarget",
-                "params": {
-                    "url": placeholder_url,
-                    // "url": clean_url,
-                    "browserContextId": context_id,
-                    "left": left,
-                    "top": top,
-                    "width": width,
-                    "height": height,
-                    "newWindow": true
-                }
-            });
-            socket
-                .send(tungstenite::Message::Text(create_target.to_string().into()))
-                .await?;
+    say!("{:?} Bounds: {:?}", options.monitor, bounds);
+
+    // Step 2: Create a new target (window) in the new browser context. With !har or
+    // !intercept it starts blank and loads the page once they are listening.
+    let mut create_target = CreateTarget {
+        url: if starts_blank(options) {
+            "about:blank".to_string()
         } else {
-            let create_target = serde_json::json!({
-                "id": unique,
-                "method": "Target.createTarget",
-                "params": {
-                    "url": placeholder_url,
-                    // "url": clean_url,
-                    "browserContextId": context_id,
-                    "newWindow": true
-                }
-            });
-            socket
-                .send(tungstenite::Message::Text(create_target.to_string().into()))
-                .await?;
+            placeholder_url.clone()
+        },
+        browser_context_id: Some(context_id),
+        new_window: true,
+        ..Default::default()
+    };
+    if let Some((left, top, width, height)) = bounds {
+        create_target.left = Some(left);
+        create_target.top = Some(top);
+        create_target.width = Some(width);
+        create_target.height = Some(height);
+    }
+    say!(
+        "Waiting for response to get targetId for bangId: {}",
+        bang_id
+    );
+    let target_id = client.create_target(&create_target).await?;
+    if starts_blank(options) {
+        listen_then_navigate(endpoint, &target_id, &placeholder_url, options).await?;
+    }
+    // The caller tags the tab; until then the bangId is in the url's fragment.
+
+    // Print all tab URLs for diagnostics
+    match list_targets(endpoint).await {
+        Ok(tabs) => {
+            say!("Tabs after window creation:");
+            for tab in &tabs {
+                say!("  title: '{}' url: '{}'", tab.title, tab.url);
+            }
         }
-        // // Step 2: Create a new target (window) in the new browser context
-        // let create_target = serde_json::json!({
-        //     "id": 2,
-        //     "method": "Target.createTarget",
-        //     "params": {
-        //         "url": clean_url,
-        //         "browserContextId": context_id,
-        //         "left": left,
-        //         "top": top,
-        //         "width": width,
-        //         "height": height,
-        //         "newWindow": true
-        //     }
-        // });
-        // socket
-        //     .send(tungstenite::Message::Text(create_target.to_string().into()))
-        //     .await?;
-
-        // Step 3: Wait for the response to get the targetId
-        println!(
-            "Waiting for response to get targetId for bangId: {}",
-            bang_id
-        );
-        let timeout = std::time::Duration::from_secs(5); // Define a timeout duration
-        if let Ok(Some(Ok(tungstenite::Message::Text(txt)))) =
-            tokio::time::timeout(timeout, socket.next()).await
-        {
-            let json: serde_json::Value = serde_json::from_str(&txt)?;
-            if let Some(target_id) = json["result"]["targetId"].as_str() {
-                let bang_id_val = bangs.get("id").cloned().unwrap_or_default();
-                let set_bang_result = set_bang_id_session(&target_id, &bang_id_val).await;
-                println!("set_bang_id_session result: {:?}", set_bang_result);
-                log::debug!("set_bang_id_session result: {:?}", set_bang_result);
-
-                let mut title = String::new();
-                // Print all tab URLs for diagnostics
-                match reqwest::get("http://localhost:9222/json").await {
-                    Ok(resp) => match resp.json::<Vec<serde_json::Value>>().await {
-                        Ok(tabs) => {
-                            println!("Tabs after window creation:");
-                            for tab in &tabs {
-                                let url = tab["url"].as_str().unwrap_or("");
-                                title = tab["title"].as_str().unwrap_or("").to_owned();
-                                println!("  title: '{}' url: '{}'", title, url);
-                            }
-                        }
-                        Err(e) => println!("Failed to parse tabs JSON: {}", e),
-                    },
-                    Err(e) => println!("Failed to fetch tabs: {}", e),
-                }
+        Err(e) => say!("Failed to fetch tabs: {}", e),
+    }
 
-                log::debug!(
-                    "Searching for tab info after window creation for bangId: {}",
-                    bang_id
-                );
-                match search_tabs_for_bang_id(&bang_id).await {
-                    Ok(Some(tab_info)) => {
-                        log::debug!("Tab info for bangId {}: {:?}", bang_id, tab_info);
-                        // tab_info.0 = target_id, tab_info.1 = title, tab_info.2 = url
-                        #[cfg(target_os = "windows")]
-                        {
-                            log::debug!("Attempting to find HWND for tab title: {}", tab_info.1);
-                            match find_chrome_hwnd_by_title(&tab_info.1, &bang_id) {
-                                Some(hwnd) => {
-                                    println!(
-                                        "HWND: {:?}\nPID: {:?}\nTITLE: {}\nTARGET: {}\nPAGE_URL: {}",
-                                        hwnd,
-                                        unsafe {
-                                            let mut pid = 0;
-                                            winapi::um::winuser::GetWindowThreadProcessId(
-                                                hwnd, &mut pid,
-                                            );
-                                            pid
-                                        },
-                                        tab_info.1,
-                                        tab_info.0,
-                                        tab_info.2
-                                    );
-                                }
-                                None => {
-                                    println!(
-                                        "No HWND found for target {} (title '{}')",
-                                        tab_info.0, tab_info.1
-                                    );
-                                    log::debug!(
-                                        "No HWND found for target {} (title '{}')",
-                                        tab_info.0,
-                                        tab_info.1
-                                    );
-                                }
-                            }
-                        }
-                    }
-                    Ok(None) => {
-                        println!("No tab found for bangId {} after window creation.", bang_id);
-                        log::debug!("No tab found for bangId {} after window creation.", bang_id);
-                    }
-                    Err(e) => {
-                        println!("Error searching for tab info after window creation: {}", e);
-                        log::debug!("Error searching for tab info after window creation: {}", e);
+    log::debug!(
+        "Searching for tab info after window creation for bangId: {}",
+        bang_id
+    );
+    match search_tabs_for_bang_id(endpoint, &bang_id).await {
+        Ok(Some(tab_info)) => {
+            log::debug!("Tab info for bangId {}: {:?}", bang_id, tab_info);
+            // tab_info.0 = target_id, tab_info.1 = title, tab_info.2 = url
+            #[cfg(target_os = "windows")]
+            if !options.headless {
+                log::debug!("Attempting to find HWND for tab title: {}", tab_info.1);
+                match find_chrome_hwnd_by_title(&tab_info.1, &bang_id) {
+                    Some(hwnd) => report_window(hwnd, &tab_info.0, &tab_info.1, &tab_info.2),
+                    None => {
+                        say!(
+                            "No HWND found for target {} (title '{}')",
+                            tab_info.0,
+                            tab_info.1
+                        );
+                        log::debug!(
+                            "No HWND found for target {} (title '{}')",
+                            tab_info.0,
+                            tab_info.1
+                        );
                     }
                 }
-                return Ok(target_id.to_owned());
             }
         }
+        Ok(None) => {
+            say!("No tab found for bangId {} after window creation.", bang_id);
+            log::debug!("No tab found for bangId {} after window creation.", bang_id);
+        }
+        Err(e) => {
+            say!("Error searching for tab info after window creation: {}", e);
+            log::debug!("Error searching for tab info after window creation: {}", e);
+        }
     }
-
-    Err("Failed to create a new window".into())
+    Ok(target_id)
 }
 
-fn get_screen_bounds(
-    bangs: &std::collections::HashMap<String, String>,
-    monitor_index: Option<usize>,
-    dpi_scaling_enabled: bool,
-) -> Option<(i32, i32, i32, i32)> {
-    #[cfg(not(target_os = "windows"))]
-    {
-        log::debug!("Screen bounds adjustment is only supported on Windows.");
-        return None;
-    }
-    #[cfg(target_os = "windows")]
-    {
-        let (screen_width, screen_height) = get_screen_resolution(monitor_index);
-
-        let x = bangs
-            .get("x")
-            .and_then(|v| parse_dimension(v, screen_width))
-            .unwrap_or(0);
-        let y = bangs
-            .get("y")
-            .and_then(|v| parse_dimension(v, screen_height))
-            .unwrap_or(0);
-        let w = bangs
-            .get("w")
-            .and_then(|v| parse_dimension(v, screen_width))
-            .unwrap_or(screen_width);
-        let h = bangs
-            .get("h")
-            .and_then(|v| parse_dimension(v, screen_height))
-            .unwrap_or(screen_height);
-        println!("params: x: {}, y: {}, w: {}, h: {}", x, y, w, h);
-        if let Some(index) = monitor_index {
-            println!(" monitor_index: {}", index);
-            if let Some((adjusted_x, adjusted_y, adjusted_w, adjusted_h)) =
-                adjust_bounds_to_monitor(index, x, y, w, h, dpi_scaling_enabled)
-            {
-                println!(
-                    "Adjusted bounds to monitor {}: x={}, y={}, w={}, h={}",
-                    index, adjusted_x, adjusted_y, adjusted_w, adjusted_h
-                );
-                return Some((adjusted_x, adjusted_y, adjusted_w, adjusted_h));
-            }
-        }
-        if bangs.contains_key("x")
-            && bangs.contains_key("y")
-            && bangs.contains_key("w")
-            && bangs.contains_key("h")
-        {
-            Some((x, y, w, h))
-        } else {
-            None
+fn get_screen_bounds(options: &BangOptions) -> Option<(i32, i32, i32, i32)> {
+    let monitors = monitor::monitors();
+    log::debug!("Monitors: {:?}", monitors);
+    if let Some(index) = options.monitor {
+        say!(" monitor_index: {}", index);
+        if index >= monitors.len() {
+            log::debug!("Monitor index out of bounds, falling back to the first monitor.");
         }
     }
+    let bounds = monitor::window_bounds(&monitors, options)?;
+    say!(
+        "Window bounds: x={}, y={}, w={}, h={}",
+        bounds.x,
+        bounds.y,
+        bounds.width,
+        bounds.height
+    );
+    Some((bounds.x, bounds.y, bounds.width, bounds.height))
 }
 
 #[cfg(target_os = "windows")]
@@ -888,434 +1066,477 @@ fn finalize_actions(previous_window: Option<winapi::shared::windef::HWND>, keep_
 }
 
 async fn open_tab_via_devtools_and_return_id(
+    endpoint: &str,
     clean_url: &str,
-    _bangs: &std::collections::HashMap<String, String>,
+    options: &BangOptions,
 ) -> Result<String, Box<dyn std::error::Error>> {
-    let response = reqwest::get("http://localhost:9222/json/version").await?;
-    let version: serde_json::Value = response.json().await?;
-    let ws_url = version["webSocketDebuggerUrl"]
-        .as_str()
-        .ok_or("No WebSocket URL")?;
-    let (socket, _) = tokio_tungstenite::connect_async(ws_url).await?;
-    let mut socket = socket;
-
-    let msg = serde_json::json!({
-        "id": 1,
-        "method": "Target.createTarget",
-        "params": { "url": clean_url }
-    });
-
-    socket
-        .send(tungstenite::Message::Text(msg.to_string().into()))
+    let client = CdpClient::connect_browser(endpoint).await?;
+    let blank = starts_blank(options);
+    let target_id = client
+        .create_target(&CreateTarget {
+            url: if blank { "about:blank" } else { clean_url }.to_string(),
+            ..Default::default()
+        })
         .await?;
-
-    let timeout = std::time::Duration::from_secs(5); // Define a timeout duration
-    match tokio::time::timeout(timeout, socket.next()).await {
-        Ok(Some(msg)) => {
-            if let Ok(Message::Text(txt)) = msg {
-                if let Ok(json) = serde_json::from_str::<serde_json::Value>(&txt) {
-                    if let Some(target_id) = json["result"]["targetId"].as_str() {
-                        println!("{:?}", json);
-                        return Ok(target_id.to_owned());
-                    }
-                }
-            }
-        }
-        Ok(None) => {
-            log::debug!("WebSocket stream ended unexpectedly.");
-        }
-        Err(_) => {
-            log::debug!("Timeout while reading from WebSocket.");
-        }
+    say!("Created target: {}", target_id);
+    if blank {
+        listen_then_navigate(endpoint, &target_id, clean_url, options).await?;
     }
+    Ok(target_id)
+}
 
-    Err("Failed to get targetId".into())
+// Whether a new tab has to wait on `about:blank` for helpers that must see its first request.
+fn starts_blank(options: &BangOptions) -> bool {
+    options.har.is_some()
+        || options.intercept.is_some()
+        || options.storage.is_some()
+        || options.emulation().is_some()
 }
 
-async fn set_window_bounds(
+// Starts the emulation bangs, the `!har=` recorder and the `!intercept=` rules on a tab
+// created blank and imports its `!storage=`, then loads `url`, so all of them see the page's
+// very first request. A helper that fails to start is reported, not fatal.
+async fn listen_then_navigate(
+    endpoint: &str,
     target_id: &str,
-    x: i32,
-    y: i32,
-    w: i32,
-    h: i32,
+    url: &str,
+    options: &BangOptions,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let socket_url = format!("ws://localhost:9222/devtools/page/{}", target_id);
-    let (mut socket, _) = tokio_tungstenite::connect_async(&socket_url).await?;
-
-    let bounds = serde_json::json!({
-        "id": 4,
-        "method": "Browser.setWindowBounds",
-        "params": {
-            "windowId": target_id,
-            "bounds": { "left": x, "top": y, "width": w, "height": h }
+    if let Some(emulation) = options.emulation() {
+        let result = spawn_emulator(endpoint, target_id, &emulation).await;
+        report::action_value(
+            target_id,
+            "emulate",
+            &result,
+            serde_json::to_value(&emulation).ok(),
+        );
+        if let Err(e) = result {
+            say!("Failed to emulate: {}", e);
+            log::debug!("Failed to emulate: {}", e);
         }
-    });
-    socket
-        .send(tungstenite::Message::Text(bounds.to_string().into()))
-        .await?;
+    }
+    if options.har.is_some() {
+        let path = har_path(options, target_id, 1);
+        let result = spawn_har_recorder(endpoint, target_id, &path, options).await;
+        report::action_value(target_id, "har", &result, Some(path.into()));
+        if let Err(e) = result {
+            say!("Failed to record HAR: {}", e);
+            log::debug!("Failed to record HAR: {}", e);
+        }
+    }
+    if let Some(rules) = &options.intercept {
+        let result = spawn_interceptor(endpoint, target_id, rules).await;
+        report::action_value(target_id, "intercept", &result, Some(rules.as_str().into()));
+        if let Err(e) = result {
+            say!("Failed to intercept requests: {}", e);
+            log::debug!("Failed to intercept requests: {}", e);
+        }
+    }
+    let client = CdpClient::connect_page(endpoint, target_id).await?;
+    let page = client.page();
+    if let Some(file) = &options.storage {
+        let result = seed_storage(&page, Path::new(file)).await;
+        report::action_value(target_id, "storage", &result, Some(file.as_str().into()));
+        if let Err(e) = result {
+            say!("Failed to import storage: {}", e);
+            log::debug!("Failed to import storage: {}", e);
+        }
+    }
+    let mut loaded = page.subscribe(Some("Page.domContentEventFired"));
+    page.navigate(url).await?;
+    if options.storage.is_some() {
+        // The seed script goes away with this connection; keep it until the page has run it.
+        let _ = tokio::time::timeout(Duration::from_secs(10), loaded.recv()).await;
+    }
     Ok(())
 }
 
-async fn take_screenshot(target_id: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let socket_url = format!("ws://localhost:9222/devtools/page/{}", target_id);
-    let (mut socket, _) = tokio_tungstenite::connect_async(&socket_url).await?;
-    let enable = serde_json::json!({
-        "id": 1,
-        "method": "Page.enable"
-    });
-    socket
-        .send(tungstenite::Message::Text(enable.to_string().into()))
-        .await?;
-
-    let capture = serde_json::json!({
-        "id": 2,
-        "method": "Page.captureScreenshot"
-    });
-    log::debug!("Sending captureScreenshot command: {}", capture);
-    socket
-        .send(tungstenite::Message::Text(capture.to_string().into()))
-        .await?;
-
-    log::debug!("Current directory: {:?}", std::env::current_dir()?);
-    let timeout_duration = Duration::from_secs(5); // Set a timeout duration
-    let timeout_future = tokio::time::sleep(timeout_duration);
-    tokio::pin!(timeout_future);
-
-    'outer: while let Some(Ok(tungstenite::Message::Text(txt))) = tokio::select! {
-        _ = &mut timeout_future => {
-            log::debug!("Timeout while waiting for screenshot response.");
-            break 'outer;
+// Puts the window showing `target_id` at `bounds` and into `state`, and returns where it
+// ended up. Chrome only moves a window in the normal state, so a maximized, minimized or
+// fullscreen one is restored first.
+async fn place_window(
+    endpoint: &str,
+    target_id: &str,
+    bounds: Option<(i32, i32, i32, i32)>,
+    state: Option<WindowState>,
+) -> Result<WindowBounds, Box<dyn std::error::Error>> {
+    let client = CdpClient::connect_browser(endpoint).await?;
+    let (window_id, current) = client.window_for_target(target_id).await?;
+    log::debug!("Window {} of {} is {:?}", window_id, target_id, current);
+    let only_state = |state| WindowBounds {
+        window_state: Some(state),
+        ..Default::default()
+    };
+    if let Some((left, top, width, height)) = bounds {
+        if current
+            .window_state
+            .is_some_and(|state| state != WindowState::Normal)
+        {
+            client
+                .set_window_bounds(window_id, &only_state(WindowState::Normal))
+                .await?;
         }
-        msg = socket.next() => msg
-    } {
-        let json: serde_json::Value = serde_json::from_str(&txt)?;
-        if let Some(data) = json["result"]["data"].as_str() {
-            let bytes = base64::engine::general_purpose::STANDARD.decode(data)?;
-            std::fs::write("debugchrome.png", bytes)?;
-            Command::new("powershell")
-                .args(["-NoProfile", "-Command", "Start-Process debugchrome.png"])
-                .status()
-                .ok();
-            log::debug!("Screenshot saved to debugchrome.png");
+        let wanted = WindowBounds {
+            left: Some(left),
+            top: Some(top),
+            width: Some(width),
+            height: Some(height),
+            window_state: None,
+        };
+        client.set_window_bounds(window_id, &wanted).await?;
+    }
+    if let Some(state) = state.filter(|state| bounds.is_none() || *state != WindowState::Normal) {
+        client
+            .set_window_bounds(window_id, &only_state(state))
+            .await?;
+    }
+
+    // Read it back; the window manager may take a moment to apply a state.
+    let wanted_state = state.unwrap_or(WindowState::Normal);
+    let mut placed = client.window_bounds(window_id).await?;
+    for _ in 0..10 {
+        if placed.window_state.unwrap_or_default() == wanted_state {
             break;
         }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        placed = client.window_bounds(window_id).await?;
     }
-    Ok(())
+    if state.is_some() && placed.window_state.unwrap_or_default() != wanted_state {
+        return Err(format!(
+            "window {} is {} instead of {}",
+            window_id,
+            placed.window_state.unwrap_or_default(),
+            wanted_state
+        )
+        .into());
+    }
+    if let Some((left, top, width, height)) = bounds
+        && wanted_state == WindowState::Normal
+        && (placed.left, placed.top, placed.width, placed.height)
+            != (Some(left), Some(top), Some(width), Some(height))
+    {
+        // Window managers may clamp a window to the screen or account for its frame.
+        say!(
+            "Window {} was placed at {:?},{:?} {:?}x{:?} instead of {},{} {}x{}",
+            window_id,
+            placed.left,
+            placed.top,
+            placed.width,
+            placed.height,
+            left,
+            top,
+            width,
+            height
+        );
+    }
+    Ok(placed)
 }
 
-use futures::stream::{FuturesUnordered, StreamExt};
-use tokio_tungstenite::connect_async;
+fn report_placement(target_id: &str, result: &Result<WindowBounds, Box<dyn std::error::Error>>) {
+    match result {
+        Ok(placed) => say!("Window bounds: {:?}", placed),
+        Err(e) => {
+            say!("Failed to place the window: {}", e);
+            log::debug!("Failed to place the window of {}: {}", target_id, e);
+        }
+    }
+    let value = result
+        .as_ref()
+        .ok()
+        .and_then(|placed| serde_json::to_value(placed).ok());
+    report::action_value(target_id, "place", result, value);
+}
 
-async fn search_tabs_for_bang_id(
-    search_id: &str,
-) -> Result<Option<(String, String, String)>, Box<dyn std::error::Error + Send + Sync>> {
-    log::debug!("Searching for bangId = {}", search_id);
-    let uses_session = true;
+// The format `!format` asks for, else the one `!screenshot=<path>`'s extension names.
+fn screenshot_format(options: &BangOptions) -> ImageFormat {
+    options
+        .format
+        .or_else(|| {
+            options
+                .screenshot
+                .as_deref()
+                .and_then(ImageFormat::from_path)
+        })
+        .unwrap_or_default()
+}
 
-    // Fetch the list of tabs
-    let response = reqwest::get("http://localhost:9222/json").await?;
-    let tabs: Vec<serde_json::Value> = response.json().await?;
-    let mut futures = FuturesUnordered::new();
+// `!screenshot=<path>`, or `debugchrome.<format>`.
+fn screenshot_path(options: &BangOptions, target_id: &str, tab_count: usize) -> String {
+    let default = format!("debugchrome.{}", screenshot_format(options).extension());
+    output_path(
+        options.screenshot.as_deref(),
+        &default,
+        target_id,
+        tab_count,
+    )
+}
 
-    for tab in tabs {
-        let tab_url = tab["url"].as_str().unwrap_or("<no url>").to_string();
-        let target_id = tab["id"].as_str().unwrap_or("<no id>").to_string();
-        let title = tab["title"].as_str().unwrap_or("<no title>").to_string();
-        let page_url = tab["url"].as_str().unwrap_or("<no url>").to_string();
-
-        // Check if the tab_url contains the search_id directly
-        if tab_url.contains(&format!("#{}", search_id)) {
-            log::debug!("Found tab with bangId {} in URL: {}", search_id, tab_url);
-            return Ok(Some((target_id, title, page_url)));
-        }
+// `!pdf=<path>`, or `debugchrome.pdf`.
+fn pdf_path(options: &BangOptions, target_id: &str, tab_count: usize) -> String {
+    output_path(
+        options.pdf.as_deref(),
+        "debugchrome.pdf",
+        target_id,
+        tab_count,
+    )
+}
 
-        if is_invalid_url(&page_url) {
-            continue;
-        }
+fn har_path(options: &BangOptions, target_id: &str, tab_count: usize) -> String {
+    output_path(
+        options.har.as_deref(),
+        "debugchrome.har",
+        target_id,
+        tab_count,
+    )
+}
 
-        log::debug!("Searching tab: {}", tab_url);
-
-        if let Some(ws_url) = tab["webSocketDebuggerUrl"].as_str() {
-            let ws_url = ws_url.to_string();
-            let search_id = search_id.to_string();
-            let target_id = target_id.clone();
-            let title = title.clone();
-            let page_url = page_url.clone();
-
-            futures.push(async move {
-                if let Ok((mut socket, _)) = connect_async(&ws_url).await {
-                    log::debug!("Connected to WebSocket URL: {}", ws_url);
-
-                    // Try to get window.bangId next
-                    let command_id = get_unique_id();
-                    let get_bang_id_window = serde_json::json!({
-                        "id": command_id,
-                        "method": "Runtime.evaluate",
-                        "params": {
-                            "expression": "window.bangId",
-                            "returnByValue": true
-                        }
-                    });
-
-                    if socket.send(Message::Text(get_bang_id_window.to_string().into())).await.is_ok() {
-                        log::debug!("Sent command to get window.bangId with id {}", command_id);
-
-                        // Wait for a response
-                        let timeout_duration = Duration::from_secs(5);
-                        let timeout_future = tokio::time::sleep(timeout_duration);
-                        tokio::pin!(timeout_future);
-
-                        'inner: loop {
-                            tokio::select! {
-                                _ = &mut timeout_future => {
-                                    log::debug!("Timeout while waiting for window.bangId response.");
-                                    break 'inner;
-                                }
-                                Some(Ok(msg)) = socket.next() => {
-                                    if let Message::Text(txt) = msg {
-                                        log::debug!("Received message: {}", txt);
-                                        if let Ok(json) = serde_json::from_str::<serde_json::Value>(&txt) {
-                                            if json["id"] == command_id {
-                                                if let Some(bang_id) = json["result"]["result"]["value"].as_str() {
-                                                    if bang_id == search_id {
-                                                        log::debug!("Found tab with bangId {}: {}", search_id, tab_url);
-                                                        return Some((target_id, title, page_url));
-                                                    }
-                                                }
-                                                break 'inner; // Exit loop after processing the response
-                                            }
-                                        }
-                                    }
-                                }
-                                else => {
-                                    log::debug!("WebSocket stream ended unexpectedly.");
-                                    break 'inner;
-                                }
-                            }
-                        }
-                    }
-                    let command_id = get_unique_id();
-                    // If window.bangId is not set, try sessionStorage.getItem('bangId')
-                    let get_bang_id_session = serde_json::json!({
-                        "id": command_id,
-                        "method": "Runtime.evaluate",
-                        "params": {
-                            "expression": "sessionStorage.getItem('bangId')",
-                            "returnByValue": true
-                        }
-                    });
-
-                    if socket.send(Message::Text(get_bang_id_session.to_string().into())).await.is_ok() {
-                        log::debug!("Sent command to get sessionStorage.bangId with id {}", command_id);
-
-                        // Wait for a response
-                        let timeout_duration = Duration::from_secs(5);
-                        let timeout_future = tokio::time::sleep(timeout_duration);
-                        tokio::pin!(timeout_future);
-
-                        'inner_session: loop {
-                            tokio::select! {
-                                _ = &mut timeout_future => {
-                                    log::debug!("Timeout while waiting for sessionStorage.bangId response.");
-                                    break 'inner_session;
-                                }
-                                Some(Ok(msg)) = socket.next() => {
-                                    if let Message::Text(txt) = msg {
-                                        log::debug!("Received message: {}", txt);
-                                        if let Ok(json) = serde_json::from_str::<serde_json::Value>(&txt) {
-                                            if json["id"] == command_id {
-                                                if let Some(bang_id) = json["result"]["result"]["value"].as_str() {
-                                                    if bang_id == search_id {
-                                                        log::debug!("Found tab with bangId {}: {}", search_id, tab_url);
-                                                        return Some((target_id, title, page_url));
-                                                    }
-                                                }
-                                                break 'inner_session; // Exit loop after processing the response
-                                            }
-                                        }
-                                    }
-                                }
-                                else => {
-                                    log::debug!("WebSocket stream ended unexpectedly.");
-                                    break 'inner_session;
-                                }
-                            }
-                        }
-                    }
-                    // let get_bang_id = if !uses_session {
-                    //     serde_json::json!({
-                    //         "id": command_id,
-                    //         "method": "Runtime.evaluate",
-                    //         "params": {
-                    //             "expression": "window.bangId",
-                    //             "returnByValue": true
-                    //         }
-                    //     })
-                    // } else {
-                    //     serde_json::json!({
-                    //         "id": command_id,
-                    //         "method": "Runtime.evaluate",
-                    //         "params": {
-                    //             "expression": "sessionStorage.getItem('bangId')",
-                    //             "returnByValue": true
-                    //         }
-                    //     })
-                    // };
-
-                    // if socket.send(Message::Text(get_bang_id.to_string().into())).await.is_ok() {
-                    //     log::debug!(
-                    //         "Sent command to get bangId with id {} {:?}",
-                    //         command_id,
-                    //         get_bang_id
-                    //     );
-
-                    //     // Wait for a response
-                    //     let timeout_duration = Duration::from_secs(5);
-                    //     let timeout_future = tokio::time::sleep(timeout_duration);
-                    //     tokio::pin!(timeout_future);
-
-                    //     'outer: loop {
-                    //         tokio::select! {
-                    //             _ = &mut timeout_future => {
-                    //                 log::debug!("Timeout while waiting for bangId response.");
-                    //                 break 'outer;
-                    //             }
-                    //             Some(Ok(msg)) = socket.next() => {
-                    //                 if let Message::Text(txt) = msg {
-                    //                     log::debug!("Received message: {}", txt);
-                    //                     if let Ok(json) = serde_json::from_str::<serde_json::Value>(&txt) {
-                    //                         if json["id"] == command_id {
-                    //                             if let Some(bang_id) =
-                    //                                 json["result"]["result"]["value"].as_str()
-                    //                             {
-                    //                                 if bang_id == search_id {
-                    //                                     log::debug!(
-                    //                                         "Found tab with bangId {}: {}",
-                    //                                         search_id,
-                    //                                         tab_url
-                    //                                     );
-                    //                                     return Some((target_id, title, page_url));
-                    //                                 }
-                    //                             }
-                    //                             break; // Exit loop after processing the response
-                    //                         }
-                    //                     }
-                    //                 }
-                    //             }
-                    //             else => {
-                    //                 log::debug!("WebSocket stream ended unexpectedly.");
-                    //                 break;
-                    //             }
-                    //         }
-                    //     }
-                    // }
-                }
-                None
-            });
-        }
+// The file asked for, or `default`. When several tabs are saved at once each gets its own
+// file, with the target id before the extension.
+fn output_path(
+    requested: Option<&str>,
+    default: &str,
+    target_id: &str,
+    tab_count: usize,
+) -> String {
+    let path = match requested {
+        Some(path) if !path.is_empty() => path.to_string(),
+        _ => default.to_string(),
+    };
+    if tab_count <= 1 {
+        return path;
     }
-
-    // Process all futures and return the first match
-    while let Some(result) = futures.next().await {
-        if let Some(tab_info) = result {
-            return Ok(Some(tab_info));
+    let file_start = path.rfind(['/', '\\']).map_or(0, |at| at + 1);
+    match path[file_start..].rfind('.') {
+        Some(dot) => {
+            let (stem, extension) = path.split_at(file_start + dot);
+            format!("{}-{}{}", stem, target_id, extension)
         }
+        None => format!("{}-{}", path, target_id),
     }
+}
 
-    log::debug!("No tab found with bangId = {}", search_id);
-    Ok(None)
+// Document-space bounds of the first element matching `selector`, or null.
+fn element_bounds_expression(selector: &str) -> String {
+    format!(
+        "(() => {{ const element = document.querySelector({}); if (!element) return null; \
+         const rect = element.getBoundingClientRect(); \
+         return {{ x: rect.left + window.scrollX, y: rect.top + window.scrollY, \
+         width: rect.width, height: rect.height }}; }})()",
+        serde_json::to_string(selector).unwrap_or_default()
+    )
 }
 
-async fn activate_tab(target_id: &str) -> Result<(), Box<dyn std::error::Error>> {
-    // Fetch the WebSocket debugger URL
-    let version: serde_json::Value = reqwest::get("http://localhost:9222/json/version")
-        .await?
-        .json()
-        .await?;
-    let ws_url = version["webSocketDebuggerUrl"]
-        .as_str()
-        .ok_or("No WebSocket URL")?;
-
-    // Connect to the WebSocket
-    let (mut socket, _) = tokio_tungstenite::connect_async(ws_url).await?;
-
-    // Send the Target.activateTarget command
-    let activate_command = serde_json::json!({
-        "id": get_unique_id(),
-        "method": "Target.activateTarget",
-        "params": { "targetId": target_id }
-    });
-    socket
-        .send(Message::Text(activate_command.to_string().into()))
-        .await?;
-    log::debug!("Activated tab with targetId: {}", target_id);
+// Holds until `!wait=` is met on the tab, so the actions after it see the settled page.
+async fn wait_for_tab(
+    endpoint: &str,
+    target_id: &str,
+    options: &BangOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(condition) = &options.wait else {
+        return Ok(());
+    };
+    let timeout = options
+        .wait_timeout
+        .map_or(DEFAULT_WAIT_TIMEOUT, Duration::from_secs);
+    let client = CdpClient::connect_page(endpoint, target_id).await?;
+    wait_for(&client.page(), condition, timeout).await?;
+    Ok(())
+}
 
+async fn take_screenshot(
+    endpoint: &str,
+    target_id: &str,
+    path: &str,
+    options: &BangOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let client = CdpClient::connect_page(endpoint, target_id).await?;
+    let page = client.page();
+    page.enable_page().await?;
+    // Overrides only last as long as this connection, so size the viewport right before capturing.
+    if let Some((width, height)) = headless_viewport(options) {
+        page.set_device_metrics(width, height, 1.0, false).await?;
+    }
+    let format = screenshot_format(options);
+    let mut params = CaptureScreenshot {
+        format,
+        quality: options.quality.filter(|_| format != ImageFormat::Png),
+        ..Default::default()
+    };
+    if let Some(selector) = &options.clip {
+        let bounds = page
+            .evaluate(&element_bounds_expression(selector), true)
+            .await?
+            .value
+            .filter(|bounds| !bounds.is_null())
+            .ok_or_else(|| format!("no element matches !clip={}", selector))?;
+        let dimension = |name: &str| bounds[name].as_f64().unwrap_or_default();
+        params.clip = Some(Viewport {
+            x: dimension("x"),
+            y: dimension("y"),
+            width: dimension("width"),
+            height: dimension("height"),
+            scale: 1.0,
+        });
+        params.capture_beyond_viewport = true;
+    } else if options.fullpage {
+        let (width, height) = page.content_size().await?;
+        params.clip = Some(Viewport {
+            x: 0.0,
+            y: 0.0,
+            width,
+            height,
+            scale: 1.0,
+        });
+        params.capture_beyond_viewport = true;
+    }
+    log::debug!("Current directory: {:?}", std::env::current_dir()?);
+    let bytes = page.capture_screenshot_with(&params).await?;
+    save_and_view(path, &bytes, options)?;
+    log::debug!("Screenshot saved to {}", path);
     Ok(())
 }
 
-fn is_invalid_url(url: &str) -> bool {
-    // List of URL prefixes or patterns to exclude
-    let invalid_prefixes = [
-        "ws://",               // WebSocket URLs
-        "chrome-extension://", // Chrome extensions
-        "chrome://",           // Internal Chrome pages
-        "chrome-untrusted://", // Internal Chrome pages
-        "about:",              // About pages
-        "file://",             // Local file URLs
-        "data:",               // Data URLs
-        "javascript:",         // JavaScript URLs
-    ];
+async fn print_pdf(
+    endpoint: &str,
+    target_id: &str,
+    path: &str,
+    options: &BangOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let client = CdpClient::connect_page(endpoint, target_id).await?;
+    let page = client.page();
+    let params = PrintToPdf {
+        landscape: options.landscape,
+        print_background: options.background,
+        paper_width: options.paper.map(|paper| paper.width),
+        paper_height: options.paper.map(|paper| paper.height),
+        margin_top: options.margin.map(|margin| margin.top),
+        margin_right: options.margin.map(|margin| margin.right),
+        margin_bottom: options.margin.map(|margin| margin.bottom),
+        margin_left: options.margin.map(|margin| margin.left),
+        display_header_footer: options.header.is_some() || options.footer.is_some(),
+        // Chrome prints its own date/title header when only one template is given.
+        header_template: options
+            .header
+            .clone()
+            .or_else(|| options.footer.as_ref().map(|_| "<span></span>".to_string())),
+        footer_template: options
+            .footer
+            .clone()
+            .or_else(|| options.header.as_ref().map(|_| "<span></span>".to_string())),
+    };
+    let bytes = page.print_to_pdf(&params).await?;
+    save_and_view(path, &bytes, options)?;
+    log::debug!("PDF saved to {}", path);
+    Ok(())
+}
+
+// Writes `bytes` to `path`, creating its directory, and opens it unless `!noview`.
+fn save_and_view(path: &str, bytes: &[u8], options: &BangOptions) -> std::io::Result<()> {
+    if let Some(parent) = std::path::Path::new(path).parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, bytes)?;
+    // Headless runs usually have no desktop to show the viewer on.
+    if !options.headless && !options.noview {
+        view_file(std::path::Path::new(path));
+    }
+    Ok(())
+}
 
-    // Check if the URL starts with any of the invalid prefixes
-    invalid_prefixes
-        .iter()
-        .any(|prefix| url.starts_with(prefix))
+// Opens a file in its default viewer. The path comes from the URL, so it goes to the opener
+// as its own argument, made absolute so it can't pass for an option, and never through a shell.
+fn view_file(path: &std::path::Path) {
+    let Ok(path) = std::path::absolute(path) else {
+        return;
+    };
+    #[cfg(target_os = "windows")]
+    let opener = "explorer";
+    #[cfg(target_os = "macos")]
+    let opener = "open";
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let opener = "xdg-open";
+    if let Err(e) = Command::new(opener).arg(&path).spawn() {
+        log::debug!("Could not open {} with {}: {}", path.display(), opener, e);
+    }
 }
 
-#[cfg(target_os = "windows")]
-use winapi::um::winuser::{GetSystemMetrics, SM_CXSCREEN, SM_CYSCREEN};
+async fn search_tabs_for_bang_id(
+    endpoint: &str,
+    search_id: &str,
+) -> Result<Option<(String, String, String)>, Box<dyn std::error::Error + Send + Sync>> {
+    let tabs = select_tabs(endpoint, &TabSelector::Bang(search_id.to_string())).await?;
+    if tabs.is_empty() {
+        log::debug!("No tab found with bangId = {}", search_id);
+    }
+    Ok(tabs
+        .into_iter()
+        .next()
+        .map(|tab| (tab.id, tab.title, tab.url)))
+}
 
-#[cfg(target_os = "windows")]
-fn get_screen_resolution(monitor_index: Option<usize>) -> (i32, i32) {
-    let monitors = get_monitor_bounds();
-    if monitors.is_empty() {
-        // Fallback to primary screen resolution if no monitors are detected
-        let width = unsafe { GetSystemMetrics(SM_CXSCREEN) };
-        let height = unsafe { GetSystemMetrics(SM_CYSCREEN) };
-        (width, height)
-    } else {
-        // Handle out-of-bounds monitor index and use the resolution of the specified monitor
-        let monitor = monitors.get(monitor_index.unwrap_or(0)).unwrap_or_else(|| {
-            log::debug!("Monitor index out of bounds, falling back to primary monitor.");
-            &monitors[0]
-        });
-        let width = monitor.rect.right - monitor.rect.left;
-        let height = monitor.rect.bottom - monitor.rect.top;
-        (width, height)
+async fn activate_tab(endpoint: &str, target_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let client = CdpClient::connect_browser(endpoint).await?;
+    client.activate_target(target_id).await?;
+    log::debug!("Activated tab with targetId: {}", target_id);
+
+    Ok(())
+}
+
+// Screen assumed for `!w`/`!h` percentages when there is no real display.
+const HEADLESS_SCREEN: (i32, i32) = (1920, 1080);
+
+// Viewport for `!headless`, from `!w`/`!h` in pixels or percent of `HEADLESS_SCREEN`.
+fn headless_viewport(options: &BangOptions) -> Option<(i32, i32)> {
+    if !options.headless || (options.w.is_none() && options.h.is_none()) {
+        return None;
     }
+    let (screen_width, screen_height) = HEADLESS_SCREEN;
+    let width = options.w.map_or(screen_width, |w| w.resolve(screen_width));
+    let height = options
+        .h
+        .map_or(screen_height, |h| h.resolve(screen_height));
+    Some((width, height))
 }
 
-fn parse_dimension(value: &str, max: i32) -> Option<i32> {
-    if value.ends_with('%') {
-        // Parse as percentage
-        let percentage = value.trim_end_matches('%').parse::<f32>().ok()?;
-        Some(((percentage / 100.0) * max as f32).round() as i32)
-    } else {
-        // Parse as absolute value
-        value.parse::<i32>().ok()
+// Extra launch flags for a headless browser; the window size doubles as the default viewport.
+fn headless_args(headless: bool, viewport: Option<(i32, i32)>) -> Vec<String> {
+    let mut args = Vec::new();
+    if headless {
+        args.push(browser::HEADLESS_ARG.to_string());
+        if let Some((width, height)) = viewport {
+            args.push(format!("--window-size={},{}", width, height));
+        }
     }
+    args
 }
 
-use std::ptr;
 #[cfg(target_os = "windows")]
 use winapi::shared::windef::HWND;
 #[cfg(target_os = "windows")]
 use winapi::um::winuser::EnumWindows;
 
+// Prints the window that shows a tab and records it in the `--json` report.
+#[cfg(target_os = "windows")]
+fn report_window(hwnd: HWND, target_id: &str, title: &str, page_url: &str) {
+    let pid = unsafe {
+        let mut pid = 0;
+        winapi::um::winuser::GetWindowThreadProcessId(hwnd, &mut pid);
+        pid
+    };
+    say!(
+        "HWND: {:?}\nPID: {:?}\nTITLE: {}\nTARGET: {}\nPAGE_URL: {}",
+        hwnd,
+        pid,
+        title,
+        target_id,
+        page_url
+    );
+    report::window(target_id, hwnd as u64, pid);
+}
+
 #[cfg(target_os = "windows")]
 #[allow(dead_code)]
 fn find_chrome_hwnd_by_title(title: &str, bangid: &str) -> Option<HWND> {
-    
     use std::ffi::OsString;
     use std::os::windows::ffi::OsStringExt;
     use winapi::um::winuser::{GW_HWNDPREV, GetWindow, GetWindowTextW};
@@ -1389,7 +1610,7 @@ fn find_chrome_hwnd_by_title(title: &str, bangid: &str) -> Option<HWND> {
         winapi::um::winuser::EnumWindows(Some(enum_windows_proc), &mut data as *mut _ as isize);
     }
     if matches.len() > 1 {
-        println!("Multiple Chrome windows found matching title '{}':", title);
+        say!("Multiple Chrome windows found matching title '{}':", title);
         for hwnd in &matches {
             let mut buffer = [0u16; 256];
             let length = unsafe {
@@ -1403,9 +1624,12 @@ fn find_chrome_hwnd_by_title(title: &str, bangid: &str) -> Option<HWND> {
                 String::from("<no title>")
             };
             let zorder = get_z_order(*hwnd);
-            println!(
+            say!(
                 "  HWND {:?}  Title '{}'  BangId '{}'  ZOrder {}",
-                hwnd, window_title, bangid, zorder
+                hwnd,
+                window_title,
+                bangid,
+                zorder
             );
         }
     }
@@ -1421,58 +1645,22 @@ fn find_chrome_hwnd_by_title(title: &str, bangid: &str) -> Option<HWND> {
 }
 
 #[allow(dead_code)]
-async fn set_tab_title(target_id: &str, new_title: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let socket_url = format!("ws://localhost:9222/devtools/page/{}", target_id);
-    let (mut socket, _) = connect_async(&socket_url).await?;
-    let enable = serde_json::json!({
-        "id": 1,
-        "method": "Runtime.enable"
-    });
-    socket
-        .send(Message::Text(enable.to_string().into()))
-        .await?;
+async fn set_tab_title(
+    endpoint: &str,
+    target_id: &str,
+    new_title: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let client = CdpClient::connect_page(endpoint, target_id).await?;
+    let page = client.page();
+    page.enable_runtime().await?;
     // JavaScript to set the document title
-    let id = get_unique_id();
-    let set_title_script = format!("document.title = '{}';", new_title);
-    let set_title_command = serde_json::json!({
-        "id": id,
-        "method": "Runtime.evaluate",
-        "params": {
-            "expression": set_title_script
-        }
-    });
-
-    socket
-        .send(Message::Text(set_title_command.to_string().into()))
-        .await?;
-    // Drain until we see our eval response
-    let timeout_duration = Duration::from_secs(5); // Set a timeout duration
-    let timeout_future = tokio::time::sleep(timeout_duration);
-    tokio::pin!(timeout_future);
-
-    'outer: while let Some(Ok(msg)) = tokio::select! {
-        _ = &mut timeout_future => {
-            log::debug!("Timeout while waiting for title set response.");
-            break 'outer;
-        }
-        msg = socket.next() => msg
-    } {
-        if let Message::Text(txt) = msg {
-            if let Ok(json) = serde_json::from_str::<Value>(&txt) {
-                if json["id"].as_i64() == Some(id.try_into().unwrap()) {
-                    log::debug!("✅ title set response: {}", txt);
-                    break;
-                }
-            }
-        }
-    }
-
+    let set_title_script = format!("document.title = {};", serde_json::json!(new_title));
+    page.evaluate(&set_title_script, false).await?;
     log::debug!("Set tab {} title to: {}", target_id, new_title);
 
     Ok(())
 }
 
-use futures_util::SinkExt;
 #[cfg(target_os = "windows")]
 use winapi::um::winuser::{SW_RESTORE, SetForegroundWindow, ShowWindow};
 
@@ -1492,22 +1680,11 @@ fn bring_hwnd_to_front(hwnd: HWND) {
     }
 }
 
-async fn is_cdp_server_running() -> bool {
-    match reqwest::get("http://localhost:9222/json").await {
-        Ok(response) => response.status().is_success(),
-        Err(_) => false,
-    }
+async fn is_cdp_server_running(endpoint: &str) -> bool {
+    list_targets(endpoint).await.is_ok()
 }
 
-fn prepare_chrome_profile(new_environment: bool) -> io::Result<std::path::PathBuf> {
-    let chrome_user_data = dirs::data_local_dir()
-        .expect("%LOCALAPPDATA% not found")
-        .join("Google\\Chrome\\User Data");
-
-    let source_default = chrome_user_data.join("Default");
-    let source_local_state = chrome_user_data.join("Local State");
-    let source_sessions = source_default.join("Sessions");
-
+fn prepare_chrome_profile(new_environment: bool) -> io::Result<PathBuf> {
     let temp_root = if new_environment {
         let timestamp = chrono::Local::now()
             .format("debugchrome-%y%m%d%H%M%S")
@@ -1523,40 +1700,37 @@ fn prepare_chrome_profile(new_environment: bool) -> io::Result<std::path::PathBu
     let _ = fs::remove_dir_all(&temp_root);
     fs::create_dir_all(&temp_default)?;
 
-    if new_environment {
-        // Only copy Local State for new environment setup
-        Command::new("xcopy")
-            .arg(&source_local_state)
-            .arg(&temp_root)
-            .args(["/H", "/Y"])
-            .stdout(std::process::Stdio::null())
-            .stderr(std::process::Stdio::null())
-            .status()?;
-    } else {
-        // Full copy of Default and Sessions
-        fs::create_dir_all(&temp_sessions)?;
+    // Without an installed profile Chrome simply starts with a fresh one.
+    let Some(chrome_user_data) = browser::user_data_dir() else {
+        log::debug!(
+            "No Chrome user data found in {:?}; using an empty profile.",
+            browser::user_data_dir_candidates()
+        );
+        return Ok(temp_root);
+    };
+    log::debug!("Using Chrome user data from {}", chrome_user_data.display());
 
-        Command::new("xcopy")
-            .arg(&source_default)
-            .arg(&temp_default)
-            .args(["/E", "/I", "/H", "/Y"])
-            .stdout(std::process::Stdio::null())
-            .stderr(std::process::Stdio::null())
-            .status()?;
+    let source_default = chrome_user_data.join("Default");
+    let source_local_state = chrome_user_data.join("Local State");
+    let source_sessions = source_default.join("Sessions");
 
-        Command::new("xcopy")
-            .arg(&source_local_state)
-            .arg(&temp_root)
-            .args(["/H", "/Y"])
-            .stdout(std::process::Stdio::null())
-            .stderr(std::process::Stdio::null())
-            .status()?;
+    if source_local_state.is_file() {
+        fs::copy(&source_local_state, temp_root.join("Local State"))?;
+    }
+    if !new_environment {
+        // Full copy of Default and Sessions
+        if source_default.is_dir() {
+            profile::copy_profile_dir(&source_default, &temp_default)?;
+        }
+        fs::create_dir_all(&temp_sessions)?;
 
-        for entry in fs::read_dir(&source_sessions)? {
-            let path = entry?.path();
-            if path.is_file() && fs::metadata(&path)?.len() > 0 {
-                let filename = path.file_name().unwrap();
-                fs::copy(&path, temp_sessions.join(filename))?;
+        if source_sessions.is_dir() {
+            for entry in fs::read_dir(&source_sessions)? {
+                let path = entry?.path();
+                if path.is_file() && fs::metadata(&path)?.len() > 0 {
+                    let filename = path.file_name().unwrap();
+                    fs::copy(&path, temp_sessions.join(filename))?;
+                }
             }
         }
     }
@@ -1564,113 +1738,85 @@ fn prepare_chrome_profile(new_environment: bool) -> io::Result<std::path::PathBu
     Ok(temp_root)
 }
 
-fn launch_chrome(user_data_dir: &Path) -> io::Result<()> {
-    Command::new("cmd")
-        .args([
-            "/C",
-            "start",
-            "chrome.exe",
-            "--remote-debugging-port=9222",
-            "--enable-automation",
-            "--no-first-run",
-            &format!("--user-data-dir={}", user_data_dir.display()),
-        ])
-        .spawn()?;
-    Ok(())
-}
-
-async fn refresh_tab(target_id: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let socket_url = format!("ws://localhost:9222/devtools/page/{}", target_id);
-    let (mut socket, _) = connect_async(&socket_url).await?;
-
-    // Send the Page.reload command
-    let reload_command = serde_json::json!({
-        "id": 1,
-        "method": "Page.reload",
-        "params": {}
-    });
-
-    socket
-        .send(Message::Text(reload_command.to_string().into()))
-        .await?;
-    log::debug!("Sent command to refresh tab with targetId: {}", target_id);
+fn launch_chrome(user_data_dir: &Path, port: u16, extra_args: &[String]) -> io::Result<()> {
+    if let Some(chrome) = browser::find_chrome() {
+        say!("Found Chrome executable at: {}", chrome.display());
+        browser::launch(&chrome, port, user_data_dir, extra_args)?;
+        return Ok(());
+    }
 
-    // Optionally, wait for a response to confirm the reload
-    let timeout_duration = Duration::from_secs(5); // Set a timeout duration
-    let timeout_future = tokio::time::sleep(timeout_duration);
-    tokio::pin!(timeout_future);
+    #[cfg(target_os = "windows")]
+    {
+        log::debug!("Chrome executable not found in registry or PATH; using start.");
+        Command::new("cmd")
+            .args(["/C", "start", "chrome.exe"])
+            .args(browser::debug_args(port, user_data_dir))
+            .args(extra_args)
+            .spawn()?;
+        Ok(())
+    }
+    #[cfg(not(target_os = "windows"))]
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!(
+            "Chrome executable not found; looked for {} in PATH",
+            browser::CHROME_EXECUTABLES.join(", ")
+        ),
+    ))
+}
 
-    tokio::select! {
-        _ = &mut timeout_future => {
-            log::debug!("Timeout while waiting for response.");
-        }
-        Some(Ok(msg)) = socket.next() => {
-            if let Message::Text(txt) = msg {
-                log::debug!("Received response: {}", txt);
-            }
+// Polls `/json` until the freshly launched browser answers or `timeout` passes.
+async fn wait_for_cdp_server(endpoint: &str, timeout: Duration) -> bool {
+    let deadline = std::time::Instant::now() + timeout;
+    while std::time::Instant::now() < deadline {
+        if is_cdp_server_running(endpoint).await {
+            return true;
         }
+        tokio::time::sleep(Duration::from_millis(250)).await;
     }
+    false
+}
+
+// Returns once the reloaded document has committed, so a `!wait=` after it can't be met
+// by the page that is about to go away. The commit gets as long as `!wait=` would.
+async fn refresh_tab(
+    endpoint: &str,
+    target_id: &str,
+    options: &BangOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let timeout = options
+        .wait_timeout
+        .map_or(DEFAULT_WAIT_TIMEOUT, Duration::from_secs);
+    let client = CdpClient::connect_page(endpoint, target_id).await?;
+    client.page().reload(timeout).await?;
+    log::debug!("Refreshed tab with targetId: {}", target_id);
 
     Ok(())
 }
 
-async fn close_tab_by_target_id(target_id: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let version: serde_json::Value = reqwest::get("http://localhost:9222/json/version")
-        .await?
-        .json()
-        .await?;
-    let ws_url = version["webSocketDebuggerUrl"]
-        .as_str()
-        .ok_or("No WebSocket URL")?;
-    let (mut socket, _) = tokio_tungstenite::connect_async(ws_url).await?;
-
-    let close_command = serde_json::json!({
-        "id": get_unique_id(),
-        "method": "Target.closeTarget",
-        "params": { "targetId": target_id }
-    });
+async fn close_tab_by_target_id(
+    endpoint: &str,
+    target_id: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let client = CdpClient::connect_browser(endpoint).await?;
 
     let max_retries = 20;
     let mut attempts = 0;
 
     while attempts < max_retries {
-        if socket
-            .send(Message::Text(close_command.to_string().into()))
-            .await
-            .is_ok()
-        {
-            log::debug!("Sent command to close tab with targetId: {}", target_id);
-
-            // Wait for a response to confirm the tab was closed with a timeout
-            let timeout_duration = Duration::from_secs(5);
-            let response = tokio::time::timeout(timeout_duration, socket.next()).await;
-
-            match response {
-                Ok(Some(Ok(Message::Text(txt)))) => {
-                    log::debug!("Received response: {}", txt);
-                    let json: serde_json::Value = serde_json::from_str(&txt)?;
-                    if json["id"] == close_command["id"] {
-                        log::debug!("Tab with targetId {} closed successfully.", target_id);
-                        break;
-                    } else {
-                        log::debug!("Failed to close tab with targetId: {}", target_id);
-                        if let Ok(mut file) = fs::OpenOptions::new()
-                            .append(true)
-                            .create(true)
-                            .open("debugchrome_error.log")
-                        {
-                            writeln!(file, "Failed to close tab with targetId: {}", target_id).ok();
-                        }
-                    }
-                }
-                Ok(Some(_)) => {
-                    log::debug!("Unexpected message received while closing tab.");
-                }
-                Ok(None) => {
-                    log::debug!("WebSocket stream ended unexpectedly.");
-                }
-                Err(_) => {
-                    log::debug!("Timeout while waiting for response to close tab.");
+        match client.close_target(target_id).await {
+            Ok(()) => {
+                log::debug!("Tab with targetId {} closed successfully.", target_id);
+                break;
+            }
+            Err(e) => {
+                log::debug!("Failed to close tab with targetId {}: {}", target_id, e);
+                if let Ok(mut file) = fs::OpenOptions::new()
+                    .append(true)
+                    .create(true)
+                    .open("debugchrome_error.log")
+                {
+                    writeln!(file, "Failed to close tab with targetId: {}", target_id).ok();
                 }
             }
         }
@@ -1697,88 +1843,429 @@ async fn close_tab_by_target_id(target_id: &str) -> Result<(), Box<dyn std::erro
 }
 
 async fn set_bang_id_session(
+    endpoint: &str,
     target_id: &str,
     bang_id: &str,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let socket_url = format!("ws://localhost:9222/devtools/page/{}", target_id);
-    let (mut socket, _) = connect_async(&socket_url).await?;
+    let client = CdpClient::connect_page(endpoint, target_id).await?;
+    let page = client.page();
+    let bang_id_js = serde_json::json!(bang_id);
     // Set the hash for the page to the bangId
-    let set_hash = serde_json::json!({
-        "id": get_unique_id(),
-        "method": "Runtime.evaluate",
-        "params": {
-            "expression": format!("window.location.hash = '{}';", bang_id),
-        }
-    });
-    log::debug!("set_hash: {:?}", set_hash);
-    socket
-        .send(Message::Text(set_hash.to_string().into()))
+    page.evaluate(&format!("window.location.hash = {};", bang_id_js), false)
         .await?;
     log::debug!("Set window.location.hash to {}", bang_id);
-    // Set the bangId in sessionStorage
-    let set_bang_id = serde_json::json!({
-        "id": get_unique_id(),
-        "method": "Runtime.evaluate",
-        "params": {
-            // "expression": format!("sessionStorage.setItem('bangId', '{}');", bang_id),
-            "expression": format!("window.bangId='{}';", bang_id),
-        }
-    });
-    log::debug!("set_bang_id: {:?}", set_bang_id);
-    socket
-        .send(Message::Text(set_bang_id.to_string().into()))
-        .await?;
+    // Tag every document the tab loads from now on, then the current one with the same script
+    let script = bang_id_script(bang_id);
+    if !bang_id.is_empty() {
+        spawn_injector(endpoint, target_id, &script).await?;
+    }
+    page.evaluate(&script, false).await?;
     log::debug!("Set window.bangId to {}", bang_id);
 
     // Verify that the bangId was set
-    let verify_bang_id = serde_json::json!({
-        "id": 4,
-        "method": "Runtime.evaluate",
-        "params": {
-            // "expression": "sessionStorage.getItem('bangId')",
-            "expression": "window.bangId",
+    let verified = page.evaluate("window.bangId", true).await?;
+    match verified.value.as_ref().and_then(|v| v.as_str()) {
+        Some(verified_bang_id) if verified_bang_id == bang_id => {
+            log::debug!("Successfully verified bangId: {}", verified_bang_id);
+            Ok(())
         }
-    });
-    log::debug!("verify_bang_id: {:?}", verify_bang_id);
-    socket
-        .send(Message::Text(verify_bang_id.to_string().into()))
-        .await?;
-    log::debug!("Sent command to verify bangId");
-
-    // Wait for the response
-    let timeout_duration = Duration::from_secs(5);
-    let timeout_future = tokio::time::timeout(timeout_duration, async {
-        while let Some(Ok(msg)) = socket.next().await {
-            if let Message::Text(txt) = msg {
-                log::debug!("Received message: {}", txt);
-                let json: serde_json::Value = serde_json::from_str(&txt)?;
-                if json["id"] == 4 {
-                    if let Some(verified_bang_id) = json["result"]["result"]["value"].as_str() {
-                        if verified_bang_id == bang_id {
-                            log::debug!("Successfully verified bangId: {}", verified_bang_id);
-                            return Ok(());
-                        } else {
-                            log::debug!(
-                                "Mismatch: Expected {}, but got {}",
-                                bang_id,
-                                verified_bang_id
-                            );
-                            return Err("Failed to verify bangId".into());
-                        }
-                    }
-                }
+        other => {
+            log::debug!("Mismatch: Expected {}, but got {:?}", bang_id, other);
+            Err("Failed to verify bangId".into())
+        }
+    }
+}
+
+// The script that tags a document with its bang id.
+fn bang_id_script(bang_id: &str) -> String {
+    format!("window.bangId = {};", serde_json::json!(bang_id))
+}
+
+// Registers `!inject=` for every document the tab loads from now on, then runs it in the
+// current one.
+async fn inject_into_tab(
+    endpoint: &str,
+    target_id: &str,
+    source: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    spawn_injector(endpoint, target_id, source).await?;
+    let client = CdpClient::connect_page(endpoint, target_id).await?;
+    let result = client.page().evaluate(source, false).await?;
+    if let Some(exception) = result.exception {
+        return Err(exception.message().into());
+    }
+    Ok(())
+}
+
+// Chrome forgets `Page.addScriptToEvaluateOnNewDocument` scripts when the connection that
+// added them closes, so a detached `--keep-injected` process registers `source` and stays
+// connected until the tab (or the browser) goes away. Returns once it is registered.
+async fn spawn_injector(
+    endpoint: &str,
+    target_id: &str,
+    source: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Running the same command on the tab again must not register the script twice.
+    if injected_script_lock(endpoint, target_id, source)?.is_none() {
+        log::debug!("{:?} is already kept injected into {}", source, target_id);
+        return Ok(());
+    }
+    // Sources can be whole files, too long for a command line; the helper reads stdin.
+    spawn_listening_helper(endpoint, &["--keep-injected", target_id], Some(source)).await
+}
+
+async fn keep_injected(endpoint: &str, target_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut source = String::new();
+    io::Read::read_to_string(&mut io::stdin(), &mut source)?;
+    // Checked again here in case two of these start at once.
+    let Some(lock) = injected_script_lock(endpoint, target_id, &source)? else {
+        log::debug!("{:?} is already kept injected into {}", source, target_id);
+        println!("{}", HELPER_READY);
+        return Ok(());
+    };
+    let client = CdpClient::connect_page(endpoint, target_id).await?;
+    let page = client.page();
+    page.enable_page().await?;
+    page.add_script_on_new_document(&source).await?;
+    println!("{}", HELPER_READY);
+    log::debug!(
+        "Keeping {:?} injected into {} until it closes",
+        source,
+        target_id
+    );
+    client.closed().await;
+    drop(lock);
+    let _ = fs::remove_file(injected_script_lock_path(endpoint, target_id, &source));
+    Ok(())
+}
+
+// The lock a `--keep-injected` process holds for each script it keeps in a tab. `None` when
+// another process holds it already.
+fn injected_script_lock(endpoint: &str, target_id: &str, source: &str) -> io::Result<Option<File>> {
+    let file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(injected_script_lock_path(endpoint, target_id, source))?;
+    Ok(file.try_lock_exclusive().is_ok().then_some(file))
+}
+
+fn injected_script_lock_path(endpoint: &str, target_id: &str, source: &str) -> PathBuf {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    (endpoint, source).hash(&mut hasher);
+    std::env::temp_dir().join(format!(
+        "debugchrome-injected-{}-{:016x}.lock",
+        target_id,
+        hasher.finish()
+    ))
+}
+
+// What `--record-har`, `--keep-intercepting`, `--keep-emulating` and `--keep-injected` print
+// once they listen, before the page may load.
+const HELPER_READY: &str = "ready";
+
+// Starts a detached `--record-har` process that writes the tab's HAR to `path` when the
+// tab closes, and returns once it is recording.
+async fn spawn_har_recorder(
+    endpoint: &str,
+    target_id: &str,
+    path: &str,
+    options: &BangOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let page_id = options
+        .id
+        .as_deref()
+        .filter(|id| !id.is_empty())
+        .unwrap_or(target_id);
+    spawn_listening_helper(
+        endpoint,
+        &[
+            "--record-har",
+            target_id,
+            "--har",
+            path,
+            "--page-id",
+            page_id,
+        ],
+        None,
+    )
+    .await
+}
+
+// Starts a detached `--keep-intercepting` process that applies the rules at `rules` to the
+// tab until it closes, and returns once requests are paused for it.
+async fn spawn_interceptor(
+    endpoint: &str,
+    target_id: &str,
+    rules: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    spawn_listening_helper(
+        endpoint,
+        &["--keep-intercepting", target_id, "--intercept", rules],
+        None,
+    )
+    .await
+}
+
+// Starts a detached `--keep-emulating` process that holds the tab's emulation overrides until
+// it closes, and returns once they are in place.
+async fn spawn_emulator(
+    endpoint: &str,
+    target_id: &str,
+    emulation: &Emulation,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let emulation = serde_json::to_string(emulation)?;
+    spawn_listening_helper(
+        endpoint,
+        &["--keep-emulating", target_id, "--emulation", &emulation],
+        None,
+    )
+    .await
+}
+
+// Runs `debugchrome <args>` detached, writes `input` to its stdin, and waits until it
+// prints `HELPER_READY`.
+async fn spawn_listening_helper(
+    endpoint: &str,
+    args: &[&str],
+    input: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let current_exe = std::env::current_exe()?;
+    let mut command = Command::new(&current_exe);
+    command
+        .args(args)
+        .args(["--cdp-endpoint", endpoint])
+        .stdin(if input.is_some() {
+            std::process::Stdio::piped()
+        } else {
+            std::process::Stdio::null()
+        })
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::null());
+    // CREATE_NO_WINDOW
+    #[cfg(target_os = "windows")]
+    command.creation_flags(0x08000000);
+    let mut child = command.spawn()?;
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        // Dropping the pipe afterwards is the end of input.
+        stdin.write_all(input.as_bytes())?;
+    }
+    let stdout = child.stdout.take().ok_or("helper has no stdout")?;
+    // The helper prints one line and nothing after it, so the pipe can be dropped.
+    let ready = tokio::time::timeout(
+        Duration::from_secs(15),
+        tokio::task::spawn_blocking(move || {
+            let mut line = String::new();
+            io::BufRead::read_line(&mut io::BufReader::new(stdout), &mut line).map(|_| line)
+        }),
+    )
+    .await;
+    match ready {
+        Ok(Ok(Ok(line))) if line.trim() == HELPER_READY => {
+            log::debug!("Started {:?}", args);
+            Ok(())
+        }
+        _ => {
+            let _ = child.kill();
+            Err(format!("{} {} did not start", args[0], args[1]).into())
+        }
+    }
+}
+
+async fn record_har(
+    endpoint: &str,
+    target_id: &str,
+    path: &str,
+    page_id: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let client = CdpClient::connect_page(endpoint, target_id).await?;
+    let capture = har::start(&client.page(), page_id).await?;
+    println!("{}", HELPER_READY);
+    let har = capture.finish().await;
+    if let Some(parent) = Path::new(path).parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, serde_json::to_vec_pretty(&har)?)?;
+    log::debug!(
+        "Wrote {} HAR entries of {} to {}",
+        har.log.entries.len(),
+        target_id,
+        path
+    );
+    Ok(())
+}
+
+async fn keep_intercepting(
+    endpoint: &str,
+    target_id: &str,
+    rules: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let rules = InterceptRules::load(Path::new(rules))?;
+    let client = CdpClient::connect_page(endpoint, target_id).await?;
+    let interceptor = intercept::start(&client.page(), rules).await?;
+    println!("{}", HELPER_READY);
+    log::debug!("Intercepting requests of {} until it closes", target_id);
+    interceptor.run().await;
+    Ok(())
+}
+
+// Emulation overrides last as long as the connection that set them.
+async fn keep_emulating(
+    endpoint: &str,
+    target_id: &str,
+    emulation: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let emulation: Emulation = serde_json::from_str(emulation)?;
+    let client = CdpClient::connect_page(endpoint, target_id).await?;
+    let page = client.page();
+    // Ends with `Inspector.detached`, or with `None` once the socket closes.
+    let mut detached = page.subscribe(Some("Inspector.detached"));
+    emulation.apply(&page).await?;
+    println!("{}", HELPER_READY);
+    log::debug!("Emulating {:?} in {} until it closes", emulation, target_id);
+    detached.recv().await;
+    Ok(())
+}
+
+// The origin a tab's URL belongs to, as `location.origin` spells it; `None` for opaque ones.
+fn url_origin(url: &str) -> Option<String> {
+    url::Url::parse(url)
+        .ok()
+        .map(|url| url.origin())
+        .filter(|origin| origin.is_tuple())
+        .map(|origin| origin.ascii_serialization())
+}
+
+// Writes cookies through the first tab and, for each origin in `origins` (every origin an
+// open tab shows when empty), the web storage of the first tab showing it.
+async fn export_storage(
+    endpoint: &str,
+    tabs: &[cdp::TargetInfo],
+    path: &Path,
+    origins: &[String],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let first = tabs.first().ok_or("no open tab to read cookies through")?;
+    let client = CdpClient::connect_page(endpoint, &first.id).await?;
+    let mut state = StorageState {
+        cookies: storage::read_cookies(&client.page()).await?,
+        origins: Vec::new(),
+    };
+    for tab in tabs {
+        let Some(origin) = url_origin(&tab.url) else {
+            continue;
+        };
+        if (!origins.is_empty() && !origins.contains(&origin)) || state.origin(&origin).is_some() {
+            continue;
+        }
+        report::tab(&tab.id, &tab.title, &tab.url);
+        let result = match CdpClient::connect_page(endpoint, &tab.id).await {
+            Ok(client) => storage::read_origin(&client.page()).await,
+            Err(e) => Err(e),
+        };
+        report::action_value(&tab.id, "storage", &result, Some(origin.clone().into()));
+        match result {
+            Ok(Some(stored)) => {
+                say!(
+                    "{}: {} localStorage and {} sessionStorage items",
+                    stored.origin,
+                    stored.local_storage.len(),
+                    stored.session_storage.len()
+                );
+                state.origins.push(stored);
             }
+            Ok(None) => log::debug!("{} has no readable storage", tab.id),
+            Err(e) => say!("Failed to read the storage of {}: {}", origin, e),
         }
-        Err("Timeout while verifying bangId".into())
-    });
+    }
+    for origin in origins {
+        if state.origin(origin).is_none() {
+            say!("No open tab shows {}", origin);
+            report::error(format!("no open tab shows {}", origin));
+        }
+    }
+    state.save(path)?;
+    say!(
+        "Exported {} cookies and {} origins to {}",
+        state.cookies.len(),
+        state.origins.len(),
+        path.display()
+    );
+    report::set("cookies", state.cookies.len());
+    Ok(())
+}
+
+// Sets the file's cookies through the first tab and writes its web storage into every open
+// tab of a stored origin.
+async fn import_storage(
+    endpoint: &str,
+    tabs: &[cdp::TargetInfo],
+    path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let state = StorageState::load(path)?;
+    let first = tabs.first().ok_or("no open tab to set cookies through")?;
+    let client = CdpClient::connect_page(endpoint, &first.id).await?;
+    storage::write_cookies(&client.page(), &state.cookies).await?;
+    say!("Imported {} cookies", state.cookies.len());
+    report::set("cookies", state.cookies.len());
+    for tab in tabs {
+        let Some(origin) = url_origin(&tab.url).filter(|origin| state.origin(origin).is_some())
+        else {
+            continue;
+        };
+        report::tab(&tab.id, &tab.title, &tab.url);
+        let result = write_storage(endpoint, &tab.id, &state).await;
+        report::action_value(&tab.id, "storage", &result, Some(origin.clone().into()));
+        match result {
+            Ok(()) => say!("Wrote the storage of {} into {}", origin, tab.id),
+            Err(e) => say!("Failed to write the storage of {}: {}", origin, e),
+        }
+    }
+    Ok(())
+}
+
+// `!storage=` on an existing tab: its cookies now, and its web storage if the tab shows one
+// of the stored origins.
+async fn storage_into_tab(
+    endpoint: &str,
+    target_id: &str,
+    file: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let state = StorageState::load(Path::new(file))?;
+    let client = CdpClient::connect_page(endpoint, target_id).await?;
+    storage::write_cookies(&client.page(), &state.cookies).await?;
+    write_storage(endpoint, target_id, &state).await
+}
+
+async fn write_storage(
+    endpoint: &str,
+    target_id: &str,
+    state: &StorageState,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let client = CdpClient::connect_page(endpoint, target_id).await?;
+    let result = client.page().evaluate(&state.seed_script(), true).await?;
+    if let Some(exception) = result.exception {
+        return Err(exception.message().into());
+    }
+    Ok(())
+}
 
-    timeout_future.await.unwrap_or_else(|_| {
-        log::debug!("Timeout while verifying bangId");
-        Err("Timeout while verifying bangId".into())
-    })
+// `!storage=` on a new tab, before it loads: the cookies, and a script that writes the web
+// storage into the first documents of the stored origins. The script only lasts as long as
+// `page`'s connection.
+async fn seed_storage(page: &CdpSession, file: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let state = StorageState::load(file)?;
+    storage::write_cookies(page, &state.cookies).await?;
+    if !state.origins.is_empty() {
+        page.add_script_on_new_document(&state.seed_script())
+            .await?;
+    }
+    page.enable_page().await?;
+    Ok(())
 }
 
 fn spawn_timeout_closer(
+    endpoint: &str,
     target_id: String,
     timeout_seconds: u64,
 ) -> Result<(), Box<dyn std::error::Error>> {
@@ -1792,6 +2279,8 @@ fn spawn_timeout_closer(
         &target_id,
         "--timeout",
         &timeout_seconds.to_string(),
+        "--cdp-endpoint",
+        endpoint,
     ];
     log::debug!("Spawning detached process with args: cmd {:?}", args);
 
@@ -1802,6 +2291,15 @@ fn spawn_timeout_closer(
             .creation_flags(0x08000000)
             .spawn()?;
     }
+    #[cfg(not(target_os = "windows"))]
+    {
+        Command::new(&current_exe)
+            .args(&args[4..])
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn()?;
+    }
 
     log::debug!(
         "Spawned detached process to close target {} after {} seconds.",
@@ -1824,132 +2322,6 @@ fn get_focused_window() -> Option<winapi::shared::windef::HWND> {
         if hwnd.is_null() { None } else { Some(hwnd) }
     }
 }
-#[cfg(target_os = "windows")]
-use winapi::shared::windef::{HMONITOR, RECT};
-#[cfg(target_os = "windows")]
-use winapi::um::winuser::EnumDisplayMonitors;
-
-#[cfg(target_os = "windows")]
-fn get_monitor_bounds() -> Vec<MonitorInfo> {
-    let mut monitors = Vec::new();
-
-    unsafe extern "system" fn monitor_enum_proc(
-        hmonitor: HMONITOR,
-        _: *mut winapi::shared::windef::HDC__,
-        lprc_monitor: *mut RECT,
-        lparam: isize,
-    ) -> i32 {
-        let monitors = unsafe { &mut *(lparam as *mut Vec<MonitorInfo>) };
-
-        // Retrieve DPI scaling for the monitor
-        let dpi_scaling = get_dpi_for_monitor(hmonitor);
-
-        monitors.push(MonitorInfo {
-            hmonitor,
-            rect: unsafe { *lprc_monitor },
-            dpi_scaling, // Populate DPI scaling
-        });
-        1 // Continue enumeration
-    }
-
-    unsafe {
-        EnumDisplayMonitors(
-            ptr::null_mut(),
-            ptr::null_mut(),
-            Some(monitor_enum_proc),
-            &mut monitors as *mut _ as isize,
-        );
-    }
-
-    monitors
-}
-
-fn adjust_bounds_to_monitor(
-    monitor_index: usize,
-    x: i32,
-    y: i32,
-    w: i32,
-    h: i32,
-    dpi_scaling_enabled: bool,
-) -> Option<(i32, i32, i32, i32)> {
-    #[cfg(not(target_os = "windows"))]
-    {
-        log::debug!("Screen bounds adjustment is only supported on Windows.");
-        return None;
-    }
-    #[cfg(target_os = "windows")]
-    {
-        let monitors = get_monitor_bounds();
-        if monitor_index >= monitors.len() {
-            return None; // Invalid monitor index
-        }
-
-        let monitor = &monitors[monitor_index];
-        // let monitor_width = monitor.rect.right - monitor.rect.left;
-        // let monitor_height = monitor.rect.bottom - monitor.rect.top;
-
-        log::debug!(
-            "Monitor bounds: left={}, top={}, right={}, bottom={}",
-            monitor.rect.left,
-            monitor.rect.top,
-            monitor.rect.right,
-            monitor.rect.bottom
-        );
-        // Adjust bounds relative to the monitor
-        let x = monitor.rect.left + x;
-        let y = monitor.rect.top + y;
-        // let w = w.min(monitor_width);
-        // let h = h.min(monitor_height);
-
-        // Apply DPI scaling if enabled
-        let (adjusted_x, adjusted_y, adjusted_w, adjusted_h) = if dpi_scaling_enabled {
-            adjust_for_dpi(x, y, w, h, monitor.dpi_scaling)
-        } else {
-            (x, y, w, h)
-        };
-        if dpi_scaling_enabled {
-            log::debug!(
-                "Monitor DPI scaling: {}, Adjusted bounds: x={}, y={}, w={}, h={}",
-                monitor.dpi_scaling,
-                adjusted_x,
-                adjusted_y,
-                adjusted_w,
-                adjusted_h
-            );
-        }
-
-        Some((adjusted_x, adjusted_y, adjusted_w, adjusted_h))
-    }
-}
-
-#[cfg(target_os = "windows")]
-use winapi::um::shellscalingapi::{GetDpiForMonitor, MDT_EFFECTIVE_DPI};
-#[cfg(target_os = "windows")]
-use winapi::um::winnt::HRESULT;
-
-#[cfg(target_os = "windows")]
-fn get_dpi_for_monitor(monitor: HMONITOR) -> f32 {
-    unsafe {
-        let mut dpi_x = 0;
-        let mut dpi_y = 0;
-        let result: HRESULT = GetDpiForMonitor(monitor, MDT_EFFECTIVE_DPI, &mut dpi_x, &mut dpi_y);
-        if result == 0 {
-            dpi_x as f32 / 96.0 // Convert DPI to scaling factor (96 DPI = 100%)
-        } else {
-            1.0 // Default scaling factor if DPI retrieval fails
-        }
-    }
-}
-
-#[cfg(target_os = "windows")]
-fn adjust_for_dpi(x: i32, y: i32, w: i32, h: i32, dpi_scaling: f32) -> (i32, i32, i32, i32) {
-    (
-        (x as f32 / dpi_scaling).round() as i32,
-        (y as f32 / dpi_scaling).round() as i32,
-        (w as f32 / dpi_scaling).round() as i32,
-        (h as f32 / dpi_scaling).round() as i32,
-    )
-}
 
 use std::panic;
 
@@ -1976,6 +2348,7 @@ fn setup_panic_hook() {
         };
         log::error!("PANIC: {}\n{}", panic_message, location);
 
+        #[cfg_attr(not(target_os = "windows"), allow(unused_variables))]
         let log_file_path = if let Ok(exe_path) = std::env::current_exe() {
             if let Some(exe_dir) = exe_path.parent() {
                 exe_dir
@@ -2011,6 +2384,7 @@ fn setup_panic_hook() {
     }));
 }
 
+#[cfg(target_os = "windows")]
 use sysinfo::System;
 #[cfg(target_os = "windows")]
 use winapi::um::winuser::{GetWindowThreadProcessId, IsWindowVisible};
@@ -2070,7 +2444,7 @@ fn find_chrome_with_debug_port() -> Option<u32> {
                 .to_ascii_lowercase()
                 .find("--remote-debugging-port")
             {
-                println!(
+                say!(
                     "Found Chrome process with PID: {} and command line: {:?}",
                     pid,
                     process.cmd().join(std::ffi::OsStr::new(" "))
@@ -2089,7 +2463,7 @@ fn find_chrome_with_debug_port() -> Option<u32> {
                 }
 
                 if let Some(hwnd) = data.hwnd {
-                    println!("Found Chrome window with HWND {:?}", hwnd);
+                    say!("Found Chrome window with HWND {:?}", hwnd);
                     return Some(pid.as_u32());
                 }
             }
@@ -2099,46 +2473,181 @@ fn find_chrome_with_debug_port() -> Option<u32> {
     None
 }
 
-// Execute arbitrary JavaScript on a tab via DevTools Protocol
+// Execute arbitrary JavaScript on a tab via DevTools Protocol. Promises are awaited; the
+// value comes back as JSON (`None` for `undefined`) and a thrown exception is an error.
 async fn execute_script_on_tab(
+    endpoint: &str,
     target_id: &str,
     script: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let socket_url = format!("ws://localhost:9222/devtools/page/{}", target_id);
-    let (mut socket, _) = tokio_tungstenite::connect_async(&socket_url).await?;
-    let enable = serde_json::json!({
-        "id": 1,
-        "method": "Runtime.enable"
-    });
-    socket
-        .send(Message::Text(enable.to_string().into()))
-        .await?;
-    let id = get_unique_id();
-    let eval_command = serde_json::json!({
-        "id": id,
-        "method": "Runtime.evaluate",
-        "params": {
-            "expression": script,
-            "returnByValue": false
+    timeout: Duration,
+) -> Result<Option<serde_json::Value>, Box<dyn std::error::Error>> {
+    // The reply only comes once the script's promise settles.
+    let client = CdpClient::connect_page(endpoint, target_id)
+        .await?
+        .with_timeout(timeout);
+    let page = client.page();
+    page.enable_runtime().await?;
+    let result = page.evaluate_awaiting(script).await?;
+    log::debug!("Script eval response: {:?}", result);
+    if let Some(exception) = result.exception {
+        return Err(exception.message().into());
+    }
+    Ok(result.value)
+}
+
+// Streams what each tab logs to stdout until every one of them has closed. With several
+// tabs each line starts with the target id; with `--json` each entry is a JSON line.
+async fn tail_tabs(endpoint: &str, target_ids: &[String]) {
+    let prefix = target_ids.len() > 1;
+    let tails = target_ids.iter().map(|target_id| async move {
+        let mut entries = 0u64;
+        let result = tail_tab(endpoint, target_id, |entry| {
+            entries += 1;
+            print_log_entry(target_id, prefix, &entry);
+        })
+        .await;
+        if let Err(e) = &result {
+            log::debug!("Failed to tail tab {}: {}", target_id, e);
         }
+        report::action_value(target_id, "tail", &result, Some(entries.into()));
     });
-    socket
-        .send(Message::Text(eval_command.to_string().into()))
-        .await?;
-    let timeout_duration = Duration::from_secs(5);
-    let timeout_future = tokio::time::sleep(timeout_duration);
-    tokio::pin!(timeout_future);
-    'outer: while let Some(Ok(msg)) = tokio::select! {
-        _ = &mut timeout_future => {
-            log::debug!("Timeout while waiting for script eval response.");
-            break 'outer;
+    futures::future::join_all(tails).await;
+}
+
+async fn tail_tab(
+    endpoint: &str,
+    target_id: &str,
+    on_entry: impl FnMut(LogEntry),
+) -> Result<(), Box<dyn std::error::Error>> {
+    let client = CdpClient::connect_page(endpoint, target_id).await?;
+    tail(&client.page(), on_entry).await?;
+    Ok(())
+}
+
+fn print_log_entry(target_id: &str, prefix: bool, entry: &LogEntry) {
+    if report::json_mode() {
+        let mut line = serde_json::json!(entry);
+        line["target_id"] = target_id.into();
+        println!("{}", line);
+    } else if prefix {
+        println!("{} {}", target_id, entry);
+    } else {
+        println!("{}", entry);
+    }
+}
+
+// A tab that was already open gets what a new tab gets before its first navigation:
+// emulation, HAR recording, interception and storage. `!refresh` then loads the page again
+// under them.
+async fn prepare_open_tab(
+    endpoint: &str,
+    target_id: &str,
+    options: &BangOptions,
+    tab_count: usize,
+) {
+    if let Some(emulation) = options.emulation() {
+        let result = spawn_emulator(endpoint, target_id, &emulation).await;
+        report::action_value(
+            target_id,
+            "emulate",
+            &result,
+            serde_json::to_value(&emulation).ok(),
+        );
+    }
+    if options.har.is_some() {
+        let path = har_path(options, target_id, tab_count);
+        let result = spawn_har_recorder(endpoint, target_id, &path, options).await;
+        report::action_value(target_id, "har", &result, Some(path.into()));
+    }
+    if let Some(rules) = &options.intercept {
+        let result = spawn_interceptor(endpoint, target_id, rules).await;
+        report::action_value(target_id, "intercept", &result, Some(rules.as_str().into()));
+    }
+    if let Some(file) = &options.storage {
+        let result = storage_into_tab(endpoint, target_id, file).await;
+        report::action_value(target_id, "storage", &result, Some(file.as_str().into()));
+    }
+    if options.refresh {
+        log::debug!("Refreshing tab {}", target_id);
+        let result = refresh_tab(endpoint, target_id, options).await;
+        report::action(target_id, "refresh", &result);
+    }
+}
+
+// Runs `!wait=`, `!inject=`, the script, `!screenshot` and `!pdf` on a tab, in that order,
+// whether it was just opened or found. A failed wait skips the rest. Returns the first
+// failure, which fails the run once every tab has been handled.
+async fn run_tab_actions(
+    endpoint: &str,
+    target_id: &str,
+    options: &BangOptions,
+    script: Option<&TabScript>,
+    tab_count: usize,
+) -> Option<String> {
+    if options.wait.is_some() {
+        let result = wait_for_tab(endpoint, target_id, options).await;
+        report::action(target_id, "wait", &result);
+        if let Err(e) = result {
+            log::debug!("Failed to wait for the page: {}", e);
+            return Some(e.to_string());
         }
-        msg = socket.next() => msg
-    } {
-        if let Message::Text(txt) = msg {
-            println!("Script eval response: {}", txt);
-            break;
+    }
+    if let Some(source) = &options.inject {
+        let result = inject_into_tab(endpoint, target_id, source).await;
+        report::action(target_id, "inject", &result);
+    }
+    let mut failure = None;
+    if let Some(script) = script
+        && let Some(e) =
+            run_script_on_tab(endpoint, target_id, &script.source, script.timeout).await
+    {
+        failure.get_or_insert(e);
+    }
+    if options.screenshot.is_some() {
+        let path = screenshot_path(options, target_id, tab_count);
+        let result = take_screenshot(endpoint, target_id, &path, options).await;
+        report::action_value(target_id, "screenshot", &result, Some(path.into()));
+        if let Err(e) = result {
+            log::debug!("Failed to take screenshot: {}", e);
+            failure.get_or_insert(e.to_string());
+        }
+    }
+    if options.pdf.is_some() {
+        let path = pdf_path(options, target_id, tab_count);
+        let result = print_pdf(endpoint, target_id, &path, options).await;
+        report::action_value(target_id, "pdf", &result, Some(path.into()));
+        if let Err(e) = result {
+            log::debug!("Failed to print PDF: {}", e);
+            failure.get_or_insert(e.to_string());
+        }
+    }
+    failure
+}
+
+// How long a `--script` promise may take to settle when `--script-timeout` isn't given.
+const DEFAULT_SCRIPT_TIMEOUT: Duration = Duration::from_secs(300);
+
+// Runs `--script` and prints its value as one line of JSON; under `--json` the value goes
+// in the report instead. Returns the error if the script threw or could not run.
+async fn run_script_on_tab(
+    endpoint: &str,
+    target_id: &str,
+    script: &str,
+    timeout: Duration,
+) -> Option<String> {
+    let result = execute_script_on_tab(endpoint, target_id, script, timeout).await;
+    let value = result.as_ref().ok().cloned().flatten();
+    report::action_value(target_id, "script", &result, value.clone());
+    match result {
+        Ok(_) => {
+            if let Some(value) = value.filter(|_| !report::json_mode()) {
+                println!("{}", value);
+            }
+            None
+        }
+        Err(e) => {
+            log::debug!("Failed to execute script: {}", e);
+            Some(e.to_string())
         }
     }
-    Ok(())
 }
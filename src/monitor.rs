@@ -0,0 +1,265 @@
+// The machine's monitors, and where `!x`, `!y`, `!w`, `!h`, `!monitor` and `!dpi` put a
+// window on them.
+//
+// Only listing the monitors is platform code: `EnumDisplayMonitors` on Windows, RandR on X11.
+// Everything after that is plain arithmetic on `Monitor`s, the same on every platform.
+use crate::bang::{BangOptions, Dimension};
+
+/// A rectangle in desktop pixels.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Rect {
+    pub fn new(x: i32, y: i32, width: i32, height: i32) -> Self {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Monitor {
+    /// `\\.\DISPLAY1` on Windows, the RandR output name (`DP-1`) on X11.
+    pub name: String,
+    pub bounds: Rect,
+    /// Display scaling, 1.0 at 96 DPI.
+    pub scale: f32,
+    pub primary: bool,
+}
+
+/// Lists the monitors of one windowing system, in the order `!monitor=` counts them.
+pub trait MonitorBackend {
+    fn monitors(&self) -> Result<Vec<Monitor>, String>;
+}
+
+/// The backend for this platform, if there is one.
+pub fn backend() -> Option<Box<dyn MonitorBackend>> {
+    #[cfg(target_os = "windows")]
+    {
+        Some(Box::new(windows::WindowsMonitors))
+    }
+    #[cfg(all(unix, not(target_os = "macos")))]
+    {
+        Some(Box::new(x11::X11Monitors))
+    }
+    #[cfg(not(any(target_os = "windows", all(unix, not(target_os = "macos")))))]
+    {
+        None
+    }
+}
+
+/// The monitors of this machine; empty when the platform has no backend or the display can
+/// not be reached.
+pub fn monitors() -> Vec<Monitor> {
+    let Some(backend) = backend() else {
+        log::debug!("No monitor backend on this platform");
+        return Vec::new();
+    };
+    backend.monitors().unwrap_or_else(|e| {
+        log::debug!("Could not list monitors: {}", e);
+        Vec::new()
+    })
+}
+
+/// The monitor `!monitor=` picks: that index, or the first monitor when there is no index or
+/// it is out of range.
+pub fn pick(monitors: &[Monitor], index: Option<usize>) -> Option<&Monitor> {
+    index
+        .and_then(|index| monitors.get(index))
+        .or_else(|| monitors.first())
+}
+
+/// Where the bangs put a window, in desktop pixels.
+///
+/// Percentages are of the picked monitor. With a valid `!monitor` the position is relative
+/// to that monitor, and `!dpi=true` divides the result by its scaling; without one, a window
+/// is only placed when `!x`, `!y`, `!w` and `!h` are all given. Without monitors only pixel
+/// values can be placed.
+pub fn window_bounds(monitors: &[Monitor], options: &BangOptions) -> Option<Rect> {
+    let Some(screen) = pick(monitors, options.monitor) else {
+        let pixels = |value: Option<Dimension>| match value {
+            Some(Dimension::Pixels(pixels)) => Some(pixels),
+            _ => None,
+        };
+        return Some(Rect::new(
+            pixels(options.x)?,
+            pixels(options.y)?,
+            pixels(options.w)?,
+            pixels(options.h)?,
+        ));
+    };
+    let (width, height) = (screen.bounds.width, screen.bounds.height);
+    let x = options.x.map_or(0, |x| x.resolve(width));
+    let y = options.y.map_or(0, |y| y.resolve(height));
+    let w = options.w.map_or(width, |w| w.resolve(width));
+    let h = options.h.map_or(height, |h| h.resolve(height));
+    match options.monitor.and_then(|index| monitors.get(index)) {
+        Some(monitor) => {
+            let bounds = Rect::new(monitor.bounds.x + x, monitor.bounds.y + y, w, h);
+            Some(if options.dpi {
+                unscale(bounds, monitor.scale)
+            } else {
+                bounds
+            })
+        }
+        None if options.has_bounds() => Some(Rect::new(x, y, w, h)),
+        None => None,
+    }
+}
+
+/// `bounds` divided by a display scaling, for windows placed in unscaled pixels.
+pub fn unscale(bounds: Rect, scale: f32) -> Rect {
+    if scale <= 0.0 {
+        return bounds;
+    }
+    let unscaled = |value: i32| (value as f32 / scale).round() as i32;
+    Rect::new(
+        unscaled(bounds.x),
+        unscaled(bounds.y),
+        unscaled(bounds.width),
+        unscaled(bounds.height),
+    )
+}
+
+#[cfg(target_os = "windows")]
+mod windows {
+    use super::{Monitor, MonitorBackend, Rect};
+    use std::{mem, ptr};
+    use winapi::shared::minwindef::{BOOL, LPARAM, TRUE};
+    use winapi::shared::windef::{HDC, HMONITOR, LPRECT};
+    use winapi::um::shellscalingapi::{GetDpiForMonitor, MDT_EFFECTIVE_DPI};
+    use winapi::um::winuser::{
+        EnumDisplayMonitors, GetMonitorInfoW, GetSystemMetrics, MONITORINFO, MONITORINFOEXW,
+        MONITORINFOF_PRIMARY, SM_CXSCREEN, SM_CYSCREEN,
+    };
+
+    pub struct WindowsMonitors;
+
+    impl MonitorBackend for WindowsMonitors {
+        fn monitors(&self) -> Result<Vec<Monitor>, String> {
+            let mut monitors: Vec<Monitor> = Vec::new();
+            unsafe {
+                EnumDisplayMonitors(
+                    ptr::null_mut(),
+                    ptr::null_mut(),
+                    Some(collect),
+                    &mut monitors as *mut _ as LPARAM,
+                );
+            }
+            if monitors.is_empty() {
+                // Fall back to the primary screen's resolution.
+                let (width, height) =
+                    unsafe { (GetSystemMetrics(SM_CXSCREEN), GetSystemMetrics(SM_CYSCREEN)) };
+                monitors.push(Monitor {
+                    name: "primary".to_string(),
+                    bounds: Rect::new(0, 0, width, height),
+                    scale: 1.0,
+                    primary: true,
+                });
+            }
+            Ok(monitors)
+        }
+    }
+
+    unsafe extern "system" fn collect(
+        hmonitor: HMONITOR,
+        _: HDC,
+        _: LPRECT,
+        lparam: LPARAM,
+    ) -> BOOL {
+        let monitors = unsafe { &mut *(lparam as *mut Vec<Monitor>) };
+        let mut info: MONITORINFOEXW = unsafe { mem::zeroed() };
+        info.cbSize = mem::size_of::<MONITORINFOEXW>() as u32;
+        if unsafe {
+            GetMonitorInfoW(
+                hmonitor,
+                &mut info as *mut MONITORINFOEXW as *mut MONITORINFO,
+            )
+        } == 0
+        {
+            return TRUE;
+        }
+        let rect = info.rcMonitor;
+        let name_len = info
+            .szDevice
+            .iter()
+            .position(|c| *c == 0)
+            .unwrap_or(info.szDevice.len());
+        monitors.push(Monitor {
+            name: String::from_utf16_lossy(&info.szDevice[..name_len]),
+            bounds: Rect::new(
+                rect.left,
+                rect.top,
+                rect.right - rect.left,
+                rect.bottom - rect.top,
+            ),
+            scale: dpi_scale(hmonitor),
+            primary: info.dwFlags & MONITORINFOF_PRIMARY != 0,
+        });
+        TRUE // Continue enumeration
+    }
+
+    fn dpi_scale(monitor: HMONITOR) -> f32 {
+        let (mut dpi_x, mut dpi_y) = (0, 0);
+        let result =
+            unsafe { GetDpiForMonitor(monitor, MDT_EFFECTIVE_DPI, &mut dpi_x, &mut dpi_y) };
+        if result == 0 {
+            dpi_x as f32 / 96.0
+        } else {
+            1.0
+        }
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+mod x11 {
+    use super::{Monitor, MonitorBackend, Rect};
+    use x11rb::connection::Connection;
+    use x11rb::protocol::randr::ConnectionExt as _;
+    use x11rb::protocol::xproto::ConnectionExt as _;
+
+    /// RandR 1.5 monitors of the `DISPLAY` screen.
+    pub struct X11Monitors;
+
+    impl MonitorBackend for X11Monitors {
+        fn monitors(&self) -> Result<Vec<Monitor>, String> {
+            let (connection, screen) = x11rb::connect(None).map_err(|e| e.to_string())?;
+            let root = connection.setup().roots[screen].root;
+            let reply = connection
+                .randr_get_monitors(root, true)
+                .map_err(|e| e.to_string())?
+                .reply()
+                .map_err(|e| e.to_string())?;
+            let mut monitors = Vec::new();
+            for info in reply.monitors {
+                let name = connection
+                    .get_atom_name(info.name)
+                    .ok()
+                    .and_then(|cookie| cookie.reply().ok())
+                    .map(|atom| String::from_utf8_lossy(&atom.name).into_owned())
+                    .unwrap_or_default();
+                monitors.push(Monitor {
+                    name,
+                    bounds: Rect::new(
+                        info.x.into(),
+                        info.y.into(),
+                        info.width.into(),
+                        info.height.into(),
+                    ),
+                    // X11 has no per-monitor scaling.
+                    scale: 1.0,
+                    primary: info.primary,
+                });
+            }
+            Ok(monitors)
+        }
+    }
+}
@@ -0,0 +1,213 @@
+// Named, persistent Chrome user data directories (`!profile=qa`).
+//
+// Profiles live in `<data dir>/profiles/<name>` and are reused across launches, so
+// logins and extensions survive restarts. The data dir is `DEBUGCHROME_DATA_DIR`, or
+// `debugchrome` under the platform's local data dir (`%LOCALAPPDATA%`, `~/.local/share`).
+use crate::browser;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Environment variable that overrides the data directory.
+pub const DATA_DIR_ENV: &str = "DEBUGCHROME_DATA_DIR";
+
+/// Source name for `clone` meaning the installed browser's own user data.
+pub const SYSTEM_PROFILE: &str = "@system";
+
+/// Directories Chrome can rebuild; skipped when copying a profile.
+pub const CACHE_DIRS: &[&str] = &[
+    "Cache",
+    "Code Cache",
+    "GPUCache",
+    "GrShaderCache",
+    "GraphiteDawnCache",
+    "ShaderCache",
+    "DawnCache",
+    "DawnGraphiteCache",
+    "DawnWebGPUCache",
+    "CacheStorage",
+    "ScriptCache",
+    "component_crx_cache",
+    "Crashpad",
+];
+
+// Files marking a profile as in use by a running browser; never copied.
+const LOCK_FILES: &[&str] = &[
+    "SingletonLock",
+    "SingletonCookie",
+    "SingletonSocket",
+    "lockfile",
+];
+
+/// The data directory from `DEBUGCHROME_DATA_DIR`, or the platform default.
+pub fn default_data_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os(DATA_DIR_ENV).filter(|dir| !dir.is_empty()) {
+        return PathBuf::from(dir);
+    }
+    dirs::data_local_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("debugchrome")
+}
+
+/// Copies a user data directory, leaving out caches and the browser's lock files.
+pub fn copy_profile_dir(src: &Path, dst: &Path) -> io::Result<()> {
+    fs::create_dir_all(dst)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() && CACHE_DIRS.contains(&name.as_ref()) {
+            log::debug!("Skipping cache directory {}", entry.path().display());
+            continue;
+        }
+        if LOCK_FILES.contains(&name.as_ref()) {
+            continue;
+        }
+        let target = dst.join(entry.file_name());
+        if file_type.is_dir() {
+            copy_profile_dir(&entry.path(), &target)?;
+        } else if file_type.is_file()
+            && let Err(e) = fs::copy(entry.path(), &target)
+        {
+            log::debug!("Skipping {}: {}", entry.path().display(), e);
+        }
+    }
+    Ok(())
+}
+
+/// Profile names double as directory names: letters, digits, `.`, `_` and `-`.
+pub fn validate_name(name: &str) -> io::Result<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+    if valid {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "invalid profile name {:?}; use letters, digits, '.', '_' and '-'",
+                name
+            ),
+        ))
+    }
+}
+
+/// The set of named profiles under one data directory.
+#[derive(Debug, Clone)]
+pub struct ProfileStore {
+    root: PathBuf,
+}
+
+impl ProfileStore {
+    pub fn new(data_dir: impl Into<PathBuf>) -> Self {
+        ProfileStore {
+            root: data_dir.into().join("profiles"),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(default_data_dir())
+    }
+
+    /// Directory holding the profiles.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn path(&self, name: &str) -> io::Result<PathBuf> {
+        validate_name(name)?;
+        Ok(self.root.join(name))
+    }
+
+    pub fn exists(&self, name: &str) -> bool {
+        self.path(name).map(|path| path.is_dir()).unwrap_or(false)
+    }
+
+    /// Names of the existing profiles, sorted.
+    pub fn list(&self) -> io::Result<Vec<String>> {
+        let mut names = Vec::new();
+        match fs::read_dir(&self.root) {
+            Ok(entries) => {
+                for entry in entries {
+                    let entry = entry?;
+                    if entry.file_type()?.is_dir() {
+                        names.push(entry.file_name().to_string_lossy().into_owned());
+                    }
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Returns the profile's directory, creating an empty profile the first time.
+    pub fn open(&self, name: &str) -> io::Result<PathBuf> {
+        let path = self.path(name)?;
+        if !path.is_dir() {
+            log::debug!("Creating profile {} at {}", name, path.display());
+            fs::create_dir_all(&path)?;
+        }
+        Ok(path)
+    }
+
+    /// Whether a running browser holds the profile's lock.
+    pub fn is_in_use(&self, name: &str) -> bool {
+        let Ok(path) = self.path(name) else {
+            return false;
+        };
+        LOCK_FILES
+            .iter()
+            .any(|lock| fs::symlink_metadata(path.join(lock)).is_ok())
+    }
+
+    /// Copies `src` (a profile name, or `@system` for the installed browser) to a new profile `dst`.
+    pub fn clone_profile(&self, src: &str, dst: &str) -> io::Result<PathBuf> {
+        let source = if src == SYSTEM_PROFILE {
+            browser::user_data_dir().ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, "no installed Chrome profile found")
+            })?
+        } else {
+            let source = self.path(src)?;
+            if !source.is_dir() {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("profile {:?} does not exist", src),
+                ));
+            }
+            source
+        };
+        let target = self.path(dst)?;
+        if target.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("profile {:?} already exists", dst),
+            ));
+        }
+        copy_profile_dir(&source, &target)?;
+        Ok(target)
+    }
+
+    /// Deletes a profile; refuses while a browser is using it.
+    pub fn delete(&self, name: &str) -> io::Result<()> {
+        let path = self.path(name)?;
+        if !path.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("profile {:?} does not exist", name),
+            ));
+        }
+        if self.is_in_use(name) {
+            return Err(io::Error::other(format!(
+                "profile {:?} is in use by a running browser",
+                name
+            )));
+        }
+        fs::remove_dir_all(path)
+    }
+}
@@ -0,0 +1,378 @@
+// Registering `debugchrome` as the handler for `debugchrome:` URLs.
+//
+// Windows writes `HKEY_CLASSES_ROOT\debugchrome` through a `.reg` file and `regedit`,
+// or `HKEY_CURRENT_USER\Software\Classes\debugchrome` directly with `--user`, which
+// needs no elevation. Linux installs a `.desktop` entry that claims
+// `x-scheme-handler/debugchrome` and makes it the default with `xdg-mime`.
+use std::io;
+#[cfg(not(target_os = "windows"))]
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[cfg(not(target_os = "windows"))]
+const SCHEME: &str = "debugchrome";
+
+#[cfg(target_os = "windows")]
+const CLASSES_KEY: &str = r"Software\Classes\debugchrome";
+
+#[cfg(target_os = "windows")]
+pub fn register(per_user: bool) -> io::Result<()> {
+    if per_user {
+        return register_current_user();
+    }
+    use std::fs::File;
+    use std::io::Write;
+
+    let exe_path = std::env::current_exe()?
+        .to_str()
+        .unwrap()
+        .replace("\\", "\\\\");
+    let reg_content = format!(
+        "Windows Registry Editor Version 5.00\n\n\
+        [HKEY_CLASSES_ROOT\\debugchrome]\n\
+        @=\"URL:Debug Chrome Protocol\"\n\
+        \"URL Protocol\"=\"\"\n\
+        [HKEY_CLASSES_ROOT\\debugchrome\\shell\\open\\command]\n\
+        @=\"\\\"{}\\\" \\\"%1\\\"\"\n",
+        exe_path
+    );
+    let mut file = File::create("debugchrome.reg")?;
+    file.write_all(reg_content.as_bytes())?;
+    say!("Written debugchrome.reg with path: {}", exe_path);
+    if let Err(e) = Command::new("regedit")
+        .args(["/s", "debugchrome.reg"])
+        .spawn()
+        .and_then(|mut child| child.wait())
+    {
+        say!("Failed to register debugchrome protocol: {}", e);
+        say!("Press Enter to run an elevated powershell or Ctrl+C to exit.");
+        let mut input = String::new();
+        let _ = std::io::stdin().read_line(&mut input);
+        if let Err(e) = Command::new("powershell")
+            .args([
+                "-Command",
+                "Start-Process",
+                "powershell",
+                "-ArgumentList",
+                &format!("'{}'", "regedit /s debugchrome.reg"),
+                "-Verb",
+                "runAs",
+            ])
+            .spawn()
+            .and_then(|mut child| child.wait())
+        {
+            say!("Failed to elevate and register debugchrome protocol: {}", e);
+        } else {
+            say!("Registered debugchrome protocol successfully with elevation.");
+        }
+    } else {
+        say!("Registered debugchrome protocol successfully.");
+    }
+    Ok(())
+}
+
+// Per-user registration: no elevation, and it takes precedence over the machine-wide key.
+#[cfg(target_os = "windows")]
+fn register_current_user() -> io::Result<()> {
+    use winreg::RegKey;
+    use winreg::enums::HKEY_CURRENT_USER;
+
+    let exe = std::env::current_exe()?;
+    let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+    let (scheme, _) = hkcu.create_subkey(CLASSES_KEY)?;
+    scheme.set_value("", &"URL:Debug Chrome Protocol")?;
+    scheme.set_value("URL Protocol", &"")?;
+    let (command, _) = scheme.create_subkey(r"shell\open\command")?;
+    command.set_value("", &format!("\"{}\" \"%1\"", exe.display()))?;
+    say!(
+        "Registered debugchrome protocol for the current user: HKEY_CURRENT_USER\\{} -> {}",
+        CLASSES_KEY,
+        exe.display()
+    );
+    Ok(())
+}
+
+// The executable named in a `shell\open\command` value such as `"C:\x\debugchrome.exe" "%1"`.
+#[cfg(target_os = "windows")]
+fn command_exe(command: &str) -> Option<&str> {
+    let command = command.trim();
+    match command.strip_prefix('"') {
+        Some(rest) => rest.split('"').next(),
+        None => command.split_whitespace().next(),
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn same_file(a: &std::path::Path, b: &std::path::Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a
+            .to_string_lossy()
+            .eq_ignore_ascii_case(&b.to_string_lossy()),
+        _ => false,
+    }
+}
+
+#[cfg(target_os = "windows")]
+pub fn unregister() -> io::Result<()> {
+    use winreg::RegKey;
+    use winreg::enums::{HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE};
+
+    for (name, hive) in [
+        ("HKEY_CURRENT_USER", HKEY_CURRENT_USER),
+        ("HKEY_LOCAL_MACHINE", HKEY_LOCAL_MACHINE),
+    ] {
+        let root = RegKey::predef(hive);
+        if root.open_subkey(CLASSES_KEY).is_err() {
+            continue;
+        }
+        match root.delete_subkey_all(CLASSES_KEY) {
+            Ok(()) => say!("Removed {}\\{}", name, CLASSES_KEY),
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                say!("Removing {}\\{} needs elevation: {}", name, CLASSES_KEY, e);
+                if let Err(e) = Command::new("powershell")
+                    .args([
+                        "-Command",
+                        "Start-Process",
+                        "reg",
+                        "-ArgumentList",
+                        &format!("'delete HKLM\\{} /f'", CLASSES_KEY),
+                        "-Verb",
+                        "runAs",
+                    ])
+                    .spawn()
+                    .and_then(|mut child| child.wait())
+                {
+                    say!(
+                        "Failed to elevate and unregister debugchrome protocol: {}",
+                        e
+                    );
+                }
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    say!("Unregistered debugchrome protocol.");
+    Ok(())
+}
+
+// `--doctor`: checks that the registered command still launches this executable.
+#[cfg(target_os = "windows")]
+pub fn status() -> io::Result<()> {
+    use winreg::RegKey;
+    use winreg::enums::{HKEY_CLASSES_ROOT, HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE};
+
+    let exe = std::env::current_exe()?;
+    say!("This executable: {}", exe.display());
+
+    for (name, hive) in [
+        ("HKEY_CURRENT_USER", HKEY_CURRENT_USER),
+        ("HKEY_LOCAL_MACHINE", HKEY_LOCAL_MACHINE),
+    ] {
+        let command = RegKey::predef(hive)
+            .open_subkey(format!(r"{}\shell\open\command", CLASSES_KEY))
+            .and_then(|key| key.get_value::<String, _>(""));
+        match command {
+            Ok(command) => say!("{}\\{}: {}", name, CLASSES_KEY, command),
+            Err(_) => say!("{}\\{}: not registered", name, CLASSES_KEY),
+        }
+    }
+
+    // HKEY_CLASSES_ROOT is the merged view Windows actually uses.
+    let effective = RegKey::predef(HKEY_CLASSES_ROOT)
+        .open_subkey(r"debugchrome\shell\open\command")
+        .and_then(|key| key.get_value::<String, _>(""));
+    match effective {
+        Ok(command) => match command_exe(&command) {
+            Some(registered) if same_file(std::path::Path::new(registered), &exe) => {
+                say!("OK: debugchrome: URLs open this executable.");
+            }
+            Some(registered) => {
+                say!(
+                    "STALE: debugchrome: URLs open {} instead of this executable.",
+                    registered
+                );
+                say!("Run `debugchrome --register --user` (or `--register` elevated) to fix it.");
+            }
+            None => say!("BROKEN: unreadable command {:?}", command),
+        },
+        Err(_) => {
+            say!("MISSING: the debugchrome: protocol is not registered.");
+            say!("Run `debugchrome --register --user` to register it without elevation.");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "windows"))]
+const DESKTOP_FILE: &str = "debugchrome.desktop";
+
+#[cfg(not(target_os = "windows"))]
+fn mime_type() -> String {
+    format!("x-scheme-handler/{}", SCHEME)
+}
+
+// `$XDG_DATA_HOME/applications`, i.e. `~/.local/share/applications` by default.
+#[cfg(not(target_os = "windows"))]
+fn applications_dir() -> io::Result<PathBuf> {
+    dirs::data_dir()
+        .map(|dir| dir.join("applications"))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "XDG data directory not found"))
+}
+
+// Quotes an `Exec=` argument as the desktop entry spec requires.
+#[cfg(not(target_os = "windows"))]
+fn quote_exec_arg(arg: &str) -> String {
+    if !arg.contains(|c: char| " \t\n\"'\\><~|&;$*?#()`".contains(c)) {
+        return arg.to_string();
+    }
+    let mut quoted = String::from("\"");
+    for c in arg.chars() {
+        if matches!(c, '"' | '`' | '$' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(not(target_os = "windows"))]
+fn desktop_entry(exe: &Path) -> String {
+    format!(
+        "[Desktop Entry]\n\
+        Type=Application\n\
+        Name=Debug Chrome\n\
+        Comment=Open debugchrome: URLs in a remote-debugging Chrome\n\
+        Exec={} %u\n\
+        Terminal=false\n\
+        NoDisplay=true\n\
+        MimeType={};\n\
+        Categories=Development;\n",
+        quote_exec_arg(&exe.to_string_lossy()),
+        mime_type()
+    )
+}
+
+// Runs a helper tool; a missing or failing tool is reported but not fatal.
+#[cfg(not(target_os = "windows"))]
+fn run_tool(program: &str, args: &[&str]) -> bool {
+    match Command::new(program).args(args).status() {
+        Ok(status) if status.success() => true,
+        Ok(status) => {
+            say!("{} {} exited with {}", program, args.join(" "), status);
+            false
+        }
+        Err(e) => {
+            say!("Could not run {}: {}", program, e);
+            false
+        }
+    }
+}
+
+// Desktop entries are always per user, so `per_user` changes nothing here.
+#[cfg(not(target_os = "windows"))]
+pub fn register(_per_user: bool) -> io::Result<()> {
+    let exe = std::env::current_exe()?;
+    let apps = applications_dir()?;
+    std::fs::create_dir_all(&apps)?;
+    let desktop_path = apps.join(DESKTOP_FILE);
+    std::fs::write(&desktop_path, desktop_entry(&exe))?;
+    say!("Written {} for {}", desktop_path.display(), exe.display());
+    log::debug!("Written {} for {}", desktop_path.display(), exe.display());
+
+    let apps_arg = apps.to_string_lossy();
+    let defaulted = run_tool("xdg-mime", &["default", DESKTOP_FILE, &mime_type()]);
+    run_tool("update-desktop-database", &[&apps_arg]);
+    if defaulted {
+        say!("Registered debugchrome protocol successfully.");
+    } else {
+        say!(
+            "Wrote the desktop entry, but could not make it the default {} handler.",
+            mime_type()
+        );
+    }
+    Ok(())
+}
+
+// Drops `x-scheme-handler/debugchrome=` lines, the associations `xdg-mime default` writes.
+#[cfg(not(target_os = "windows"))]
+fn remove_scheme_associations(mimeapps: &str) -> String {
+    let prefix = format!("{}=", mime_type());
+    mimeapps
+        .lines()
+        .filter(|line| !line.trim_start().starts_with(&prefix))
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn unregister() -> io::Result<()> {
+    let apps = applications_dir()?;
+    let desktop_path = apps.join(DESKTOP_FILE);
+    match std::fs::remove_file(&desktop_path) {
+        Ok(()) => say!("Removed {}", desktop_path.display()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            say!("{} was not installed", desktop_path.display())
+        }
+        Err(e) => return Err(e),
+    }
+
+    if let Some(mimeapps_path) = dirs::config_dir().map(|dir| dir.join("mimeapps.list"))
+        && let Ok(contents) = std::fs::read_to_string(&mimeapps_path)
+    {
+        let cleaned = remove_scheme_associations(&contents);
+        if cleaned != contents {
+            std::fs::write(&mimeapps_path, cleaned)?;
+            say!("Removed {} from {}", mime_type(), mimeapps_path.display());
+        }
+    }
+
+    run_tool("update-desktop-database", &[&apps.to_string_lossy()]);
+    say!("Unregistered debugchrome protocol.");
+    Ok(())
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn status() -> io::Result<()> {
+    let exe = std::env::current_exe()?;
+    let desktop_path = applications_dir()?.join(DESKTOP_FILE);
+
+    let handler = Command::new("xdg-mime")
+        .args(["query", "default", &mime_type()])
+        .output()
+        .ok()
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|handler| !handler.is_empty());
+    match &handler {
+        Some(handler) => say!("{} handler: {}", mime_type(), handler),
+        None => say!("{} handler: none", mime_type()),
+    }
+
+    match std::fs::read_to_string(&desktop_path) {
+        Ok(entry) => {
+            say!("Desktop entry: {}", desktop_path.display());
+            let expected = desktop_entry(&exe);
+            let exec = |entry: &str| {
+                entry
+                    .lines()
+                    .find(|line| line.starts_with("Exec="))
+                    .map(str::to_string)
+            };
+            if exec(&entry) == exec(&expected) {
+                say!("Desktop entry points at this executable: {}", exe.display());
+            } else {
+                say!(
+                    "Desktop entry is stale: {} (this executable is {})",
+                    exec(&entry).unwrap_or_default(),
+                    exe.display()
+                );
+            }
+        }
+        Err(_) => say!("Desktop entry: not installed ({})", desktop_path.display()),
+    }
+
+    if handler.as_deref() != Some(DESKTOP_FILE) {
+        say!("Run `debugchrome --register` to make debugchrome the handler.");
+    }
+    Ok(())
+}
@@ -0,0 +1,216 @@
+// `--json` output: one document per invocation, printed when `main` finishes.
+//
+// Commands record the tabs they found and the actions they ran here. In JSON mode the
+// `say!` progress lines are suppressed, so stdout carries nothing but the document.
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fmt::Display;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex};
+
+/// Exit codes, with or without `--json`.
+pub const EXIT_OK: i32 = 0;
+pub const EXIT_ERROR: i32 = 1;
+pub const EXIT_NOT_FOUND: i32 = 2;
+pub const EXIT_CDP_UNREACHABLE: i32 = 3;
+
+/// `println!` that stays quiet under `--json` and `--script`.
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if !$crate::report::quiet() {
+            println!($($arg)*);
+        }
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    #[default]
+    Ok,
+    Found,
+    Opened,
+    NotFound,
+    CdpUnreachable,
+    Error,
+}
+
+impl Status {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Status::Ok | Status::Found | Status::Opened => EXIT_OK,
+            Status::NotFound => EXIT_NOT_FOUND,
+            Status::CdpUnreachable => EXIT_CDP_UNREACHABLE,
+            Status::Error => EXIT_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ActionReport {
+    pub action: String,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<Value>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct TabReport {
+    pub target_id: String,
+    pub title: String,
+    pub url: String,
+    pub bang_id: Option<String>,
+    pub hwnd: Option<u64>,
+    pub pid: Option<u32>,
+    pub actions: Vec<ActionReport>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    pub command: String,
+    pub status: Status,
+    pub exit_code: i32,
+    pub endpoint: Option<String>,
+    pub tabs: Vec<TabReport>,
+    pub errors: Vec<String>,
+    /// Command-specific fields, e.g. `profiles` for `--profile list`.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+static JSON: AtomicBool = AtomicBool::new(false);
+static QUIET: AtomicBool = AtomicBool::new(false);
+static REPORT: LazyLock<Mutex<Report>> = LazyLock::new(Default::default);
+
+pub fn enable_json() {
+    JSON.store(true, Ordering::Relaxed);
+}
+
+pub fn json_mode() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// Silences `say!`, so stdout carries only what a command prints on purpose, such as
+/// `--script` results.
+pub fn silence() {
+    QUIET.store(true, Ordering::Relaxed);
+}
+
+pub fn quiet() -> bool {
+    json_mode() || QUIET.load(Ordering::Relaxed)
+}
+
+fn with<R>(f: impl FnOnce(&mut Report) -> R) -> R {
+    let mut report = REPORT.lock().unwrap_or_else(|e| e.into_inner());
+    f(&mut report)
+}
+
+fn with_tab(target_id: &str, f: impl FnOnce(&mut TabReport)) {
+    with(|report| {
+        let index = match report
+            .tabs
+            .iter()
+            .position(|tab| tab.target_id == target_id)
+        {
+            Some(index) => index,
+            None => {
+                report.tabs.push(TabReport {
+                    target_id: target_id.to_string(),
+                    ..Default::default()
+                });
+                report.tabs.len() - 1
+            }
+        };
+        f(&mut report.tabs[index]);
+    });
+}
+
+pub fn command(name: &str) {
+    with(|report| report.command = name.to_string());
+}
+
+pub fn endpoint(endpoint: &str) {
+    with(|report| report.endpoint = Some(endpoint.to_string()));
+}
+
+pub fn status(status: Status) {
+    with(|report| report.status = status);
+}
+
+pub fn tab(target_id: &str, title: &str, url: &str) {
+    with_tab(target_id, |tab| {
+        tab.title = title.to_string();
+        tab.url = url.to_string();
+    });
+}
+
+pub fn bang_id(target_id: &str, bang_id: &str) {
+    if !bang_id.is_empty() {
+        with_tab(target_id, |tab| tab.bang_id = Some(bang_id.to_string()));
+    }
+}
+
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+pub fn window(target_id: &str, hwnd: u64, pid: u32) {
+    with_tab(target_id, |tab| {
+        tab.hwnd = Some(hwnd);
+        tab.pid = Some(pid);
+    });
+}
+
+/// Records the outcome of `action` on a tab.
+pub fn action<T, E: Display>(target_id: &str, action: &str, result: &Result<T, E>) {
+    action_value(target_id, action, result, None);
+}
+
+/// Like [`action`], attaching a value such as the file a screenshot was written to.
+pub fn action_value<T, E: Display>(
+    target_id: &str,
+    action: &str,
+    result: &Result<T, E>,
+    value: Option<Value>,
+) {
+    let entry = ActionReport {
+        action: action.to_string(),
+        ok: result.is_ok(),
+        error: result.as_ref().err().map(|e| e.to_string()),
+        value: value.filter(|_| result.is_ok()),
+    };
+    with_tab(target_id, |tab| tab.actions.push(entry));
+}
+
+pub fn error(message: impl Display) {
+    with(|report| report.errors.push(message.to_string()));
+}
+
+/// Adds a command-specific top-level field.
+pub fn set(key: &str, value: impl Into<Value>) {
+    with(|report| {
+        report.extra.insert(key.to_string(), value.into());
+    });
+}
+
+/// Folds `result` into the report, prints it under `--json` and returns the exit code.
+pub fn finish(result: io::Result<()>) -> i32 {
+    with(|report| {
+        if let Err(e) = &result {
+            report.errors.push(e.to_string());
+            if report.status.exit_code() == EXIT_OK {
+                report.status = Status::Error;
+            }
+        }
+        report.exit_code = report.status.exit_code();
+        if json_mode() {
+            match serde_json::to_string_pretty(&*report) {
+                Ok(json) => println!("{}", json),
+                Err(e) => eprintln!("Failed to serialize report: {}", e),
+            }
+        } else if let Err(e) = &result {
+            eprintln!("Error: {}", e);
+        }
+        report.exit_code
+    })
+}
@@ -0,0 +1,195 @@
+// Tab selectors for `--search` and `!select=`.
+//
+// `url:` and `title:` take a glob (`*` and `?`, case-insensitive) matched against the
+// whole URL or title, or a regex between slashes (`title:/^PR #\d+/`). `target:<id>` picks
+// a DevTools target id, `index:N` the N-th tab in `/json` order, and `bang:<id>` (or a
+// bare id) the tabs tagged with `!id=<id>`. Every matching tab is returned.
+use crate::cdp::{CdpClient, CdpResult, TargetInfo, list_targets};
+use futures::future::join_all;
+use regex::Regex;
+use std::str::FromStr;
+
+/// A glob or `/regex/` matched against a tab's URL or title.
+#[derive(Debug, Clone)]
+pub enum TextPattern {
+    Glob(String),
+    Regex(Regex),
+}
+
+impl TextPattern {
+    pub fn matches(&self, text: &str) -> bool {
+        match self {
+            TextPattern::Glob(glob) => glob_matches(glob, text),
+            TextPattern::Regex(regex) => regex.is_match(text),
+        }
+    }
+}
+
+impl FromStr for TextPattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix('/').and_then(|rest| rest.strip_suffix('/')) {
+            Some(body) => Regex::new(body)
+                .map(TextPattern::Regex)
+                .map_err(|e| format!("invalid regex {:?}: {}", body, e)),
+            None => Ok(TextPattern::Glob(s.to_string())),
+        }
+    }
+}
+
+/// Whole-string, case-insensitive glob match supporting `*` and `?`.
+pub fn glob_matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` and the text position it is currently standing in for.
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            // Let the last `*` swallow one more character and retry.
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Which tabs a `--search` or `!select=` applies to.
+#[derive(Debug, Clone)]
+pub enum TabSelector {
+    Url(TextPattern),
+    Title(TextPattern),
+    Target(String),
+    Index(usize),
+    Bang(String),
+}
+
+impl FromStr for TabSelector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((kind, value)) = s.split_once(':') else {
+            // A bare value is a bang id, as `--search 123` has always meant.
+            return if s.is_empty() {
+                Err("empty tab selector".to_string())
+            } else {
+                Ok(TabSelector::Bang(s.to_string()))
+            };
+        };
+        if value.is_empty() {
+            return Err(format!("tab selector {:?} needs a value", s));
+        }
+        match kind {
+            "url" => value.parse().map(TabSelector::Url),
+            "title" => value.parse().map(TabSelector::Title),
+            "target" => Ok(TabSelector::Target(value.to_string())),
+            "index" => value
+                .parse()
+                .map(TabSelector::Index)
+                .map_err(|_| format!("invalid tab index {:?}", value)),
+            "bang" => Ok(TabSelector::Bang(value.to_string())),
+            _ => Err(format!(
+                "unknown tab selector {:?}; use url:, title:, target:, index: or bang:",
+                kind
+            )),
+        }
+    }
+}
+
+impl TabSelector {
+    /// Whether the tab at `index` in the `/json` listing matches without asking the page.
+    /// For `bang:` this only checks the URL fragment; [`select_tabs`] also asks the page.
+    pub fn matches(&self, index: usize, tab: &TargetInfo) -> bool {
+        match self {
+            TabSelector::Url(pattern) => pattern.matches(&tab.url),
+            TabSelector::Title(pattern) => pattern.matches(&tab.title),
+            TabSelector::Target(id) => tab.id == *id,
+            TabSelector::Index(wanted) => index == *wanted,
+            TabSelector::Bang(id) => tab.url.contains(&format!("#{}", id)),
+        }
+    }
+}
+
+/// All page targets on `endpoint` matching `selector`, in `/json` order.
+pub async fn select_tabs(endpoint: &str, selector: &TabSelector) -> CdpResult<Vec<TargetInfo>> {
+    let tabs: Vec<TargetInfo> = list_targets(endpoint)
+        .await?
+        .into_iter()
+        .filter(|tab| tab.target_type == "page")
+        .collect();
+    let TabSelector::Bang(bang_id) = selector else {
+        return Ok(tabs
+            .into_iter()
+            .enumerate()
+            .filter(|(index, tab)| selector.matches(*index, tab))
+            .map(|(_, tab)| tab)
+            .collect());
+    };
+    log::debug!("Searching for bangId = {}", bang_id);
+    let checks = tabs.iter().enumerate().map(|(index, tab)| async move {
+        selector.matches(index, tab) || page_has_bang_id(tab, bang_id).await
+    });
+    let found = join_all(checks).await;
+    Ok(tabs
+        .into_iter()
+        .zip(found)
+        .filter(|(_, found)| *found)
+        .map(|(tab, _)| tab)
+        .collect())
+}
+
+// Asks the page for the bang id stored by `!id=` in `window.bangId` or `sessionStorage`.
+async fn page_has_bang_id(tab: &TargetInfo, bang_id: &str) -> bool {
+    if is_invalid_url(&tab.url) {
+        return false;
+    }
+    let Some(ws_url) = tab.web_socket_debugger_url.as_deref() else {
+        return false;
+    };
+    log::debug!("Searching tab: {}", tab.url);
+    let Ok(client) = CdpClient::connect(ws_url).await else {
+        return false;
+    };
+    let page = client.page();
+    for expression in ["window.bangId", "sessionStorage.getItem('bangId')"] {
+        match page.evaluate(expression, true).await {
+            Ok(result) => {
+                if result.value.as_ref().and_then(|v| v.as_str()) == Some(bang_id) {
+                    log::debug!("Found tab with bangId {}: {}", bang_id, tab.url);
+                    return true;
+                }
+            }
+            Err(e) => log::debug!("Failed to evaluate {}: {}", expression, e),
+        }
+    }
+    false
+}
+
+fn is_invalid_url(url: &str) -> bool {
+    // List of URL prefixes or patterns to exclude
+    let invalid_prefixes = [
+        "ws://",               // WebSocket URLs
+        "chrome-extension://", // Chrome extensions
+        "chrome://",           // Internal Chrome pages
+        "chrome-untrusted://", // Internal Chrome pages
+        "about:",              // About pages
+        "file://",             // Local file URLs
+        "data:",               // Data URLs
+        "javascript:",         // JavaScript URLs
+    ];
+
+    // Check if the URL starts with any of the invalid prefixes
+    invalid_prefixes
+        .iter()
+        .any(|prefix| url.starts_with(prefix))
+}
@@ -0,0 +1,184 @@
+// `--export-storage`, `--import-storage` and `!storage=`: cookies plus `localStorage` and
+// `sessionStorage` in a portable JSON file.
+//
+// Cookies go through `Network.getAllCookies` / `Network.setCookies`, which reach the whole
+// browser context from any page. Web storage belongs to an origin and is only reachable from
+// a document of that origin, so it is read from an open tab and written by a script that runs
+// in the tab's documents.
+use crate::cdp::{CdpError, CdpResult, CdpSession};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Cookies and web storage of a debug session.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StorageState {
+    pub cookies: Vec<Cookie>,
+    pub origins: Vec<OriginStorage>,
+}
+
+/// A cookie, in the fields `Network.setCookies` takes back.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    pub domain: String,
+    #[serde(default = "root_path")]
+    pub path: String,
+    /// Seconds since the Unix epoch; `None` for a session cookie.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<f64>,
+    #[serde(default)]
+    pub http_only: bool,
+    #[serde(default)]
+    pub secure: bool,
+    /// `Strict`, `Lax` or `None`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub same_site: Option<String>,
+}
+
+fn root_path() -> String {
+    "/".to_string()
+}
+
+/// The web storage of one origin, e.g. `https://app.example.com`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct OriginStorage {
+    pub origin: String,
+    pub local_storage: BTreeMap<String, String>,
+    pub session_storage: BTreeMap<String, String>,
+}
+
+#[derive(Debug)]
+pub enum StorageError {
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    Json {
+        path: PathBuf,
+        error: serde_json::Error,
+    },
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            StorageError::Json { path, error } => {
+                write!(f, "{} is not a storage file: {}", path.display(), error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+impl StorageState {
+    pub fn load(path: &Path) -> Result<Self, StorageError> {
+        let text = std::fs::read_to_string(path).map_err(|error| StorageError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        serde_json::from_str(&text).map_err(|error| StorageError::Json {
+            path: path.to_path_buf(),
+            error,
+        })
+    }
+
+    /// Writes the file pretty-printed, creating its directory.
+    pub fn save(&self, path: &Path) -> Result<(), StorageError> {
+        let io = |error| StorageError::Io {
+            path: path.to_path_buf(),
+            error,
+        };
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            std::fs::create_dir_all(parent).map_err(io)?;
+        }
+        let json = serde_json::to_vec_pretty(self).map_err(|error| StorageError::Json {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        std::fs::write(path, json).map_err(io)
+    }
+
+    pub fn origin(&self, origin: &str) -> Option<&OriginStorage> {
+        self.origins.iter().find(|stored| stored.origin == origin)
+    }
+
+    /// JavaScript that writes the stored web storage of the document's own origin, if there is
+    /// any, and evaluates to that origin or `null`.
+    pub fn seed_script(&self) -> String {
+        let origins: BTreeMap<&str, &OriginStorage> = self
+            .origins
+            .iter()
+            .map(|stored| (stored.origin.as_str(), stored))
+            .collect();
+        format!(
+            r#"(() => {{
+  const stored = {}[location.origin];
+  if (!stored) return null;
+  try {{
+    for (const [key, value] of Object.entries(stored.localStorage || {{}})) localStorage.setItem(key, value);
+    for (const [key, value] of Object.entries(stored.sessionStorage || {{}})) sessionStorage.setItem(key, value);
+  }} catch (e) {{
+    return null;
+  }}
+  return location.origin;
+}})()"#,
+            serde_json::to_string(&origins).unwrap_or_else(|_| "{}".to_string())
+        )
+    }
+}
+
+/// Every cookie of the page's browser context.
+pub async fn read_cookies(page: &CdpSession) -> CdpResult<Vec<Cookie>> {
+    let reply = page.call("Network.getAllCookies", json!({})).await?;
+    let cookies = reply
+        .get("cookies")
+        .cloned()
+        .ok_or(CdpError::MissingField("cookies"))?;
+    let mut cookies: Vec<Cookie> = serde_json::from_value(cookies)?;
+    // Session cookies come back with `expires: -1`.
+    for cookie in &mut cookies {
+        cookie.expires = cookie.expires.filter(|expires| *expires > 0.0);
+    }
+    Ok(cookies)
+}
+
+pub async fn write_cookies(page: &CdpSession, cookies: &[Cookie]) -> CdpResult<()> {
+    if cookies.is_empty() {
+        return Ok(());
+    }
+    page.call("Network.setCookies", json!({ "cookies": cookies }))
+        .await?;
+    Ok(())
+}
+
+const READ_STORAGE: &str = r#"(() => {
+  const entries = (storage) => Object.fromEntries(Object.keys(storage).map((key) => [key, storage.getItem(key)]));
+  try {
+    return { origin: location.origin, localStorage: entries(localStorage), sessionStorage: entries(sessionStorage) };
+  } catch (e) {
+    return null;
+  }
+})()"#;
+
+/// The web storage of the page's current document, or `None` when it has no usable origin
+/// (`about:blank`, `data:`, sandboxed frames).
+pub async fn read_origin(page: &CdpSession) -> CdpResult<Option<OriginStorage>> {
+    let result = page.evaluate(READ_STORAGE, true).await?;
+    let Some(value) = result.value.filter(|value| !value.is_null()) else {
+        return Ok(None);
+    };
+    let stored: OriginStorage = serde_json::from_value(value)?;
+    Ok(Some(stored).filter(|stored| stored.origin != "null"))
+}
@@ -0,0 +1,156 @@
+// `--tail` / `!tail`: a page's console, uncaught exceptions and browser log as they happen.
+//
+// `Runtime.enable` and `Log.enable` replay what the page logged before we attached, then
+// stream `Runtime.consoleAPICalled`, `Runtime.exceptionThrown` and `Log.entryAdded` until
+// the tab closes (`Inspector.detached`, or the page WebSocket going away).
+use crate::cdp::{CdpEvent, CdpResult, CdpSession, ExceptionDetails};
+use chrono::{DateTime, Local, TimeZone};
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::fmt;
+
+/// One console message, exception or log entry.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LogEntry {
+    /// When the page logged it, in milliseconds since the Unix epoch.
+    pub timestamp: f64,
+    /// `debug`, `log`, `info`, `warning` or `error`.
+    pub level: String,
+    /// `console`, `exception`, or the `Log` domain source (`network`, `violation`, ...).
+    pub source: String,
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// 1-based.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<i64>,
+}
+
+impl LogEntry {
+    /// The entry an event carries, or `None` for events that are not log output.
+    pub fn from_event(event: &CdpEvent) -> Option<LogEntry> {
+        let params = &event.params;
+        match event.method.as_str() {
+            "Runtime.consoleAPICalled" => {
+                let level = match params["type"].as_str().unwrap_or_default() {
+                    "error" | "assert" => "error",
+                    "warning" => "warning",
+                    "info" => "info",
+                    "debug" => "debug",
+                    _ => "log",
+                };
+                let text = params["args"]
+                    .as_array()
+                    .map(|args| args.iter().map(describe).collect::<Vec<_>>().join(" "))
+                    .unwrap_or_default();
+                let frame = &params["stackTrace"]["callFrames"][0];
+                Some(LogEntry {
+                    timestamp: params["timestamp"].as_f64().unwrap_or_default(),
+                    level: level.to_string(),
+                    source: "console".to_string(),
+                    text,
+                    url: frame["url"]
+                        .as_str()
+                        .filter(|url| !url.is_empty())
+                        .map(str::to_string),
+                    line: frame["lineNumber"].as_i64().map(|line| line + 1),
+                })
+            }
+            "Runtime.exceptionThrown" => {
+                let details: ExceptionDetails =
+                    serde_json::from_value(params["exceptionDetails"].clone()).ok()?;
+                let url = params["exceptionDetails"]["url"]
+                    .as_str()
+                    .filter(|url| !url.is_empty())
+                    .map(str::to_string);
+                Some(LogEntry {
+                    timestamp: params["timestamp"].as_f64().unwrap_or_default(),
+                    level: "error".to_string(),
+                    source: "exception".to_string(),
+                    text: details.message(),
+                    url,
+                    line: None,
+                })
+            }
+            "Log.entryAdded" => {
+                let entry = &params["entry"];
+                let level = match entry["level"].as_str().unwrap_or_default() {
+                    "verbose" => "debug",
+                    "" => "log",
+                    level => level,
+                };
+                Some(LogEntry {
+                    timestamp: entry["timestamp"].as_f64().unwrap_or_default(),
+                    level: level.to_string(),
+                    source: entry["source"].as_str().unwrap_or("other").to_string(),
+                    text: entry["text"].as_str().unwrap_or_default().to_string(),
+                    url: entry["url"]
+                        .as_str()
+                        .filter(|url| !url.is_empty())
+                        .map(str::to_string),
+                    line: entry["lineNumber"].as_i64().map(|line| line + 1),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// The timestamp in local time.
+    pub fn time(&self) -> DateTime<Local> {
+        Local
+            .timestamp_millis_opt(self.timestamp as i64)
+            .single()
+            .unwrap_or_else(Local::now)
+    }
+}
+
+// `12:00:01.250 ERROR exception: Uncaught TypeError: ... (line 3, column 5)`
+impl fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {:<7} {}: {}",
+            self.time().format("%H:%M:%S%.3f"),
+            self.level.to_uppercase(),
+            self.source,
+            self.text
+        )?;
+        match (&self.url, self.line) {
+            (Some(url), Some(line)) => write!(f, " ({}:{})", url, line),
+            (Some(url), None) => write!(f, " ({})", url),
+            _ => Ok(()),
+        }
+    }
+}
+
+// A console argument the way DevTools prints it: strings bare, other values as JSON,
+// objects by their description.
+fn describe(arg: &Value) -> String {
+    match arg.get("value") {
+        Some(Value::String(text)) => text.clone(),
+        Some(value) => value.to_string(),
+        None => arg["unserializableValue"]
+            .as_str()
+            .or_else(|| arg["description"].as_str())
+            .or_else(|| arg["type"].as_str())
+            .unwrap_or("undefined")
+            .to_string(),
+    }
+}
+
+/// Calls `on_entry` for every entry `page` logs, and returns once the tab has closed.
+pub async fn tail(page: &CdpSession, mut on_entry: impl FnMut(LogEntry)) -> CdpResult<()> {
+    let mut events = page.subscribe(None);
+    page.enable_runtime().await?;
+    page.call("Log.enable", json!({})).await?;
+    while let Some(event) = events.recv().await {
+        if event.method == "Inspector.detached" {
+            log::debug!("Tail ended: {}", event.params["reason"]);
+            break;
+        }
+        if let Some(entry) = LogEntry::from_event(&event) {
+            on_entry(entry);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,239 @@
+// `!wait=` conditions: when a page counts as ready for screenshots, PDFs and scripts.
+//
+// The condition is evaluated with `Runtime.evaluate` right away, again whenever a
+// `Page.lifecycleEvent` or `Network` event arrives, and at least every 250ms until the
+// timeout. The `about:blank` a new target starts on and the `!openwindow` redirect page
+// never count as ready, so waiting right after opening sees the real page. `networkidle`
+// also needs 500ms without a request in flight.
+use crate::cdp::{CdpError, CdpSession};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Value, json};
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::time::{Instant, sleep_until};
+
+/// How long `!wait=` waits when `!wait_timeout=` is not given.
+pub const DEFAULT_WAIT_TIMEOUT: Duration = Duration::from_secs(30);
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+const NETWORK_QUIET: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WaitCondition {
+    /// The `load` event has fired.
+    Load,
+    /// The document has been parsed (`DOMContentLoaded`).
+    DomContentLoaded,
+    /// Loaded, and no network request for 500ms.
+    NetworkIdle,
+    /// An element matches the CSS selector.
+    Selector(String),
+    /// The JavaScript expression is truthy.
+    Js(String),
+}
+
+impl WaitCondition {
+    /// The expression that evaluates to `true` once the condition holds.
+    pub fn expression(&self) -> String {
+        let ready = match self {
+            WaitCondition::Load | WaitCondition::NetworkIdle => {
+                "document.readyState === 'complete'".to_string()
+            }
+            WaitCondition::DomContentLoaded => "document.readyState !== 'loading'".to_string(),
+            WaitCondition::Selector(css) => format!(
+                "document.querySelector({}) !== null",
+                serde_json::to_string(css).unwrap_or_default()
+            ),
+            // On lines of its own so a trailing `//` comment can't swallow the parenthesis.
+            WaitCondition::Js(expression) => format!("Boolean((\n{}\n))", expression),
+        };
+        format!(
+            "(() => {{ if (location.href === 'about:blank' || \
+             document.querySelector('meta[name=\"debugchrome-redirect\"]')) return false; \
+             return {}; }})()",
+            ready
+        )
+    }
+}
+
+impl FromStr for WaitCondition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let with_value = |value: &str, make: fn(String) -> WaitCondition| {
+            if value.is_empty() {
+                Err(format!("wait condition {:?} needs a value", s))
+            } else {
+                Ok(make(value.to_string()))
+            }
+        };
+        if let Some(css) = s.strip_prefix("selector:") {
+            return with_value(css, WaitCondition::Selector);
+        }
+        if let Some(expression) = s.strip_prefix("js:") {
+            return with_value(expression, WaitCondition::Js);
+        }
+        match s.to_ascii_lowercase().as_str() {
+            "load" => Ok(WaitCondition::Load),
+            "domcontentloaded" => Ok(WaitCondition::DomContentLoaded),
+            "networkidle" => Ok(WaitCondition::NetworkIdle),
+            _ => Err(format!(
+                "unknown wait condition {:?}; use load, domcontentloaded, networkidle, \
+                 selector:<css> or js:<expr>",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for WaitCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WaitCondition::Load => write!(f, "load"),
+            WaitCondition::DomContentLoaded => write!(f, "domcontentloaded"),
+            WaitCondition::NetworkIdle => write!(f, "networkidle"),
+            WaitCondition::Selector(css) => write!(f, "selector:{}", css),
+            WaitCondition::Js(expression) => write!(f, "js:{}", expression),
+        }
+    }
+}
+
+impl Serialize for WaitCondition {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for WaitCondition {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Debug)]
+pub enum WaitError {
+    Cdp(CdpError),
+    Timeout {
+        condition: WaitCondition,
+        after: Duration,
+        /// The last error the condition's expression threw, if any.
+        last_error: Option<String>,
+    },
+}
+
+impl fmt::Display for WaitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WaitError::Cdp(e) => write!(f, "{}", e),
+            WaitError::Timeout {
+                condition,
+                after,
+                last_error,
+            } => {
+                write!(
+                    f,
+                    "timed out after {:?} waiting for !wait={}",
+                    after, condition
+                )?;
+                if let Some(error) = last_error {
+                    write!(f, " (last error: {})", error)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for WaitError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WaitError::Cdp(e) => Some(e),
+            WaitError::Timeout { .. } => None,
+        }
+    }
+}
+
+impl From<CdpError> for WaitError {
+    fn from(e: CdpError) -> Self {
+        WaitError::Cdp(e)
+    }
+}
+
+/// Waits until `condition` holds on `page`, or fails after `timeout`.
+pub async fn wait_for(
+    page: &CdpSession,
+    condition: &WaitCondition,
+    timeout: Duration,
+) -> Result<(), WaitError> {
+    let deadline = Instant::now() + timeout;
+    let mut events = page.subscribe(None);
+    page.enable_page().await?;
+    page.call("Page.setLifecycleEventsEnabled", json!({ "enabled": true }))
+        .await?;
+    let track_network = *condition == WaitCondition::NetworkIdle;
+    if track_network {
+        page.call("Network.enable", json!({})).await?;
+    }
+    let expression = condition.expression();
+    let mut in_flight = HashSet::new();
+    let mut last_activity = Instant::now();
+    let mut last_error = None;
+    loop {
+        match page.evaluate(&expression, true).await {
+            Ok(result) if result.exception.is_some() => {
+                last_error = result
+                    .description
+                    .or_else(|| result.exception.map(|e| e.text));
+            }
+            Ok(result) => {
+                let quiet = !track_network
+                    || (in_flight.is_empty() && last_activity.elapsed() >= NETWORK_QUIET);
+                if result.value == Some(Value::Bool(true)) && quiet {
+                    log::debug!("!wait={} satisfied", condition);
+                    return Ok(());
+                }
+            }
+            // Evaluating while the page navigates fails; the next event or tick retries.
+            Err(e) => {
+                log::debug!("!wait={}: {}", condition, e);
+                last_error = Some(e.to_string());
+            }
+        }
+        if Instant::now() >= deadline {
+            return Err(WaitError::Timeout {
+                condition: condition.clone(),
+                after: timeout,
+                last_error,
+            });
+        }
+        tokio::select! {
+            event = events.recv() => {
+                let Some(event) = event else {
+                    return Err(CdpError::Closed.into());
+                };
+                let request_id = event.params["requestId"].as_str().map(str::to_string);
+                match (event.method.as_str(), request_id) {
+                    ("Network.requestWillBeSent", Some(id)) => {
+                        in_flight.insert(id);
+                        last_activity = Instant::now();
+                    }
+                    ("Network.loadingFinished" | "Network.loadingFailed", Some(id)) => {
+                        in_flight.remove(&id);
+                        last_activity = Instant::now();
+                    }
+                    // Chrome's own idle signal also covers requests sent before
+                    // `Network.enable`, whose end we would never see.
+                    ("Page.lifecycleEvent", _) if event.params["name"] == "networkIdle" => {
+                        in_flight.clear();
+                    }
+                    _ => {}
+                }
+            }
+            _ = sleep_until((Instant::now() + POLL_INTERVAL).min(deadline)) => {}
+        }
+    }
+}
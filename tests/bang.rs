@@ -0,0 +1,245 @@
+use debugchrome::ImageFormat;
+use debugchrome::bang::{
+    BANGS, BangError, BangOptions, BangUrl, BangValue, Dimension, Margins, PaperSize,
+};
+
+fn parse(raw: &str) -> BangUrl {
+    BangUrl::parse(raw).unwrap_or_else(|e| panic!("{}: {}", raw, e))
+}
+
+#[test]
+fn bangs_are_taken_out_of_the_query_and_the_rest_is_kept() {
+    let parsed = parse("https://example.com/search?q=a!b&page=2&!id=7&!close#results");
+    assert_eq!(
+        parsed.url,
+        "https://example.com/search?q=a!b&page=2#results"
+    );
+    assert_eq!(parsed.options.id.as_deref(), Some("7"));
+    assert!(parsed.options.close);
+
+    let parsed = parse("https://example.com/?!id=7");
+    assert_eq!(parsed.url, "https://example.com/");
+}
+
+#[test]
+fn urls_without_bangs_pass_through_exactly() {
+    for raw in [
+        "https://example.com/wow!/page?x=1&&y#frag!ment",
+        "https://example.com/?q=%21id",
+        "https://example.com/#/route?!id=1",
+    ] {
+        let parsed = parse(raw);
+        assert_eq!(parsed.url, raw);
+        assert_eq!(parsed.options, BangOptions::default(), "{}", raw);
+    }
+}
+
+#[test]
+fn values_are_percent_decoded() {
+    let parsed = parse("https://github.com/?!select=title:/%5EPR%20%23%5Cd+/&!id=a%26b");
+    assert_eq!(parsed.options.select.as_deref(), Some(r"title:/^PR #\d+/"));
+    assert_eq!(parsed.options.id.as_deref(), Some("a&b"));
+}
+
+#[test]
+fn bangs_straight_after_the_address_still_work() {
+    let parsed = parse("https://www.rust-lang.org!id=1&!close&!keep_focus");
+    assert_eq!(parsed.url, "https://www.rust-lang.org");
+    assert_eq!(parsed.options.id.as_deref(), Some("1"));
+    assert!(parsed.options.close && parsed.options.keep_focus);
+}
+
+#[test]
+fn unknown_and_malformed_bangs_are_rejected() {
+    let err = |raw: &str| BangUrl::parse(raw).unwrap_err();
+    assert_eq!(err("https://a/?!nope"), BangError::Unknown("nope".into()));
+    assert_eq!(err("https://a/?!=1"), BangError::MissingName("!=1".into()));
+    assert_eq!(
+        err("https://a/?!id=1&!id=2"),
+        BangError::Duplicate("id".into())
+    );
+    assert!(matches!(
+        err("https://a/?!timeout=abc"),
+        BangError::InvalidValue { ref name, .. } if name == "timeout"
+    ));
+    assert!(matches!(
+        err("https://a/?!close=yes"),
+        BangError::InvalidValue { .. }
+    ));
+    assert!(matches!(
+        err("https://a/?!id=%FF"),
+        BangError::Encoding { .. }
+    ));
+    for raw in [
+        "https://a/?!w=50%25",
+        "https://a/?!w=800&!h=12.5%25",
+        "https://a/?!port=9223",
+        "https://a/?!dpi=TRUE",
+        "https://a/?!select=index:0",
+    ] {
+        parse(raw);
+    }
+    for raw in [
+        "https://a/?!w=wide",
+        "https://a/?!port=70000",
+        "https://a/?!dpi=yes",
+        "https://a/?!select=name:x",
+    ] {
+        assert!(BangUrl::parse(raw).is_err(), "{}", raw);
+    }
+    assert!(err("https://a/?!nope").to_string().contains("!screenshot"));
+}
+
+#[test]
+fn url_flags_and_json_give_the_same_options() {
+    let from_url =
+        parse("https://a/?!id=7&!w=50%25&!h=600&!timeout=10&!keep_focus&!dpi=true").options;
+    assert_eq!(from_url.w, Some(Dimension::Percent(50.0)));
+    assert_eq!(from_url.h, Some(Dimension::Pixels(600)));
+    assert_eq!(from_url.timeout, Some(10));
+    assert!(from_url.dpi && from_url.keep_focus);
+
+    let mut from_args = BangOptions::default();
+    let args: Vec<String> = [
+        "--id",
+        "7",
+        "--w",
+        "50%",
+        "--h",
+        "600",
+        "--timeout",
+        "10",
+        "--keep-focus",
+        "--dpi",
+        "true",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect();
+    from_args.apply_args(&args).unwrap();
+    assert_eq!(from_args, from_url);
+
+    let mut round_trip = BangOptions::default();
+    round_trip.apply_args(&from_url.to_args()).unwrap();
+    assert_eq!(round_trip, from_url);
+
+    let from_json: BangUrl = serde_json::from_str(
+        r#"{"url": "https://a/", "options": {"id": "7", "w": "50%", "h": 600, "timeout": 10, "keep_focus": true, "dpi": true}}"#,
+    )
+    .unwrap();
+    assert_eq!(from_json.options, from_url);
+    assert!(
+        serde_json::from_str::<BangUrl>(r#"{"url": "https://a/", "options": {"nope": 1}}"#)
+            .is_err()
+    );
+    assert!(
+        serde_json::from_str::<BangUrl>(r#"{"url": "https://a/", "options": {"w": "wide"}}"#)
+            .is_err()
+    );
+}
+
+#[test]
+fn every_bang_can_be_set() {
+    for (name, kind) in BANGS {
+        let value = match kind {
+            BangValue::Flag | BangValue::FlagOrText => "",
+            BangValue::Text => "x",
+            BangValue::Dimension => "10%",
+            BangValue::Count | BangValue::Port => "1",
+            BangValue::Bool => "true",
+            BangValue::Selector => "index:0",
+            BangValue::ImageFormat => "webp",
+            BangValue::Quality => "80",
+            BangValue::Paper => "a4",
+            BangValue::Margins => "0.5",
+            BangValue::Wait => "networkidle",
+            BangValue::Device => "iphone-14",
+            BangValue::Geo => "52.52,13.405",
+            BangValue::Throttle => "3g",
+            BangValue::ColorScheme => "dark",
+            BangValue::WindowState => "maximized",
+        };
+        let mut options = BangOptions::default();
+        options
+            .set(name, value)
+            .unwrap_or_else(|e| panic!("{}: {}", name, e));
+        assert_ne!(options, BangOptions::default(), "!{} changed nothing", name);
+    }
+    let mut options = BangOptions::default();
+    assert_eq!(
+        options.apply_args(&["--timeout".to_string()]),
+        Err(BangError::MissingValue("timeout".into()))
+    );
+}
+
+#[test]
+fn screenshot_takes_an_optional_path_and_capture_options() {
+    let options =
+        parse("https://a/?!screenshot=docs/home.jpg&!quality=80&!fullpage&!noview").options;
+    assert_eq!(options.screenshot.as_deref(), Some("docs/home.jpg"));
+    assert_eq!(options.quality, Some(80));
+    assert!(options.fullpage && options.noview);
+    assert_eq!(
+        parse("https://a/?!screenshot&!format=webp&!clip=%23main")
+            .options
+            .clip
+            .as_deref(),
+        Some("#main")
+    );
+    for raw in ["https://a/?!quality=101", "https://a/?!format=gif"] {
+        assert!(BangUrl::parse(raw).is_err(), "{}", raw);
+    }
+
+    let mut from_args = BangOptions::default();
+    from_args
+        .apply_args(&["--screenshot".to_string(), "--close".to_string()])
+        .unwrap();
+    assert_eq!(from_args.screenshot.as_deref(), Some(""));
+    assert!(from_args.close);
+
+    let mut round_trip = BangOptions::default();
+    round_trip.apply_args(&options.to_args()).unwrap();
+    assert_eq!(round_trip, options);
+
+    let from_json: BangOptions =
+        serde_json::from_str(r#"{"screenshot": true, "format": "jpeg"}"#).unwrap();
+    assert_eq!(from_json.screenshot.as_deref(), Some(""));
+    assert_eq!(from_json.format, Some(ImageFormat::Jpeg));
+    assert_eq!(
+        serde_json::to_value(&from_json).unwrap(),
+        serde_json::json!({"screenshot": true, "format": "jpeg"})
+    );
+}
+
+#[test]
+fn paper_sizes_and_margins() {
+    let a4: PaperSize = "A4".parse().unwrap();
+    assert_eq!((a4.width, a4.height), (8.27, 11.69));
+    assert_eq!(a4.to_string(), "a4");
+    let custom: PaperSize = "6x9".parse().unwrap();
+    assert_eq!((custom.width, custom.height), (6.0, 9.0));
+    assert_eq!(custom.to_string(), "6x9");
+    for bad in ["b5", "6x", "0x9", "-1x9"] {
+        assert!(bad.parse::<PaperSize>().is_err(), "{}", bad);
+    }
+
+    let margins = |s: &str| {
+        let m: Margins = s.parse().unwrap();
+        (m.top, m.right, m.bottom, m.left)
+    };
+    assert_eq!(margins("0.5"), (0.5, 0.5, 0.5, 0.5));
+    assert_eq!(margins("0.5,0.25"), (0.5, 0.25, 0.5, 0.25));
+    assert_eq!(margins("1,0.25,0.5"), (1.0, 0.25, 0.5, 0.25));
+    assert_eq!(margins("1,2,3,4"), (1.0, 2.0, 3.0, 4.0));
+    assert!("1,2,3,4,5".parse::<Margins>().is_err());
+
+    let options =
+        parse("https://a/?!pdf=out/report.pdf&!paper=letter&!margin=0.5,0.25&!landscape").options;
+    let json = serde_json::to_value(&options).unwrap();
+    assert_eq!(json["paper"], "letter");
+    assert_eq!(json["margin"], "0.5,0.25,0.5,0.25");
+    assert_eq!(
+        serde_json::from_value::<BangOptions>(json).unwrap(),
+        options
+    );
+}
@@ -0,0 +1,9 @@
+use debugchrome::browser;
+
+#[test]
+fn debug_args_enable_remote_debugging_on_port() {
+    let args = browser::debug_args(9333, std::path::Path::new("/tmp/profile"));
+
+    assert!(args.contains(&"--remote-debugging-port=9333".to_string()));
+    assert!(args.contains(&"--user-data-dir=/tmp/profile".to_string()));
+}
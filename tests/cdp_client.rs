@@ -0,0 +1,209 @@
+mod support;
+
+use debugchrome::cdp::list_targets;
+use debugchrome::{CdpClient, CdpError, CreateTarget};
+use serde_json::json;
+use std::time::Duration;
+use support::{MockCdp, Reply, SCREENSHOT_BYTES};
+
+#[tokio::test]
+async fn lists_targets_from_json_endpoint() {
+    let mock = MockCdp::start().await;
+    let id = mock.add_page("https://example.com/", "Example");
+
+    let targets = list_targets(&mock.endpoint()).await.unwrap();
+
+    assert_eq!(targets.len(), 1);
+    assert_eq!(targets[0].id, id);
+    assert_eq!(targets[0].title, "Example");
+    assert_eq!(targets[0].target_type, "page");
+    assert!(
+        targets[0]
+            .web_socket_debugger_url
+            .as_deref()
+            .unwrap()
+            .ends_with(&format!("/devtools/page/{}", id))
+    );
+}
+
+#[tokio::test]
+async fn creates_and_closes_targets_over_browser_socket() {
+    let mock = MockCdp::start().await;
+    let browser = CdpClient::connect_browser(&mock.endpoint()).await.unwrap();
+
+    let context_id = browser.create_browser_context().await.unwrap();
+    let target_id = browser
+        .create_target(&CreateTarget {
+            url: "https://example.com/".into(),
+            browser_context_id: Some(context_id.clone()),
+            new_window: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let created = &mock.calls("Target.createTarget")[0];
+    assert_eq!(created.params["newWindow"], json!(true));
+    assert_eq!(created.params["browserContextId"], json!(context_id));
+    assert!(created.params.get("left").is_none());
+
+    browser.close_target(&target_id).await.unwrap();
+    assert!(mock.targets().is_empty());
+}
+
+#[tokio::test]
+async fn concurrent_calls_receive_their_own_replies() {
+    let mock = MockCdp::start().await;
+    let first = mock.add_page("https://one.example/", "One");
+    let second = mock.add_page("https://two.example/", "Two");
+    let browser = CdpClient::connect_browser(&mock.endpoint()).await.unwrap();
+    let one = browser.attach(&first).await.unwrap();
+    let two = browser.attach(&second).await.unwrap();
+
+    let calls = (0..20).map(|i| {
+        let session = if i % 2 == 0 { one.clone() } else { two.clone() };
+        async move {
+            let title = session.evaluate("document.title", true).await.unwrap();
+            (i, title.value.unwrap())
+        }
+    });
+    for (i, title) in futures::future::join_all(calls).await {
+        assert_eq!(
+            title,
+            if i % 2 == 0 {
+                json!("One")
+            } else {
+                json!("Two")
+            }
+        );
+    }
+}
+
+#[tokio::test]
+async fn protocol_errors_carry_method_and_code() {
+    let mock = MockCdp::start().await;
+    mock.respond(
+        "Target.activateTarget",
+        Reply::Error(-32000, "No target with given id found".into()),
+    );
+    let browser = CdpClient::connect_browser(&mock.endpoint()).await.unwrap();
+
+    match browser.activate_target("missing").await {
+        Err(CdpError::Protocol {
+            method,
+            code,
+            message,
+        }) => {
+            assert_eq!(method, "Target.activateTarget");
+            assert_eq!(code, -32000);
+            assert_eq!(message, "No target with given id found");
+        }
+        other => panic!("expected a protocol error, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn unanswered_call_times_out_without_poisoning_the_socket() {
+    let mock = MockCdp::start().await;
+    let id = mock.add_page("https://example.com/", "Example");
+    mock.respond("Page.reload", Reply::Silent);
+    let client = CdpClient::connect_page(&mock.endpoint(), &id)
+        .await
+        .unwrap()
+        .with_timeout(Duration::from_millis(200));
+    let page = client.page();
+
+    assert!(matches!(
+        page.reload(Duration::from_secs(5)).await,
+        Err(CdpError::Timeout(_))
+    ));
+    let title = page.evaluate("document.title", true).await.unwrap();
+    assert_eq!(title.value, Some(json!("Example")));
+}
+
+#[tokio::test]
+async fn a_closed_socket_ends_every_subscription_and_call() {
+    let mock = MockCdp::start().await;
+    let id = mock.add_page("https://example.com/", "Example");
+    mock.respond("Page.close", Reply::Hangup);
+    let client = CdpClient::connect_page(&mock.endpoint(), &id)
+        .await
+        .unwrap();
+    let mut before = client.subscribe(None, None);
+
+    assert!(matches!(
+        client.call("Page.close", json!({})).await,
+        Err(CdpError::Closed)
+    ));
+    let mut after = client.subscribe(None, None);
+
+    for events in [&mut before, &mut after] {
+        let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .expect("the subscription outlived its socket");
+        assert!(event.is_none());
+    }
+    assert!(client.is_closed());
+    assert!(matches!(
+        client.call("Page.reload", json!({})).await,
+        Err(CdpError::Closed)
+    ));
+}
+
+#[tokio::test]
+async fn events_are_routed_by_method_and_session() {
+    let mock = MockCdp::start().await;
+    let client = CdpClient::connect_browser(&mock.endpoint()).await.unwrap();
+    let mut targets = client.subscribe(Some("Target."), None);
+    let mut loads = client.subscribe(Some("Page.loadEventFired"), Some("S1"));
+    // A round trip guarantees the mock has registered this socket for events.
+    client.call("Browser.getVersion", json!({})).await.unwrap();
+
+    mock.emit("Page.loadEventFired", json!({ "timestamp": 1 }), Some("S2"));
+    mock.emit("Target.targetCreated", json!({ "targetInfo": {} }), None);
+    mock.emit("Page.loadEventFired", json!({ "timestamp": 2 }), Some("S1"));
+
+    let created = targets.recv().await.unwrap();
+    assert_eq!(created.method, "Target.targetCreated");
+    let loaded = loads.recv().await.unwrap();
+    assert_eq!(loaded.session_id.as_deref(), Some("S1"));
+    assert_eq!(loaded.params["timestamp"], json!(2));
+    assert!(targets.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn evaluate_reports_exceptions() {
+    let mock = MockCdp::start().await;
+    let id = mock.add_page("https://example.com/", "Example");
+    mock.evaluate_to(
+        "boom()",
+        json!({
+            "result": { "type": "object", "subtype": "error", "description": "ReferenceError: boom is not defined" },
+            "exceptionDetails": { "text": "Uncaught", "lineNumber": 0, "columnNumber": 0 }
+        }),
+    );
+    let client = CdpClient::connect_page(&mock.endpoint(), &id)
+        .await
+        .unwrap();
+
+    let result = client.page().evaluate("boom()", true).await.unwrap();
+
+    assert_eq!(result.exception.unwrap().text, "Uncaught");
+    assert_eq!(
+        result.description.as_deref(),
+        Some("ReferenceError: boom is not defined")
+    );
+}
+
+#[tokio::test]
+async fn captures_screenshot_bytes() {
+    let mock = MockCdp::start().await;
+    let id = mock.add_page("https://example.com/", "Example");
+    let client = CdpClient::connect_page(&mock.endpoint(), &id)
+        .await
+        .unwrap();
+
+    let bytes = client.page().capture_screenshot().await.unwrap();
+
+    assert_eq!(bytes, SCREENSHOT_BYTES);
+}
@@ -0,0 +1,117 @@
+mod support;
+
+use debugchrome::CdpClient;
+use debugchrome::bang::{BangError, BangUrl};
+use debugchrome::emulate::{ColorScheme, Emulation, Throttle};
+use serde_json::json;
+use support::{MockCdp, Reply};
+
+#[test]
+fn emulation_bangs_parse_into_one_emulation() {
+    let parsed = BangUrl::parse(
+        "https://example.com/?!device=Pixel-7&!geo=52.52,13.405&!tz=Europe/Berlin&!locale=de-DE&!throttle=3g&!colorscheme=dark",
+    )
+    .unwrap();
+    assert_eq!(parsed.url, "https://example.com/");
+    let emulation = parsed.options.emulation().unwrap();
+    let device = emulation.device.unwrap();
+    assert_eq!(
+        (device.name, device.width, device.height),
+        ("pixel-7", 412, 915)
+    );
+    assert_eq!(device.device_scale_factor, 2.625);
+    let geo = emulation.geolocation.unwrap();
+    assert_eq!(
+        (geo.latitude, geo.longitude, geo.accuracy),
+        (52.52, 13.405, 100.0)
+    );
+    assert_eq!(emulation.timezone.as_deref(), Some("Europe/Berlin"));
+    assert_eq!(
+        emulation.throttle,
+        Some(Throttle {
+            latency_ms: 562.5,
+            download_kbps: 1440.0,
+            upload_kbps: 675.0,
+        })
+    );
+    assert_eq!(emulation.color_scheme, Some(ColorScheme::Dark));
+    assert!(!emulation.offline);
+
+    // The helper gets the emulation as JSON; it has to come back unchanged.
+    let json = serde_json::to_string(&emulation).unwrap();
+    assert_eq!(serde_json::from_str::<Emulation>(&json).unwrap(), emulation);
+
+    let custom: Throttle = "1600/750/150".parse().unwrap();
+    assert_eq!(custom.to_string(), "1600/750/150");
+    assert!(
+        BangUrl::parse("https://example.com/")
+            .unwrap()
+            .options
+            .emulation()
+            .is_none()
+    );
+
+    for (bang, value) in [
+        ("device", "nokia-3310"),
+        ("geo", "91,0"),
+        ("geo", "1"),
+        ("throttle", "5g"),
+        ("colorscheme", "blue"),
+    ] {
+        let error =
+            BangUrl::parse(&format!("https://example.com/?!{}={}", bang, value)).expect_err(value);
+        assert!(
+            matches!(&error, BangError::InvalidValue { name, .. } if name == bang),
+            "{:?}",
+            error
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn apply_sends_the_overrides() {
+    let mock = MockCdp::start().await;
+    let id = mock.add_page("about:blank", "");
+    mock.respond(
+        "Browser.getVersion",
+        Reply::Result(json!({ "userAgent": "Mozilla/5.0 HeadlessChrome/124" })),
+    );
+    let client = CdpClient::connect_page(&mock.endpoint(), &id)
+        .await
+        .unwrap();
+    let emulation = Emulation {
+        locale: Some("fr-FR".to_string()),
+        timezone: Some("Asia/Tokyo".to_string()),
+        geolocation: Some("35.68,139.69,10".parse().unwrap()),
+        offline: true,
+        color_scheme: Some(ColorScheme::Light),
+        ..Emulation::default()
+    };
+    emulation.apply(&client.page()).await.unwrap();
+
+    // No device or `!ua=`: the browser's own user agent carries the locale.
+    let user_agent = &mock.calls("Emulation.setUserAgentOverride")[0].params;
+    assert_eq!(user_agent["userAgent"], "Mozilla/5.0 HeadlessChrome/124");
+    assert_eq!(user_agent["acceptLanguage"], "fr-FR");
+    assert_eq!(
+        mock.calls("Emulation.setLocaleOverride")[0].params["locale"],
+        "fr-FR"
+    );
+    assert_eq!(
+        mock.calls("Emulation.setTimezoneOverride")[0].params["timezoneId"],
+        "Asia/Tokyo"
+    );
+    assert_eq!(
+        mock.calls("Emulation.setGeolocationOverride")[0].params,
+        json!({ "latitude": 35.68, "longitude": 139.69, "accuracy": 10.0 })
+    );
+    assert_eq!(
+        mock.calls("Emulation.setEmulatedMedia")[0].params["features"],
+        json!([{ "name": "prefers-color-scheme", "value": "light" }])
+    );
+    assert_eq!(
+        mock.calls("Network.emulateNetworkConditions")[0].params,
+        json!({ "offline": true, "latency": 0.0, "downloadThroughput": -1.0, "uploadThroughput": -1.0 })
+    );
+    assert!(mock.calls("Emulation.setDeviceMetricsOverride").is_empty());
+}
@@ -0,0 +1,135 @@
+use debugchrome::CdpEvent;
+use debugchrome::har::HarRecorder;
+use serde_json::{Value, json};
+
+fn record(recorder: &mut HarRecorder, method: &str, params: Value) {
+    recorder.record(&CdpEvent {
+        method: method.to_string(),
+        params,
+        session_id: None,
+    });
+}
+
+fn request(id: &str, url: &str, timestamp: f64) -> Value {
+    json!({
+        "requestId": id,
+        "type": "Document",
+        "timestamp": timestamp,
+        "wallTime": 1_700_000_000.0 + timestamp,
+        "request": { "url": url, "method": "GET", "headers": { "Accept": "text/html" } },
+    })
+}
+
+#[test]
+fn requests_redirects_and_failures_become_har_entries() {
+    let mut recorder = HarRecorder::new("bang-7");
+    record(
+        &mut recorder,
+        "Network.requestWillBeSent",
+        request("1", "http://a.test/", 10.0),
+    );
+    let mut redirected = request("1", "https://a.test/?q=1&lang=en", 10.05);
+    redirected["redirectResponse"] = json!({
+        "status": 301,
+        "statusText": "Moved Permanently",
+        "headers": { "Location": "https://a.test/?q=1&lang=en" },
+        "protocol": "http/1.1",
+        "mimeType": "text/html",
+    });
+    record(&mut recorder, "Network.requestWillBeSent", redirected);
+    record(
+        &mut recorder,
+        "Network.responseReceived",
+        json!({
+            "requestId": "1",
+            "timestamp": 10.2,
+            "response": {
+                "status": 200,
+                "statusText": "OK",
+                "protocol": "h2",
+                "mimeType": "text/html",
+                "remoteIPAddress": "[2001:db8::1]",
+                "encodedDataLength": 120,
+                "headers": { "Set-Cookie": "a=1; Path=/\nb=2", "Content-Type": "text/html" },
+                "timing": {
+                    "requestTime": 10.1,
+                    "dnsStart": 1.0, "dnsEnd": 11.0,
+                    "connectStart": 11.0, "connectEnd": 41.0,
+                    "sslStart": 21.0, "sslEnd": 41.0,
+                    "sendStart": 41.0, "sendEnd": 42.0,
+                    "receiveHeadersEnd": 92.0,
+                },
+            },
+        }),
+    );
+    record(
+        &mut recorder,
+        "Network.dataReceived",
+        json!({ "requestId": "1", "dataLength": 5000, "encodedDataLength": 0 }),
+    );
+    record(
+        &mut recorder,
+        "Network.loadingFinished",
+        json!({ "requestId": "1", "timestamp": 10.3, "encodedDataLength": 1620 }),
+    );
+    record(
+        &mut recorder,
+        "Network.requestWillBeSent",
+        request("2", "https://a.test/app.js", 10.4),
+    );
+    record(
+        &mut recorder,
+        "Network.loadingFailed",
+        json!({ "requestId": "2", "timestamp": 10.5, "errorText": "net::ERR_BLOCKED_BY_CLIENT" }),
+    );
+    record(
+        &mut recorder,
+        "Page.loadEventFired",
+        json!({ "timestamp": 11.0 }),
+    );
+
+    let har = serde_json::to_value(recorder.to_har()).unwrap();
+    let log = &har["log"];
+    assert_eq!(log["version"], "1.2");
+    assert_eq!(log["pages"][0]["id"], "bang-7");
+    assert_eq!(
+        log["pages"][0]["startedDateTime"],
+        "2023-11-14T22:13:30.000Z"
+    );
+    assert_eq!(log["pages"][0]["pageTimings"]["onLoad"], 1000.0);
+    assert_eq!(log["pages"][0]["pageTimings"]["onContentLoad"], -1.0);
+
+    let entries = log["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 3);
+    assert_eq!(entries[0]["response"]["status"], 301);
+    assert_eq!(
+        entries[0]["response"]["redirectURL"],
+        "https://a.test/?q=1&lang=en"
+    );
+
+    let page = &entries[1];
+    assert_eq!(page["pageref"], "bang-7");
+    assert_eq!(page["request"]["httpVersion"], "HTTP/2.0");
+    assert_eq!(
+        page["request"]["queryString"],
+        json!([{ "name": "q", "value": "1" }, { "name": "lang", "value": "en" }])
+    );
+    assert_eq!(
+        page["response"]["cookies"][1],
+        json!({ "name": "b", "value": "2" })
+    );
+    assert_eq!(page["response"]["content"]["size"], 5000);
+    assert_eq!(page["response"]["bodySize"], 1500);
+    assert_eq!(page["serverIPAddress"], "2001:db8::1");
+    let timings = &page["timings"];
+    assert_eq!(timings["dns"], 10.0);
+    assert_eq!(timings["connect"], 30.0);
+    assert_eq!(timings["ssl"], 20.0);
+    assert_eq!(timings["wait"], 50.0);
+    let time = page["time"].as_f64().unwrap();
+    assert!((time - 250.0).abs() < 0.01, "{}", time);
+
+    assert_eq!(entries[2]["_error"], "net::ERR_BLOCKED_BY_CLIENT");
+    assert_eq!(entries[2]["response"]["status"], 0);
+    assert_eq!(entries[2]["response"]["bodySize"], -1);
+}
@@ -0,0 +1,203 @@
+mod support;
+
+use base64::Engine;
+use debugchrome::CdpClient;
+use debugchrome::intercept::{self, InterceptError, InterceptRules};
+use serde_json::{Value, json};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use support::MockCdp;
+
+// Writes `rules` (and `files`) to a fresh directory and returns the rules file's path.
+fn rules_file(name: &str, rules: Value, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "debugchrome-intercept-{}-{}",
+        name,
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("fixtures")).unwrap();
+    for (file, contents) in files {
+        std::fs::write(dir.join(file), contents).unwrap();
+    }
+    let path = dir.join("rules.json");
+    std::fs::write(&path, rules.to_string()).unwrap();
+    path
+}
+
+fn paused(id: &str, method: &str, url: &str) -> Value {
+    json!({
+        "requestId": id,
+        "request": { "url": url, "method": method, "headers": { "Accept": "*/*", "X-Env": "staging" } },
+    })
+}
+
+#[test]
+fn rules_match_in_order_with_captures() {
+    let path = rules_file(
+        "match",
+        json!([
+            { "url": "*://*/ads/*", "block": true },
+            { "url": "https://staging.example.com/api/*", "method": "post", "delay": 10 },
+            { "url": "https://staging.example.com/api/*?v=?", "rewrite": { "url": "http://localhost:3000/$1" } },
+            { "url": "*/config", "fulfill": { "file": "fixtures/config.json" } },
+        ]),
+        &[],
+    );
+    let rules = InterceptRules::load(&path).unwrap();
+
+    let (rule, captures) = rules
+        .find("https://cdn.example.com/ADS/x.js", "GET")
+        .unwrap();
+    assert_eq!(rule.block.as_deref(), Some("BlockedByClient"));
+    assert_eq!(captures, ["https", "cdn.example.com", "x.js"]);
+    let (rule, _) = rules
+        .find("https://staging.example.com/api/users", "POST")
+        .unwrap();
+    assert_eq!(rule.delay, Some(10));
+    let (rule, captures) = rules
+        .find("https://staging.example.com/api/users?v=2", "GET")
+        .unwrap();
+    assert!(rule.rewrite.is_some());
+    assert_eq!(captures, ["users"]);
+    assert!(
+        rules
+            .find("https://staging.example.com/api/users", "GET")
+            .is_none()
+    );
+    let (rule, _) = rules.find("https://a.test/config", "GET").unwrap();
+    assert_eq!(
+        rule.fulfill.as_ref().unwrap().file.as_deref(),
+        Some(
+            path.parent()
+                .unwrap()
+                .join("fixtures/config.json")
+                .as_path()
+        )
+    );
+}
+
+#[test]
+fn bad_rules_files_are_rejected() {
+    let invalid = |rules: Value| InterceptRules::load(&rules_file("bad", rules, &[])).unwrap_err();
+    assert!(matches!(
+        invalid(json!([{ "url": "*" }])),
+        InterceptError::InvalidRule { index: 0, .. }
+    ));
+    assert!(matches!(
+        invalid(json!([{ "url": "*", "delay": 1 }, { "url": "*", "block": true, "fulfill": {} }])),
+        InterceptError::InvalidRule { index: 1, .. }
+    ));
+    assert!(matches!(
+        invalid(json!([{ "url": "*", "fulfill": { "body": "", "file": "x" } }])),
+        InterceptError::InvalidRule { .. }
+    ));
+    assert!(matches!(
+        invalid(json!([{ "url": "*", "blok": true }])),
+        InterceptError::Json { .. }
+    ));
+    assert!(matches!(
+        InterceptRules::load(Path::new("/nonexistent/rules.json")),
+        Err(InterceptError::Io { .. })
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn paused_requests_are_blocked_rewritten_fulfilled_or_continued() {
+    let mock = MockCdp::start().await;
+    let id = mock.add_page("about:blank", "");
+    let path = rules_file(
+        "run",
+        json!([
+            { "url": "*/ads/*", "block": "TimedOut" },
+            { "url": "https://staging.example.com/api/*", "rewrite": {
+                "url": "http://localhost:3000/api/$1",
+                "headers": { "x-env": "local" },
+            } },
+            { "url": "*/config", "fulfill": { "status": 201, "file": "fixtures/config.json" } },
+        ]),
+        &[("fixtures/config.json", r#"{"feature":true}"#)],
+    );
+    let client = CdpClient::connect_page(&mock.endpoint(), &id)
+        .await
+        .unwrap();
+    let interceptor = intercept::start(&client.page(), InterceptRules::load(&path).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(
+        mock.calls("Fetch.enable")[0].params["patterns"][0]["urlPattern"],
+        "*"
+    );
+    let running = tokio::spawn(interceptor.run());
+
+    for (request_id, url) in [
+        ("1", "https://a.test/ads/banner.js"),
+        ("2", "https://staging.example.com/api/users?page=2"),
+        ("3", "https://a.test/config"),
+        ("4", "https://a.test/app.js"),
+    ] {
+        mock.emit("Fetch.requestPaused", paused(request_id, "GET", url), None);
+    }
+    let deadline = std::time::Instant::now() + Duration::from_secs(5);
+    let answered = || {
+        [
+            "Fetch.failRequest",
+            "Fetch.fulfillRequest",
+            "Fetch.continueRequest",
+        ]
+        .iter()
+        .map(|method| mock.calls(method).len())
+        .sum::<usize>()
+    };
+    while answered() < 4 && std::time::Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    let failed = mock.calls("Fetch.failRequest");
+    assert_eq!(
+        failed[0].params,
+        json!({ "requestId": "1", "errorReason": "TimedOut" })
+    );
+    let fulfilled = &mock.calls("Fetch.fulfillRequest")[0].params;
+    assert_eq!(fulfilled["requestId"], "3");
+    assert_eq!(fulfilled["responseCode"], 201);
+    assert_eq!(
+        fulfilled["responseHeaders"],
+        json!([{ "name": "Content-Type", "value": "application/json" }])
+    );
+    assert_eq!(
+        base64::engine::general_purpose::STANDARD
+            .decode(fulfilled["body"].as_str().unwrap())
+            .unwrap(),
+        br#"{"feature":true}"#
+    );
+    let continued = mock.calls("Fetch.continueRequest");
+    let rewritten = continued
+        .iter()
+        .find(|call| call.params["requestId"] == "2")
+        .unwrap();
+    assert_eq!(
+        rewritten.params["url"],
+        "http://localhost:3000/api/users?page=2"
+    );
+    assert_eq!(
+        rewritten.params["headers"],
+        json!([{ "name": "Accept", "value": "*/*" }, { "name": "x-env", "value": "local" }])
+    );
+    let untouched = continued
+        .iter()
+        .find(|call| call.params["requestId"] == "4")
+        .unwrap();
+    assert_eq!(untouched.params, json!({ "requestId": "4" }));
+
+    mock.emit(
+        "Inspector.detached",
+        json!({ "reason": "target_closed" }),
+        None,
+    );
+    tokio::time::timeout(Duration::from_secs(5), running)
+        .await
+        .expect("still intercepting after the tab closed")
+        .unwrap();
+    let _ = std::fs::remove_dir_all(path.parent().unwrap());
+}
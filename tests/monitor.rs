@@ -0,0 +1,75 @@
+use debugchrome::bang::BangUrl;
+use debugchrome::monitor::{Monitor, Rect, pick, unscale, window_bounds};
+
+// A 1920x1080 primary with a scaled 2560x1440 monitor to its right.
+fn desk() -> Vec<Monitor> {
+    vec![
+        Monitor {
+            name: "DP-1".to_string(),
+            bounds: Rect::new(0, 0, 1920, 1080),
+            scale: 1.0,
+            primary: true,
+        },
+        Monitor {
+            name: "HDMI-1".to_string(),
+            bounds: Rect::new(1920, -200, 2560, 1440),
+            scale: 1.5,
+            primary: false,
+        },
+    ]
+}
+
+fn bounds(monitors: &[Monitor], bangs: &str) -> Option<Rect> {
+    let options = BangUrl::parse(&format!("https://example.com/?{}", bangs))
+        .unwrap()
+        .options;
+    window_bounds(monitors, &options)
+}
+
+#[test]
+fn percentages_are_of_the_picked_monitor_and_offset_onto_it() {
+    let monitors = desk();
+    assert_eq!(
+        bounds(&monitors, "!x=50%&!y=0&!w=50%&!h=100%"),
+        Some(Rect::new(960, 0, 960, 1080))
+    );
+    assert_eq!(
+        bounds(&monitors, "!monitor=1&!x=25%&!y=10%&!w=50%"),
+        Some(Rect::new(1920 + 640, -200 + 144, 1280, 1440))
+    );
+    // Only !monitor: the whole monitor.
+    assert_eq!(
+        bounds(&monitors, "!monitor=1"),
+        Some(Rect::new(1920, -200, 2560, 1440))
+    );
+    assert_eq!(
+        bounds(&monitors, "!monitor=1&!dpi=true&!x=0&!y=0&!w=1500&!h=900"),
+        Some(Rect::new(1280, -133, 1000, 600))
+    );
+}
+
+#[test]
+fn partial_or_unplaceable_bounds_leave_the_window_alone() {
+    let monitors = desk();
+    assert_eq!(bounds(&monitors, "!w=800"), None);
+    assert_eq!(bounds(&monitors, ""), None);
+
+    // An unknown monitor sizes percentages by the first one and places nothing by itself.
+    assert_eq!(pick(&monitors, Some(7)).unwrap().name, "DP-1");
+    assert_eq!(bounds(&monitors, "!monitor=7&!w=50%"), None);
+    assert_eq!(
+        bounds(&monitors, "!monitor=7&!x=0&!y=0&!w=50%&!h=50%"),
+        Some(Rect::new(0, 0, 960, 540))
+    );
+
+    // Without a monitor list only pixels can be placed.
+    assert_eq!(
+        bounds(&[], "!x=10&!y=20&!w=800&!h=600"),
+        Some(Rect::new(10, 20, 800, 600))
+    );
+    assert_eq!(bounds(&[], "!x=10&!y=20&!w=50%&!h=600"), None);
+    assert_eq!(
+        unscale(Rect::new(100, 100, 100, 100), 0.0),
+        Rect::new(100, 100, 100, 100)
+    );
+}
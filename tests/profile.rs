@@ -0,0 +1,104 @@
+use debugchrome::profile::{ProfileStore, copy_profile_dir, validate_name};
+use std::fs;
+use std::path::PathBuf;
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "debugchrome-profile-{}-{}",
+        name,
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn open_creates_and_reuses_named_profiles() {
+    let data_dir = scratch("open");
+    let store = ProfileStore::new(&data_dir);
+
+    let qa = store.open("qa").unwrap();
+    fs::write(qa.join("Cookies"), "session").unwrap();
+    store.open("dev").unwrap();
+
+    assert_eq!(qa, data_dir.join("profiles").join("qa"));
+    assert_eq!(
+        fs::read_to_string(store.open("qa").unwrap().join("Cookies")).unwrap(),
+        "session"
+    );
+    assert_eq!(store.list().unwrap(), vec!["dev", "qa"]);
+    fs::remove_dir_all(&data_dir).unwrap();
+}
+
+#[test]
+fn copy_profile_dir_copies_nested_profile() {
+    let root = scratch("copy");
+    let src = root.join("src");
+    let dst = root.join("dst");
+    fs::create_dir_all(src.join("Default").join("Sessions")).unwrap();
+    fs::write(src.join("Local State"), "{}").unwrap();
+    fs::write(
+        src.join("Default").join("Sessions").join("Session_1"),
+        "tabs",
+    )
+    .unwrap();
+
+    copy_profile_dir(&src, &dst).unwrap();
+
+    assert_eq!(fs::read_to_string(dst.join("Local State")).unwrap(), "{}");
+    assert_eq!(
+        fs::read_to_string(dst.join("Default").join("Sessions").join("Session_1")).unwrap(),
+        "tabs"
+    );
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn clone_skips_caches_and_locks() {
+    let data_dir = scratch("clone");
+    let store = ProfileStore::new(&data_dir);
+    let qa = store.open("qa").unwrap();
+    fs::create_dir_all(qa.join("Default").join("Cache")).unwrap();
+    fs::create_dir_all(qa.join("Default").join("Extensions")).unwrap();
+    fs::write(qa.join("Default").join("Cache").join("data_0"), "cached").unwrap();
+    fs::write(
+        qa.join("Default").join("Extensions").join("manifest.json"),
+        "{}",
+    )
+    .unwrap();
+    fs::write(qa.join("lockfile"), "").unwrap();
+
+    let copy = store.clone_profile("qa", "qa-copy").unwrap();
+
+    assert!(copy.join("Default/Extensions/manifest.json").is_file());
+    assert!(!copy.join("Default/Cache").exists());
+    assert!(!copy.join("lockfile").exists());
+    assert!(store.clone_profile("qa", "qa-copy").is_err());
+    fs::remove_dir_all(&data_dir).unwrap();
+}
+
+#[test]
+fn delete_refuses_profiles_in_use() {
+    let data_dir = scratch("delete");
+    let store = ProfileStore::new(&data_dir);
+    let qa = store.open("qa").unwrap();
+    fs::write(qa.join("lockfile"), "").unwrap();
+
+    assert!(store.is_in_use("qa"));
+    assert!(store.delete("qa").is_err());
+    fs::remove_file(qa.join("lockfile")).unwrap();
+    store.delete("qa").unwrap();
+    assert!(!store.exists("qa"));
+    assert!(store.delete("qa").is_err());
+    fs::remove_dir_all(&data_dir).unwrap();
+}
+
+#[test]
+fn profile_names_must_be_plain_directory_names() {
+    for name in ["qa", "team.qa", "qa_2", "qa-2"] {
+        assert!(validate_name(name).is_ok(), "{}", name);
+    }
+    for name in ["", "..", ".hidden", "a/b", "a\\b", "q a"] {
+        assert!(validate_name(name).is_err(), "{}", name);
+    }
+}
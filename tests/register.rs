@@ -0,0 +1,48 @@
+//! `--register` / `--unregister` against a throwaway XDG home.
+#![cfg(not(target_os = "windows"))]
+
+use std::fs;
+use std::path::Path;
+use std::process::Output;
+
+fn debugchrome(home: &Path, arg: &str) -> Output {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_debugchrome"))
+        .arg(arg)
+        .env("HOME", home)
+        .env("XDG_DATA_HOME", home.join("data"))
+        .env("XDG_CONFIG_HOME", home.join("config"))
+        .output()
+        .expect("failed to run debugchrome");
+    assert!(output.status.success(), "debugchrome {} failed", arg);
+    output
+}
+
+#[test]
+fn register_writes_desktop_entry_and_unregister_removes_it() {
+    let home = std::env::temp_dir().join(format!("debugchrome-xdg-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&home);
+    let desktop = home.join("data/applications/debugchrome.desktop");
+    let mimeapps = home.join("config/mimeapps.list");
+
+    debugchrome(&home, "--register");
+    let entry = fs::read_to_string(&desktop).unwrap();
+    assert!(entry.contains("MimeType=x-scheme-handler/debugchrome;"));
+    assert!(entry.contains(&format!("Exec={} %u", env!("CARGO_BIN_EXE_debugchrome"))));
+
+    let status = debugchrome(&home, "--status");
+    assert!(String::from_utf8_lossy(&status.stdout).contains("points at this executable"));
+
+    fs::create_dir_all(mimeapps.parent().unwrap()).unwrap();
+    fs::write(
+        &mimeapps,
+        "[Default Applications]\nx-scheme-handler/debugchrome=debugchrome.desktop\ntext/html=firefox.desktop\n",
+    )
+    .unwrap();
+    debugchrome(&home, "--unregister");
+    assert!(!desktop.exists());
+    assert_eq!(
+        fs::read_to_string(&mimeapps).unwrap(),
+        "[Default Applications]\ntext/html=firefox.desktop\n"
+    );
+    fs::remove_dir_all(&home).unwrap();
+}
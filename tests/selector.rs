@@ -0,0 +1,52 @@
+use debugchrome::TargetInfo;
+use debugchrome::selector::{TabSelector, glob_matches};
+
+fn tab(id: &str, url: &str, title: &str) -> TargetInfo {
+    serde_json::from_value(serde_json::json!({
+        "id": id,
+        "url": url,
+        "title": title,
+        "type": "page",
+    }))
+    .unwrap()
+}
+
+#[test]
+fn globs_match_the_whole_string_ignoring_case() {
+    assert!(glob_matches("*github.com*", "https://GitHub.com/rust-lang"));
+    assert!(glob_matches("https://?.example/*", "https://a.example/x"));
+    assert!(glob_matches("*", ""));
+    assert!(!glob_matches("github.com", "https://github.com/"));
+    assert!(!glob_matches("https://?.example/*", "https://ab.example/x"));
+}
+
+#[test]
+fn selectors_match_url_title_target_index_and_bang() {
+    let docs = tab("T1", "https://docs.rs/regex", "regex - Rust");
+    let pr = tab("T2", "https://github.com/a/b/pull/7#run42", "PR #7 by a");
+
+    let select = |spec: &str| spec.parse::<TabSelector>().unwrap();
+    let matching = |spec: &str| {
+        [&docs, &pr]
+            .iter()
+            .enumerate()
+            .filter(|(index, tab)| select(spec).matches(*index, tab))
+            .map(|(_, tab)| tab.id.clone())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(matching("url:*github.com*"), ["T2"]);
+    assert_eq!(matching("url:https://*"), ["T1", "T2"]);
+    assert_eq!(matching(r"title:/^PR #\d+/"), ["T2"]);
+    assert_eq!(matching("title:*rust*"), ["T1"]);
+    assert_eq!(matching("target:T1"), ["T1"]);
+    assert_eq!(matching("index:1"), ["T2"]);
+    assert_eq!(matching("bang:run42"), ["T2"]);
+    assert_eq!(matching("run42"), ["T2"]);
+}
+
+#[test]
+fn malformed_selectors_are_rejected() {
+    for spec in ["", "url:", "index:first", "title:/(/", "name:foo"] {
+        assert!(spec.parse::<TabSelector>().is_err(), "{}", spec);
+    }
+}
@@ -0,0 +1,87 @@
+//! Drives `debugchrome --serve` over HTTP against the mock DevTools endpoint.
+#![cfg(feature = "uses_gui")]
+mod support;
+
+use serde_json::json;
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+use support::MockCdp;
+
+// Kills the server when the test ends, passed or not.
+struct Server(Child);
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+async fn serve(mock: &MockCdp) -> (Server, String) {
+    // Bind and drop a listener to get a free port.
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let addr = format!("127.0.0.1:{}", port);
+    let child = Command::new(env!("CARGO_BIN_EXE_debugchrome"))
+        .args(["--serve", &addr, "--cdp-endpoint", &mock.endpoint()])
+        .env_remove("DEBUGCHROME_CDP")
+        .stdout(Stdio::null())
+        .spawn()
+        .expect("failed to run debugchrome --serve");
+    let server = Server(child);
+    let base = format!("http://{}", addr);
+    for _ in 0..100 {
+        if reqwest::get(format!("{}/hello", base)).await.is_ok() {
+            return (server, base);
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("debugchrome --serve never answered on {}", addr);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn open_rejects_anything_but_a_url() {
+    let mock = MockCdp::start().await;
+    let (_server, base) = serve(&mock).await;
+    let client = reqwest::Client::new();
+
+    for url in [
+        "--unregister",
+        "--profile",
+        "debugchrome:",
+        "javascript:alert(1)",
+    ] {
+        let response = client
+            .post(format!("{}/open", base))
+            .json(&json!({ "url": url }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400, "{}", url);
+        let report: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(report["status"], "error");
+    }
+    assert!(mock.received().is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn open_opens_the_url_with_its_options() {
+    let mock = MockCdp::start().await;
+    let (_server, base) = serve(&mock).await;
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/open", base))
+        .json(&json!({ "url": "https://www.rust-lang.org", "options": { "id": "api1" } }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 200);
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["status"], "opened");
+    assert_eq!(report["tabs"][0]["bang_id"], "api1");
+    assert_eq!(mock.targets()[0].bang_id.as_deref(), Some("api1"));
+}
@@ -0,0 +1,82 @@
+mod support;
+
+use debugchrome::CdpClient;
+use debugchrome::storage::{self, OriginStorage, StorageState};
+use serde_json::json;
+use support::{MockCdp, Reply};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn cookies_and_storage_round_trip_through_a_file() {
+    let mock = MockCdp::start().await;
+    let id = mock.add_page("https://app.example.com/", "App");
+    mock.respond(
+        "Network.getAllCookies",
+        Reply::Result(json!({ "cookies": [
+            {
+                "name": "sid", "value": "abc", "domain": "app.example.com", "path": "/",
+                "expires": -1, "size": 6, "httpOnly": true, "secure": true, "session": true,
+                "sameSite": "Lax", "priority": "Medium",
+            },
+            {
+                "name": "theme", "value": "dark", "domain": ".example.com", "path": "/",
+                "expires": 1_900_000_000.5, "size": 9, "httpOnly": false, "secure": false,
+                "session": false, "priority": "Medium",
+            },
+        ]})),
+    );
+    mock.respond(
+        "Runtime.evaluate",
+        Reply::Result(json!({ "result": { "type": "object", "value": {
+            "origin": "https://app.example.com",
+            "localStorage": { "token": "t0k3n" },
+            "sessionStorage": {},
+        }}})),
+    );
+    let client = CdpClient::connect_page(&mock.endpoint(), &id)
+        .await
+        .unwrap();
+    let page = client.page();
+
+    let cookies = storage::read_cookies(&page).await.unwrap();
+    assert_eq!(cookies.len(), 2);
+    assert_eq!(cookies[0].expires, None, "session cookie keeps no expiry");
+    assert!(cookies[0].http_only && cookies[0].secure);
+    assert_eq!(cookies[0].same_site.as_deref(), Some("Lax"));
+    assert_eq!(cookies[1].expires, Some(1_900_000_000.5));
+
+    let stored = storage::read_origin(&page).await.unwrap().unwrap();
+    assert_eq!(stored.local_storage["token"], "t0k3n");
+
+    let state = StorageState {
+        cookies,
+        origins: vec![stored],
+    };
+    let path = std::env::temp_dir()
+        .join(format!("debugchrome-storage-{}", std::process::id()))
+        .join("state.json");
+    state.save(&path).unwrap();
+    let loaded = StorageState::load(&path).unwrap();
+    assert_eq!(loaded, state);
+    let _ = std::fs::remove_dir_all(path.parent().unwrap());
+
+    storage::write_cookies(&page, &loaded.cookies)
+        .await
+        .unwrap();
+    let set = &mock.calls("Network.setCookies")[0].params["cookies"];
+    assert_eq!(
+        set[0],
+        json!({
+            "name": "sid", "value": "abc", "domain": "app.example.com", "path": "/",
+            "httpOnly": true, "secure": true, "sameSite": "Lax",
+        })
+    );
+    assert_eq!(set[1]["expires"], 1_900_000_000.5);
+
+    let script = loaded.seed_script();
+    assert!(script.contains(r#""https://app.example.com":{"origin":"https://app.example.com","localStorage":{"token":"t0k3n"}"#), "{}", script);
+    assert!(
+        loaded
+            .origin("https://app.example.com")
+            .is_some_and(|stored: &OriginStorage| stored.session_storage.is_empty())
+    );
+}
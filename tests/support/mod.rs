@@ -0,0 +1,637 @@
+//! In-process stand-in for Chrome's DevTools endpoint.
+//!
+//! `MockCdp` listens on an ephemeral local port and serves `/json`, `/json/version`,
+//! `/devtools/browser/<id>` and `/devtools/page/<id>` WebSockets, so the library
+//! and the `debugchrome` binary can be driven end to end without a browser.
+//! Commands get plausible default replies; tests can script others with `respond`.
+#![allow(dead_code)]
+
+use base64::Engine;
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tokio_tungstenite::tungstenite::Message;
+
+/// Bytes returned (base64-encoded) by `Page.captureScreenshot`.
+pub const SCREENSHOT_BYTES: &[u8] = b"\x89PNG\r\n\x1a\nmock-screenshot";
+
+/// Bytes returned (base64-encoded) by `Page.printToPDF`.
+pub const PDF_BYTES: &[u8] = b"%PDF-1.7\nmock-pdf";
+
+const BROWSER_ID: &str = "mock-browser";
+
+/// The `windowId` of the browser window; deliberately unlike any target id.
+pub const MOCK_WINDOW_ID: i64 = 7;
+
+/// A scripted reply for a command.
+#[derive(Debug, Clone)]
+pub enum Reply {
+    Result(Value),
+    Error(i64, String),
+    /// Never answer, to exercise client timeouts.
+    Silent,
+    /// Close the socket instead of answering.
+    Hangup,
+    /// Answer with the inner reply after a while, leaving the socket free meanwhile.
+    Delayed(Duration, Box<Reply>),
+}
+
+/// A page the mock browser knows about.
+#[derive(Debug, Clone, Default)]
+pub struct MockTarget {
+    pub id: String,
+    pub url: String,
+    pub title: String,
+    pub browser_context_id: Option<String>,
+    /// Value of `window.bangId` in the page.
+    pub bang_id: Option<String>,
+    /// Value of `sessionStorage.getItem('bangId')` in the page.
+    pub session_bang_id: Option<String>,
+    /// Sources registered with `Page.addScriptToEvaluateOnNewDocument`.
+    pub new_document_scripts: Vec<String>,
+}
+
+/// A command received on one of the WebSockets.
+#[derive(Debug, Clone)]
+pub struct Received {
+    /// Request path of the socket, e.g. `/devtools/page/PAGE-1`.
+    pub path: String,
+    pub method: String,
+    pub params: Value,
+    pub session_id: Option<String>,
+}
+
+#[derive(Default)]
+struct State {
+    targets: Vec<MockTarget>,
+    received: Vec<Received>,
+    scripted: HashMap<String, Reply>,
+    expressions: HashMap<String, Reply>,
+    // sessionId -> targetId for `Target.attachToTarget`
+    sessions: HashMap<String, String>,
+    // The bounds of the one browser window every target lives in; null until first asked.
+    window: Value,
+    // Events a default reply sends on the same socket right after the reply itself.
+    follow_ups: Vec<Value>,
+    next_id: usize,
+}
+
+impl State {
+    fn next(&mut self, prefix: &str) -> String {
+        self.next_id += 1;
+        format!("{}-{}", prefix, self.next_id)
+    }
+
+    fn target_mut(&mut self, id: &str) -> Option<&mut MockTarget> {
+        self.targets.iter_mut().find(|t| t.id == id)
+    }
+
+    fn window(&mut self) -> &mut Value {
+        if self.window.is_null() {
+            self.window = json!({ "left": 0, "top": 0, "width": 1280, "height": 720, "windowState": "normal" });
+        }
+        &mut self.window
+    }
+}
+
+pub struct MockCdp {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    events: broadcast::Sender<String>,
+    server: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for MockCdp {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+impl MockCdp {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind mock CDP");
+        let addr = listener.local_addr().unwrap();
+        let state = Arc::new(Mutex::new(State::default()));
+        let (events, _) = broadcast::channel(64);
+
+        let server = {
+            let state = state.clone();
+            let events = events.clone();
+            tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(serve(stream, addr, state.clone(), events.clone()));
+                }
+            })
+        };
+
+        MockCdp {
+            addr,
+            state,
+            events,
+            server,
+        }
+    }
+
+    /// `host:port` of the mock, suitable for `--cdp-endpoint`.
+    pub fn endpoint(&self) -> String {
+        self.addr.to_string()
+    }
+
+    /// Adds a page and returns its target id.
+    pub fn add_page(&self, url: &str, title: &str) -> String {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next("PAGE");
+        state.targets.push(MockTarget {
+            id: id.clone(),
+            url: url.to_string(),
+            title: title.to_string(),
+            ..Default::default()
+        });
+        id
+    }
+
+    pub fn set_bang_id(&self, target_id: &str, bang_id: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.target_mut(target_id).expect("unknown target").bang_id = Some(bang_id.to_string());
+    }
+
+    pub fn set_session_bang_id(&self, target_id: &str, bang_id: &str) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .target_mut(target_id)
+            .expect("unknown target")
+            .session_bang_id = Some(bang_id.to_string());
+    }
+
+    pub fn targets(&self) -> Vec<MockTarget> {
+        self.state.lock().unwrap().targets.clone()
+    }
+
+    pub fn target(&self, target_id: &str) -> Option<MockTarget> {
+        self.targets().into_iter().find(|t| t.id == target_id)
+    }
+
+    /// Replaces the default reply for every later call to `method`.
+    pub fn respond(&self, method: &str, reply: Reply) {
+        let mut state = self.state.lock().unwrap();
+        state.scripted.insert(method.to_string(), reply);
+    }
+
+    /// Makes `Runtime.evaluate` of exactly `expression` return `result` as its `result` field.
+    pub fn evaluate_to(&self, expression: &str, result: Value) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .expressions
+            .insert(expression.to_string(), Reply::Result(result));
+    }
+
+    /// Like `evaluate_to`, but the reply only comes after `delay`, as for a slow promise.
+    pub fn evaluate_after(&self, expression: &str, delay: Duration, result: Value) {
+        let mut state = self.state.lock().unwrap();
+        state.expressions.insert(
+            expression.to_string(),
+            Reply::Delayed(delay, Box::new(Reply::Result(result))),
+        );
+    }
+
+    /// Overrides fields of the browser window's bounds, e.g. `{"windowState": "maximized"}`.
+    pub fn set_window(&self, bounds: Value) {
+        let mut state = self.state.lock().unwrap();
+        let window = state.window();
+        for (key, value) in bounds.as_object().cloned().unwrap_or_default() {
+            window[key] = value;
+        }
+    }
+
+    /// The browser window's current bounds.
+    pub fn window(&self) -> Value {
+        self.state.lock().unwrap().window().clone()
+    }
+
+    /// Sends an event to every open WebSocket.
+    pub fn emit(&self, method: &str, params: Value, session_id: Option<&str>) {
+        let mut event = json!({ "method": method, "params": params });
+        if let Some(session_id) = session_id {
+            event["sessionId"] = json!(session_id);
+        }
+        let _ = self.events.send(event.to_string());
+    }
+
+    pub fn received(&self) -> Vec<Received> {
+        self.state.lock().unwrap().received.clone()
+    }
+
+    pub fn calls(&self, method: &str) -> Vec<Received> {
+        self.received()
+            .into_iter()
+            .filter(|r| r.method == method)
+            .collect()
+    }
+
+    /// Polls until `method` has been received, for flows that finish in another process.
+    pub async fn wait_for(&self, method: &str, timeout: Duration) -> Option<Received> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(call) = self.calls(method).into_iter().next() {
+                return Some(call);
+            }
+            if Instant::now() >= deadline {
+                return None;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+}
+
+async fn serve(
+    mut stream: TcpStream,
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    events: broadcast::Sender<String>,
+) {
+    // Peek at the request head so a WebSocket upgrade can still be handed to tungstenite.
+    let mut buf = vec![0u8; 8192];
+    let head = loop {
+        let n = match stream.peek(&mut buf).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => n,
+        };
+        let text = String::from_utf8_lossy(&buf[..n]).to_string();
+        if text.contains("\r\n\r\n") || n == buf.len() {
+            break text;
+        }
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    };
+    let path = head
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .unwrap_or("/")
+        .to_string();
+    let is_upgrade = head
+        .lines()
+        .any(|line| line.to_ascii_lowercase().starts_with("upgrade:") && line.contains("ebsocket"));
+
+    if is_upgrade {
+        if let Ok(ws) = tokio_tungstenite::accept_async(stream).await {
+            serve_socket(ws, path, state, events.subscribe()).await;
+        }
+        return;
+    }
+
+    let head_len = head.find("\r\n\r\n").map(|i| i + 4).unwrap_or(head.len());
+    let mut consumed = vec![0u8; head_len];
+    if stream.read_exact(&mut consumed).await.is_err() {
+        return;
+    }
+    let (status, body) = match path.trim_end_matches('/') {
+        "/json" | "/json/list" => {
+            let state = state.lock().unwrap();
+            let list: Vec<Value> = state
+                .targets
+                .iter()
+                .map(|t| {
+                    json!({
+                        "id": t.id,
+                        "title": t.title,
+                        "url": t.url,
+                        "type": "page",
+                        "webSocketDebuggerUrl": format!("ws://{}/devtools/page/{}", addr, t.id),
+                    })
+                })
+                .collect();
+            ("200 OK", Value::Array(list).to_string())
+        }
+        "/json/version" => (
+            "200 OK",
+            json!({
+                "Browser": "MockChrome/1.0",
+                "Protocol-Version": "1.3",
+                "webSocketDebuggerUrl": format!("ws://{}/devtools/browser/{}", addr, BROWSER_ID),
+            })
+            .to_string(),
+        ),
+        _ => ("404 Not Found", String::new()),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+async fn serve_socket(
+    ws: tokio_tungstenite::WebSocketStream<TcpStream>,
+    path: String,
+    state: Arc<Mutex<State>>,
+    mut events: broadcast::Receiver<String>,
+) {
+    let (mut sink, mut source) = ws.split();
+    let page_id = path.strip_prefix("/devtools/page/").map(str::to_string);
+    let (delayed_sender, mut delayed) = tokio::sync::mpsc::unbounded_channel::<Message>();
+
+    loop {
+        tokio::select! {
+            message = source.next() => {
+                let text = match message {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | None | Some(Err(_)) => break,
+                    Some(Ok(_)) => continue,
+                };
+                let Ok(request) = serde_json::from_str::<Value>(&text) else {
+                    continue;
+                };
+                let (delay, replies) = handle_command(&state, &path, page_id.as_deref(), &request);
+                if !delay.is_zero() {
+                    let sender = delayed_sender.clone();
+                    tokio::spawn(async move {
+                        tokio::time::sleep(delay).await;
+                        for reply in replies {
+                            let _ = sender.send(reply);
+                        }
+                    });
+                    continue;
+                }
+                for reply in replies {
+                    let hangup = matches!(reply, Message::Close(_));
+                    if sink.send(reply).await.is_err() || hangup {
+                        return;
+                    }
+                }
+            }
+            Some(reply) = delayed.recv() => {
+                let hangup = matches!(reply, Message::Close(_));
+                if sink.send(reply).await.is_err() || hangup {
+                    return;
+                }
+            }
+            event = events.recv() => {
+                match event {
+                    Ok(event) => {
+                        if sink.send(Message::Text(event.into())).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        }
+    }
+}
+
+fn handle_command(
+    state: &Mutex<State>,
+    path: &str,
+    page_id: Option<&str>,
+    request: &Value,
+) -> (Duration, Vec<Message>) {
+    let id = request["id"].clone();
+    let method = request["method"].as_str().unwrap_or_default().to_string();
+    let params = request.get("params").cloned().unwrap_or(Value::Null);
+    let session_id = request["sessionId"].as_str().map(str::to_string);
+
+    let mut state = state.lock().unwrap();
+    state.received.push(Received {
+        path: path.to_string(),
+        method: method.clone(),
+        params: params.clone(),
+        session_id: session_id.clone(),
+    });
+
+    // Commands on a flattened session act on the attached page.
+    let page_id = match &session_id {
+        Some(session_id) => state.sessions.get(session_id).cloned(),
+        None => page_id.map(str::to_string),
+    };
+
+    let mut outcome = match state.scripted.get(&method).cloned() {
+        Some(reply) => reply,
+        None => default_reply(&mut state, page_id.as_deref(), &method, &params),
+    };
+    let mut delay = Duration::ZERO;
+    while let Reply::Delayed(after, reply) = outcome {
+        delay += after;
+        outcome = *reply;
+    }
+
+    let reply = match outcome {
+        Reply::Result(result) => json!({ "id": id, "result": result }),
+        Reply::Error(code, message) => {
+            json!({ "id": id, "error": { "code": code, "message": message } })
+        }
+        Reply::Silent => return (delay, Vec::new()),
+        Reply::Hangup => return (delay, vec![Message::Close(None)]),
+        Reply::Delayed(..) => unreachable!("unwrapped above"),
+    };
+    let mut messages = vec![reply];
+    messages.append(&mut state.follow_ups);
+    let messages = messages
+        .into_iter()
+        .map(|mut message| {
+            if let Some(session_id) = &session_id {
+                message["sessionId"] = json!(session_id);
+            }
+            Message::Text(message.to_string().into())
+        })
+        .collect();
+    (delay, messages)
+}
+
+fn default_reply(state: &mut State, page_id: Option<&str>, method: &str, params: &Value) -> Reply {
+    let not_found = || Reply::Error(-32601, format!("'{}' wasn't found", method));
+    match method {
+        "Target.createBrowserContext" => {
+            Reply::Result(json!({ "browserContextId": state.next("CONTEXT") }))
+        }
+        "Target.createTarget" => {
+            let id = state.next("PAGE");
+            state.targets.push(MockTarget {
+                id: id.clone(),
+                url: params["url"].as_str().unwrap_or_default().to_string(),
+                browser_context_id: params["browserContextId"].as_str().map(str::to_string),
+                ..Default::default()
+            });
+            Reply::Result(json!({ "targetId": id }))
+        }
+        "Target.closeTarget" => {
+            let target_id = params["targetId"].as_str().unwrap_or_default();
+            let before = state.targets.len();
+            state.targets.retain(|t| t.id != target_id);
+            if state.targets.len() == before {
+                Reply::Error(-32602, "No target with given id found".to_string())
+            } else {
+                Reply::Result(json!({ "success": true }))
+            }
+        }
+        "Target.attachToTarget" => {
+            let target_id = params["targetId"].as_str().unwrap_or_default().to_string();
+            if state.target_mut(&target_id).is_none() {
+                return Reply::Error(-32602, "No target with given id found".to_string());
+            }
+            let session_id = state.next("SESSION");
+            state.sessions.insert(session_id.clone(), target_id);
+            Reply::Result(json!({ "sessionId": session_id }))
+        }
+        "Target.detachFromTarget" => {
+            if let Some(session_id) = params["sessionId"].as_str() {
+                state.sessions.remove(session_id);
+            }
+            Reply::Result(json!({}))
+        }
+        "Target.getTargets" => {
+            let infos: Vec<Value> = state
+                .targets
+                .iter()
+                .map(
+                    |t| json!({ "targetId": t.id, "type": "page", "title": t.title, "url": t.url }),
+                )
+                .collect();
+            Reply::Result(json!({ "targetInfos": infos }))
+        }
+        "Browser.getWindowForTarget" => {
+            let target_id = params["targetId"].as_str().unwrap_or_default().to_string();
+            if state.target_mut(&target_id).is_none() {
+                return Reply::Error(
+                    -32000,
+                    "No web contents for the given target id".to_string(),
+                );
+            }
+            Reply::Result(json!({ "windowId": MOCK_WINDOW_ID, "bounds": state.window() }))
+        }
+        "Browser.getWindowBounds" | "Browser.setWindowBounds"
+            if params["windowId"] != MOCK_WINDOW_ID =>
+        {
+            Reply::Error(-32000, "Browser window not found".to_string())
+        }
+        "Browser.getWindowBounds" => Reply::Result(json!({ "bounds": state.window() })),
+        "Browser.setWindowBounds" => {
+            // Chrome's own rules: a state can't come with a position, and only a normal
+            // window can be moved.
+            let bounds = params["bounds"].as_object().cloned().unwrap_or_default();
+            let moves = ["left", "top", "width", "height"]
+                .iter()
+                .any(|key| bounds.contains_key(*key));
+            let state_to = bounds.get("windowState").and_then(Value::as_str);
+            if moves && state_to.is_some_and(|to| to != "normal") {
+                return Reply::Error(
+                    -32000,
+                    "The 'minimized', 'maximized' and 'fullscreen' states cannot be combined with 'left', 'top', 'width' or 'height'".to_string(),
+                );
+            }
+            let window = state.window();
+            if moves && window["windowState"] != "normal" {
+                return Reply::Error(
+                    -32000,
+                    "To resize minimized/maximized/fullscreen window, restore it to normal state first.".to_string(),
+                );
+            }
+            for (key, value) in bounds {
+                window[key] = value;
+            }
+            Reply::Result(json!({}))
+        }
+        "Page.captureScreenshot" if page_id.is_some() => Reply::Result(json!({
+            "data": base64::engine::general_purpose::STANDARD.encode(SCREENSHOT_BYTES)
+        })),
+        "Page.printToPDF" if page_id.is_some() => Reply::Result(json!({
+            "data": base64::engine::general_purpose::STANDARD.encode(PDF_BYTES)
+        })),
+        "Page.addScriptToEvaluateOnNewDocument" => {
+            let identifier = state.next("SCRIPT");
+            match page_id.and_then(|id| state.target_mut(id)) {
+                Some(target) => {
+                    let source = params["source"].as_str().unwrap_or_default();
+                    target.new_document_scripts.push(source.to_string());
+                    Reply::Result(json!({ "identifier": identifier }))
+                }
+                None => not_found(),
+            }
+        }
+        // Chrome answers before the new document commits; the commit follows as an event.
+        "Page.reload" => match page_id.and_then(|id| state.target_mut(id)) {
+            Some(target) => {
+                let frame = json!({ "id": target.id, "url": target.url });
+                state
+                    .follow_ups
+                    .push(json!({ "method": "Page.frameNavigated", "params": { "frame": frame } }));
+                Reply::Result(json!({}))
+            }
+            None => not_found(),
+        },
+        "Page.navigate" => match page_id.and_then(|id| state.target_mut(id)) {
+            Some(target) => {
+                target.url = params["url"].as_str().unwrap_or_default().to_string();
+                Reply::Result(json!({ "frameId": target.id }))
+            }
+            None => not_found(),
+        },
+        "Runtime.evaluate" => match page_id {
+            Some(page_id) => evaluate(state, page_id, params["expression"].as_str().unwrap_or("")),
+            None => not_found(),
+        },
+        _ => Reply::Result(json!({})),
+    }
+}
+
+// Understands the handful of expressions `debugchrome` sends; anything else is `undefined`.
+fn evaluate(state: &mut State, page_id: &str, expression: &str) -> Reply {
+    if let Some(reply) = state.expressions.get(expression).cloned() {
+        return reply;
+    }
+    let Some(target) = state.target_mut(page_id) else {
+        return Reply::Error(-32000, "Cannot find context with specified id".to_string());
+    };
+    let expression = expression.trim().trim_end_matches(';');
+    let assigned = |prefix: &str| {
+        expression
+            .strip_prefix(prefix)
+            .and_then(|value| serde_json::from_str::<Value>(value.trim()).ok())
+    };
+
+    let value = if let Some(value) = assigned("window.bangId =") {
+        target.bang_id = value.as_str().map(str::to_string);
+        Some(value)
+    } else if let Some(value) = assigned("window.location.hash =") {
+        let hash = value.as_str().unwrap_or_default();
+        let base = target.url.split('#').next().unwrap_or_default().to_string();
+        target.url = format!("{}#{}", base, hash);
+        Some(value)
+    } else {
+        match expression {
+            "window.bangId" => target.bang_id.clone().map(Value::from),
+            "sessionStorage.getItem('bangId')" => Some(
+                target
+                    .session_bang_id
+                    .clone()
+                    .map_or(Value::Null, Value::from),
+            ),
+            "document.title" => Some(Value::from(target.title.clone())),
+            _ => None,
+        }
+    };
+
+    Reply::Result(json!({ "result": remote_object(value) }))
+}
+
+fn remote_object(value: Option<Value>) -> Value {
+    match value {
+        None => json!({ "type": "undefined" }),
+        Some(Value::Null) => json!({ "type": "object", "subtype": "null", "value": null }),
+        Some(Value::String(s)) => json!({ "type": "string", "value": s }),
+        Some(Value::Bool(b)) => json!({ "type": "boolean", "value": b }),
+        Some(Value::Number(n)) => json!({ "type": "number", "value": n }),
+        Some(other) => json!({ "type": "object", "value": other }),
+    }
+}
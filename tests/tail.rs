@@ -0,0 +1,123 @@
+mod support;
+
+use debugchrome::tail::{LogEntry, tail};
+use debugchrome::{CdpClient, CdpEvent};
+use serde_json::{Value, json};
+use std::time::Duration;
+use support::MockCdp;
+
+fn event(method: &str, params: Value) -> CdpEvent {
+    CdpEvent {
+        method: method.to_string(),
+        params,
+        session_id: None,
+    }
+}
+
+#[test]
+fn console_exception_and_log_events_become_entries() {
+    let console = LogEntry::from_event(&event(
+        "Runtime.consoleAPICalled",
+        json!({
+            "type": "warning",
+            "timestamp": 1_700_000_000_250.0,
+            "args": [
+                { "type": "string", "value": "count" },
+                { "type": "number", "value": 3 },
+                { "type": "object", "description": "Object" },
+            ],
+            "stackTrace": { "callFrames": [{ "url": "https://a/app.js", "lineNumber": 9 }] },
+        }),
+    ))
+    .unwrap();
+    assert_eq!(console.level, "warning");
+    assert_eq!(console.source, "console");
+    assert_eq!(console.text, "count 3 Object");
+    assert_eq!(
+        (console.url.as_deref(), console.line),
+        (Some("https://a/app.js"), Some(10))
+    );
+    assert!(
+        console
+            .to_string()
+            .ends_with("WARNING console: count 3 Object (https://a/app.js:10)"),
+        "{}",
+        console
+    );
+
+    let exception = LogEntry::from_event(&event(
+        "Runtime.exceptionThrown",
+        json!({
+            "timestamp": 1_700_000_000_500.0,
+            "exceptionDetails": {
+                "text": "Uncaught",
+                "lineNumber": 2,
+                "columnNumber": 4,
+                "exception": { "description": "TypeError: x is undefined\n    at app.js:3:5" },
+            },
+        }),
+    ))
+    .unwrap();
+    assert_eq!(exception.level, "error");
+    assert_eq!(
+        exception.text,
+        "Uncaught TypeError: x is undefined (line 3, column 5)"
+    );
+
+    let log = LogEntry::from_event(&event(
+        "Log.entryAdded",
+        json!({ "entry": {
+            "source": "network",
+            "level": "error",
+            "text": "Failed to load resource: 404",
+            "timestamp": 1_700_000_001_000.0,
+            "url": "https://a/missing.png",
+        }}),
+    ))
+    .unwrap();
+    assert_eq!(
+        (log.level.as_str(), log.source.as_str()),
+        ("error", "network")
+    );
+    assert_eq!(log.url.as_deref(), Some("https://a/missing.png"));
+
+    assert!(LogEntry::from_event(&event("Page.loadEventFired", json!({}))).is_none());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn streams_until_the_tab_is_detached() {
+    let mock = MockCdp::start().await;
+    let id = mock.add_page("https://example.com/", "Example");
+    let client = CdpClient::connect_page(&mock.endpoint(), &id)
+        .await
+        .unwrap();
+    let tailing = tokio::spawn(async move {
+        let mut entries = Vec::new();
+        tail(&client.page(), |entry| entries.push(entry))
+            .await
+            .map(|()| entries)
+    });
+
+    mock.wait_for("Log.enable", Duration::from_secs(5))
+        .await
+        .expect("Log.enable not sent");
+    assert_eq!(mock.calls("Runtime.enable").len(), 1);
+    mock.emit(
+        "Runtime.consoleAPICalled",
+        json!({ "type": "log", "timestamp": 0.0, "args": [{ "type": "string", "value": "hi" }] }),
+        None,
+    );
+    mock.emit(
+        "Inspector.detached",
+        json!({ "reason": "target_closed" }),
+        None,
+    );
+
+    let entries = tokio::time::timeout(Duration::from_secs(5), tailing)
+        .await
+        .expect("still tailing after the tab was detached")
+        .unwrap()
+        .unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].text, "hi");
+}
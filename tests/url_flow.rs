@@ -0,0 +1,1275 @@
+//! Drives the `debugchrome` binary against the mock DevTools endpoint.
+mod support;
+
+use debugchrome::wait::WaitCondition;
+use serde_json::json;
+use std::process::Output;
+use std::time::Duration;
+use support::{MOCK_WINDOW_ID, MockCdp, PDF_BYTES, Reply, SCREENSHOT_BYTES};
+
+async fn run(endpoint: &str, args: &[&str]) -> Output {
+    let mut command = std::process::Command::new(env!("CARGO_BIN_EXE_debugchrome"));
+    command
+        .args(args)
+        .args(["--cdp-endpoint", endpoint])
+        .env_remove("DEBUGCHROME_CDP");
+    tokio::task::spawn_blocking(move || command.output())
+        .await
+        .unwrap()
+        .expect("failed to run debugchrome")
+}
+
+async fn debugchrome(mock: &MockCdp, args: &[&str]) -> Output {
+    let output = run(&mock.endpoint(), args).await;
+    assert!(
+        output.status.success(),
+        "debugchrome {:?} failed:\n{}\n{}",
+        args,
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output
+}
+
+// Runs with `--json` and returns the exit code and the parsed document.
+async fn debugchrome_json(endpoint: &str, args: &[&str]) -> (i32, serde_json::Value) {
+    let mut args = args.to_vec();
+    args.push("--json");
+    let output = run(endpoint, &args).await;
+    let report = serde_json::from_slice(&output.stdout).unwrap_or_else(|e| {
+        panic!(
+            "stdout is not one JSON document ({}):\n{}",
+            e,
+            String::from_utf8_lossy(&output.stdout)
+        )
+    });
+    (output.status.code().unwrap(), report)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn opens_new_tab_and_tags_it_with_bang_id() {
+    let mock = MockCdp::start().await;
+
+    debugchrome(&mock, &["debugchrome:https://www.rust-lang.org?!id=flow1"]).await;
+
+    let created = mock.calls("Target.createTarget");
+    assert_eq!(created.len(), 1);
+    assert!(
+        created[0].params["url"]
+            .as_str()
+            .unwrap()
+            .starts_with("https://www.rust-lang.org")
+    );
+    let targets = mock.targets();
+    assert_eq!(targets.len(), 1);
+    assert_eq!(targets[0].bang_id.as_deref(), Some("flow1"));
+    assert!(targets[0].url.ends_with("#flow1"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reuses_tab_found_by_bang_id() {
+    let mock = MockCdp::start().await;
+    mock.add_page("https://other.example/", "Other");
+    let id = mock.add_page("https://www.rust-lang.org/", "Rust");
+    mock.set_bang_id(&id, "flow2");
+
+    debugchrome(&mock, &["debugchrome:https://www.rust-lang.org?!id=flow2"]).await;
+
+    assert!(mock.calls("Target.createTarget").is_empty());
+    let activated = mock.calls("Target.activateTarget");
+    assert_eq!(activated.len(), 1);
+    assert_eq!(activated[0].params["targetId"], json!(id));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn refreshes_and_closes_existing_tab() {
+    let mock = MockCdp::start().await;
+    let id = mock.add_page("https://www.rust-lang.org/", "Rust");
+    mock.set_bang_id(&id, "flow3");
+
+    debugchrome(
+        &mock,
+        &["debugchrome:https://www.rust-lang.org?!id=flow3&!refresh&!close"],
+    )
+    .await;
+
+    let reloads = mock.calls("Page.reload");
+    assert_eq!(reloads.len(), 1);
+    assert_eq!(reloads[0].path, format!("/devtools/page/{}", id));
+    assert!(mock.target(&id).is_none());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn wait_after_refresh_starts_once_the_new_document_commits() {
+    let mock = MockCdp::start().await;
+    let id = mock.add_page("https://www.rust-lang.org/", "Rust");
+    mock.set_bang_id(&id, "flow5");
+    let ready = WaitCondition::Selector("#app".into()).expression();
+    mock.evaluate_to(
+        &ready,
+        json!({ "result": { "type": "boolean", "value": true } }),
+    );
+    // Answer the reload but hold back the commit until the test sends it.
+    mock.respond("Page.reload", Reply::Result(json!({})));
+
+    let endpoint = mock.endpoint();
+    let run = tokio::spawn(async move {
+        run(
+            &endpoint,
+            &["debugchrome:https://www.rust-lang.org?!id=flow5&!refresh&!wait=selector:%23app"],
+        )
+        .await
+    });
+    mock.wait_for("Page.reload", Duration::from_secs(10))
+        .await
+        .expect("tab was never reloaded");
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    // Evaluations of the wait condition since the reload was asked for.
+    let waited = |mock: &MockCdp| {
+        mock.received()
+            .iter()
+            .skip_while(|r| r.method != "Page.reload")
+            .filter(|r| r.method == "Runtime.evaluate" && r.params["expression"] == json!(ready))
+            .count()
+    };
+    let before_commit = waited(&mock);
+    mock.emit(
+        "Page.frameNavigated",
+        json!({ "frame": { "id": id, "url": "https://www.rust-lang.org/" } }),
+        None,
+    );
+    let output = run.await.unwrap();
+
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(before_commit, 0);
+    assert!(waited(&mock) > 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn search_finds_bang_id_in_session_storage_and_closes_tab() {
+    let mock = MockCdp::start().await;
+    let id = mock.add_page("https://www.rust-lang.org/", "Rust");
+    mock.set_session_bang_id(&id, "flow4");
+
+    debugchrome(&mock, &["--search", "flow4", "--close"]).await;
+
+    let closed = mock.calls("Target.closeTarget");
+    assert_eq!(closed.len(), 1);
+    assert_eq!(closed[0].params["targetId"], json!(id));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn select_bang_closes_every_matching_tab() {
+    let mock = MockCdp::start().await;
+    let keep = mock.add_page("https://docs.rs/", "Docs");
+    let first = mock.add_page("https://github.com/a", "A");
+    let second = mock.add_page("https://github.com/b", "B");
+
+    debugchrome(
+        &mock,
+        &["debugchrome:https://github.com?!select=url:*github.com*&!refresh&!close"],
+    )
+    .await;
+
+    assert!(mock.calls("Target.createTarget").is_empty());
+    assert_eq!(mock.calls("Page.reload").len(), 2);
+    assert!(mock.target(&first).is_none());
+    assert!(mock.target(&second).is_none());
+    assert!(mock.target(&keep).is_some());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn select_without_a_match_opens_nothing_unless_asked() {
+    let mock = MockCdp::start().await;
+    mock.add_page("https://docs.rs/", "Docs");
+
+    let (code, report) = debugchrome_json(
+        &mock.endpoint(),
+        &["debugchrome:https://github.com?!select=url:*github.com*&!screenshot&!close"],
+    )
+    .await;
+    assert_eq!(code, 2);
+    assert_eq!(report["status"], "not_found");
+    assert!(mock.calls("Target.createTarget").is_empty());
+    assert!(mock.calls("Page.captureScreenshot").is_empty());
+
+    debugchrome(
+        &mock,
+        &["debugchrome:https://github.com?!select=url:*github.com*&!open_if_missing"],
+    )
+    .await;
+    assert_eq!(mock.calls("Target.createTarget").len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn search_lists_tabs_matching_a_title_regex() {
+    let mock = MockCdp::start().await;
+    let pr = mock.add_page("https://github.com/a/b/pull/7", "PR #7");
+    mock.add_page("https://github.com/a/b", "a/b");
+    let other_pr = mock.add_page("https://github.com/a/b/pull/8", "PR #8");
+
+    let output = debugchrome(&mock, &["--search", r"title:/^PR #\d+$/"]).await;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let ids: Vec<&str> = stdout
+        .lines()
+        .filter_map(|line| line.split_once('\t').map(|(id, _)| id))
+        .collect();
+    assert_eq!(ids, [pr.as_str(), other_pr.as_str()]);
+    assert!(mock.calls("Target.closeTarget").is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn search_runs_the_same_tab_actions_as_a_url() {
+    let mock = MockCdp::start().await;
+    let id = mock.add_page("https://www.rust-lang.org/", "Rust");
+    let target = format!("target:{}", id);
+
+    let output = debugchrome(&mock, &["--search", &target, "--script", "document.title"]).await;
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "\"Rust\"\n");
+
+    // A wait that never holds fails the run and skips what would see the unready page.
+    let (code, report) = debugchrome_json(
+        &mock.endpoint(),
+        &[
+            "--search",
+            &target,
+            "--wait",
+            "js:window.never",
+            "--wait-timeout",
+            "0",
+            "--script",
+            "document.title",
+            "--screenshot",
+            "--close",
+        ],
+    )
+    .await;
+    assert_eq!(code, 1);
+    let actions: Vec<_> = report["tabs"][0]["actions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|action| (action["action"].as_str().unwrap(), action["ok"] == true))
+        .collect();
+    assert_eq!(actions, [("wait", false), ("close", true)]);
+    assert!(mock.calls("Page.captureScreenshot").is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn json_reports_opened_tab_and_its_actions() {
+    let mock = MockCdp::start().await;
+
+    let (code, report) = debugchrome_json(
+        &mock.endpoint(),
+        &["debugchrome:https://www.rust-lang.org?!id=json1&!screenshot&!noview"],
+    )
+    .await;
+
+    assert_eq!(code, 0);
+    assert_eq!(report["command"], "open");
+    assert_eq!(report["status"], "opened");
+    let tab = &report["tabs"][0];
+    assert_eq!(tab["target_id"], json!(mock.targets()[0].id));
+    assert_eq!(tab["bang_id"], "json1");
+    let actions: Vec<_> = tab["actions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|action| (action["action"].as_str().unwrap(), action["ok"] == true))
+        .collect();
+    assert_eq!(actions, [("screenshot", true), ("set_bang_id", true)]);
+    assert_eq!(tab["actions"][0]["value"], "debugchrome.png");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn json_search_sets_found_and_not_found_exit_codes() {
+    let mock = MockCdp::start().await;
+    let id = mock.add_page("https://www.rust-lang.org/", "Rust");
+    mock.set_bang_id(&id, "json2");
+
+    let (code, report) = debugchrome_json(&mock.endpoint(), &["--search", "json2"]).await;
+    assert_eq!(code, 0);
+    assert_eq!(report["status"], "found");
+    assert_eq!(report["tabs"][0]["target_id"], json!(id));
+    assert_eq!(report["tabs"][0]["bang_id"], "json2");
+
+    let (code, report) = debugchrome_json(&mock.endpoint(), &["--search", "missing"]).await;
+    assert_eq!(code, 2);
+    assert_eq!(report["status"], "not_found");
+    assert_eq!(report["tabs"], json!([]));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn json_search_reports_unreachable_endpoint() {
+    // Bind and drop a listener to get a port nothing is serving on.
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+
+    let (code, report) =
+        debugchrome_json(&format!("127.0.0.1:{}", port), &["--search", "anything"]).await;
+
+    assert_eq!(code, 3);
+    assert_eq!(report["status"], "cdp_unreachable");
+    assert_eq!(report["errors"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn malformed_bang_fails_before_touching_the_browser() {
+    let mock = MockCdp::start().await;
+
+    let output = run(
+        &mock.endpoint(),
+        &["debugchrome:https://www.rust-lang.org?!id=bad&!timeout=soon"],
+    )
+    .await;
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("!timeout"));
+    assert!(mock.received().is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn open_window_uses_a_fresh_browser_context() {
+    let mock = MockCdp::start().await;
+
+    debugchrome(
+        &mock,
+        &[
+            "debugchrome:https://www.rust-lang.org?!id=flow5&!openwindow",
+            "--direct",
+        ],
+    )
+    .await;
+
+    let created = mock.calls("Target.createTarget");
+    assert_eq!(created.len(), 1);
+    assert_eq!(created[0].params["newWindow"], json!(true));
+    assert!(created[0].params["browserContextId"].is_string());
+    assert!(
+        created[0].params["url"]
+            .as_str()
+            .unwrap()
+            .ends_with("#flow5")
+    );
+    assert_eq!(mock.targets()[0].bang_id.as_deref(), Some("flow5"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn window_is_restored_moved_and_maximized_through_its_window_id() {
+    let mock = MockCdp::start().await;
+    let id = mock.add_page("https://www.rust-lang.org/", "Rust");
+    mock.set_bang_id(&id, "place1");
+    mock.set_window(json!({ "windowState": "maximized" }));
+
+    let (code, report) = debugchrome_json(
+        &mock.endpoint(),
+        &["debugchrome:https://www.rust-lang.org?!id=place1&!x=10&!y=20&!w=800&!h=600&!state=maximized"],
+    )
+    .await;
+
+    assert_eq!(code, 0);
+    assert_eq!(
+        mock.calls("Browser.getWindowForTarget")[0].params["targetId"],
+        json!(id)
+    );
+    let set: Vec<_> = mock
+        .calls("Browser.setWindowBounds")
+        .into_iter()
+        .map(|call| {
+            assert_eq!(call.params["windowId"], MOCK_WINDOW_ID);
+            call.params["bounds"].clone()
+        })
+        .collect();
+    assert_eq!(
+        set,
+        [
+            json!({ "windowState": "normal" }),
+            json!({ "left": 10, "top": 20, "width": 800, "height": 600 }),
+            json!({ "windowState": "maximized" }),
+        ]
+    );
+    let place = report["tabs"][0]["actions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|action| action["action"] == "place")
+        .unwrap();
+    assert_eq!(place["ok"], true);
+    assert_eq!(place["value"], mock.window());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn new_tab_goes_fullscreen_without_moving() {
+    let mock = MockCdp::start().await;
+
+    debugchrome(
+        &mock,
+        &["debugchrome:https://www.rust-lang.org?!id=place2&!state=fullscreen"],
+    )
+    .await;
+
+    let set = mock.calls("Browser.setWindowBounds");
+    assert_eq!(set.len(), 1);
+    assert_eq!(
+        set[0].params["bounds"],
+        json!({ "windowState": "fullscreen" })
+    );
+    assert_eq!(mock.window()["width"], 1280);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn timeout_closes_the_tab_later() {
+    let mock = MockCdp::start().await;
+
+    debugchrome(
+        &mock,
+        &["debugchrome:https://www.rust-lang.org?!id=flow6&!timeout=1"],
+    )
+    .await;
+
+    let id = mock.targets()[0].id.clone();
+    let closed = mock
+        .wait_for("Target.closeTarget", Duration::from_secs(15))
+        .await
+        .expect("the timeout closer never closed the tab");
+    assert_eq!(closed.params["targetId"], json!(id));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn close_target_command_closes_by_id() {
+    let mock = MockCdp::start().await;
+    let id = mock.add_page("https://www.rust-lang.org/", "Rust");
+
+    debugchrome(&mock, &["--close-target", &id, "--timeout", "0"]).await;
+
+    assert!(mock.target(&id).is_none());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn headless_screenshot_sizes_viewport_first() {
+    let mock = MockCdp::start().await;
+
+    debugchrome(
+        &mock,
+        &[
+            "debugchrome:https://www.rust-lang.org?!id=flow7&!w=800&!h=50%&!screenshot",
+            "--headless",
+        ],
+    )
+    .await;
+
+    let methods: Vec<String> = mock.received().into_iter().map(|r| r.method).collect();
+    let metrics = methods
+        .iter()
+        .position(|m| m == "Emulation.setDeviceMetricsOverride")
+        .expect("viewport was not overridden");
+    let capture = methods
+        .iter()
+        .position(|m| m == "Page.captureScreenshot")
+        .expect("no screenshot taken");
+    assert!(metrics < capture);
+    let params = &mock.calls("Emulation.setDeviceMetricsOverride")[0].params;
+    assert_eq!(params["width"], json!(800));
+    assert_eq!(params["height"], json!(540));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn full_page_screenshot_is_written_where_asked() {
+    let mock = MockCdp::start().await;
+    mock.respond(
+        "Page.getLayoutMetrics",
+        Reply::Result(
+            json!({ "cssContentSize": { "x": 0, "y": 0, "width": 1200, "height": 5000 } }),
+        ),
+    );
+    let dir = std::env::temp_dir().join(format!("debugchrome-shots-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let path = dir.join("home.jpg");
+
+    let url = format!(
+        "debugchrome:https://www.rust-lang.org?!id=shot1&!fullpage&!quality=70&!noview&!screenshot={}",
+        path.display()
+    );
+    debugchrome(&mock, &[&url]).await;
+
+    let params = &mock.calls("Page.captureScreenshot")[0].params;
+    assert_eq!(params["format"], "jpeg");
+    assert_eq!(params["quality"], 70);
+    assert_eq!(params["captureBeyondViewport"], true);
+    assert_eq!(params["clip"]["height"], json!(5000.0));
+    assert_eq!(std::fs::read(&path).unwrap(), SCREENSHOT_BYTES);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn relative_output_paths_are_taken_from_where_debugchrome_was_started() {
+    let mock = MockCdp::start().await;
+    let dir = std::env::temp_dir().join(format!("debugchrome-cwd-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let mut command = std::process::Command::new(env!("CARGO_BIN_EXE_debugchrome"));
+    command
+        .arg("debugchrome:https://www.rust-lang.org?!id=rel1&!noview&!screenshot=shots/home.png&!pdf=out/report.pdf")
+        .args(["--cdp-endpoint", &mock.endpoint()])
+        .env_remove("DEBUGCHROME_CDP")
+        .current_dir(&dir);
+    let output = tokio::task::spawn_blocking(move || command.output())
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        std::fs::read(dir.join("shots/home.png")).unwrap(),
+        SCREENSHOT_BYTES
+    );
+    assert_eq!(
+        std::fs::read(dir.join("out/report.pdf")).unwrap(),
+        PDF_BYTES
+    );
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn screenshot_clip_without_a_matching_element_fails_the_action() {
+    let mock = MockCdp::start().await;
+    let id = mock.add_page("https://www.rust-lang.org/", "Rust");
+
+    let (_, report) = debugchrome_json(
+        &mock.endpoint(),
+        &[
+            "--search",
+            &format!("target:{}", id),
+            "--screenshot",
+            "--clip",
+            "#missing",
+            "--noview",
+        ],
+    )
+    .await;
+
+    let action = &report["tabs"][0]["actions"][0];
+    assert_eq!(action["action"], "screenshot");
+    assert_eq!(action["ok"], false);
+    assert!(action["error"].as_str().unwrap().contains("#missing"));
+    assert!(mock.calls("Page.captureScreenshot").is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pdf_bang_prints_with_paper_margins_and_footer() {
+    let mock = MockCdp::start().await;
+    let dir = std::env::temp_dir().join(format!("debugchrome-pdf-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let path = dir.join("report.pdf");
+
+    let url = format!(
+        "debugchrome:https://www.rust-lang.org?!id=pdf1&!pdf={}&!paper=a4&!landscape&!margin=0.5,0.25&!background&!footer=<span class=pageNumber></span>&!noview",
+        path.display()
+    );
+    let (code, report) = debugchrome_json(&mock.endpoint(), &[&url]).await;
+
+    assert_eq!(code, 0);
+    let params = &mock.calls("Page.printToPDF")[0].params;
+    assert_eq!(params["paperWidth"], json!(8.27));
+    assert_eq!(params["paperHeight"], json!(11.69));
+    assert_eq!(params["landscape"], true);
+    assert_eq!(params["printBackground"], true);
+    assert_eq!(params["marginTop"], json!(0.5));
+    assert_eq!(params["marginLeft"], json!(0.25));
+    assert_eq!(params["displayHeaderFooter"], true);
+    assert_eq!(params["footerTemplate"], "<span class=pageNumber></span>");
+    assert_eq!(std::fs::read(&path).unwrap(), PDF_BYTES);
+    let action = &report["tabs"][0]["actions"][0];
+    assert_eq!(action["action"], "pdf");
+    assert_eq!(action["value"], json!(path.display().to_string()));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn wait_holds_actions_until_the_page_is_ready() {
+    let mock = MockCdp::start().await;
+    let ready = WaitCondition::Selector("#app".into()).expression();
+    mock.evaluate_to(
+        &ready,
+        json!({ "result": { "type": "boolean", "value": true } }),
+    );
+
+    debugchrome(
+        &mock,
+        &["debugchrome:https://www.rust-lang.org?!id=wait1&!wait=selector:%23app&!screenshot&!noview"],
+    )
+    .await;
+
+    let received = mock.received();
+    let waited = received
+        .iter()
+        .position(|r| r.method == "Runtime.evaluate" && r.params["expression"] == json!(ready))
+        .expect("wait condition was never evaluated");
+    let captured = received
+        .iter()
+        .position(|r| r.method == "Page.captureScreenshot")
+        .expect("no screenshot taken");
+    assert!(waited < captured);
+
+    let (code, report) = debugchrome_json(
+        &mock.endpoint(),
+        &["debugchrome:https://example.com?!id=wait2&!wait=js:window.never&!wait_timeout=0&!screenshot"],
+    )
+    .await;
+    assert_eq!(code, 1);
+    let action = &report["tabs"][0]["actions"][0];
+    assert_eq!(action["action"], "wait");
+    assert_eq!(action["ok"], false);
+    assert_eq!(mock.calls("Page.captureScreenshot").len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn script_value_is_the_only_thing_on_stdout() {
+    let mock = MockCdp::start().await;
+    let id = mock.add_page("https://www.rust-lang.org/", "Rust");
+    mock.set_bang_id(&id, "script1");
+
+    let output = debugchrome(
+        &mock,
+        &[
+            "debugchrome:https://www.rust-lang.org?!id=script1",
+            "--script",
+            "document.title",
+        ],
+    )
+    .await;
+
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "\"Rust\"\n");
+    let evaluated = mock
+        .calls("Runtime.evaluate")
+        .into_iter()
+        .find(|call| call.params["expression"] == "document.title")
+        .expect("script was not evaluated");
+    assert_eq!(evaluated.params["awaitPromise"], true);
+    assert_eq!(evaluated.params["returnByValue"], true);
+
+    let (code, report) = debugchrome_json(
+        &mock.endpoint(),
+        &[
+            "debugchrome:https://www.rust-lang.org?!id=script1",
+            "--script",
+            "document.title",
+        ],
+    )
+    .await;
+    assert_eq!(code, 0);
+    let script = &report["tabs"][0]["actions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|action| action["action"] == "script")
+        .cloned()
+        .unwrap();
+    assert_eq!(script["value"], "Rust");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn slow_script_promise_is_awaited_past_the_reply_timeout() {
+    let mock = MockCdp::start().await;
+    let id = mock.add_page("https://www.rust-lang.org/", "Rust");
+    mock.set_bang_id(&id, "script3");
+    let script = "new Promise(resolve => setTimeout(() => resolve(42), 6000))";
+    // Longer than the 5s every other DevTools call gets.
+    mock.evaluate_after(
+        script,
+        Duration::from_secs(6),
+        json!({ "result": { "type": "number", "value": 42 } }),
+    );
+
+    let output = debugchrome(
+        &mock,
+        &[
+            "debugchrome:https://www.rust-lang.org?!id=script3",
+            "--script",
+            script,
+        ],
+    )
+    .await;
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "42\n");
+
+    let (code, report) = debugchrome_json(
+        &mock.endpoint(),
+        &[
+            "debugchrome:https://www.rust-lang.org?!id=script3",
+            "--script",
+            script,
+            "--script-timeout",
+            "1",
+        ],
+    )
+    .await;
+    assert_eq!(code, 1);
+    let action = report["tabs"][0]["actions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|action| action["action"] == "script")
+        .cloned()
+        .expect("no script action");
+    assert_eq!(action["ok"], false);
+    assert!(
+        action["error"].as_str().unwrap().contains("timed out"),
+        "{}",
+        action
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn throwing_script_exits_non_zero_with_its_position() {
+    let mock = MockCdp::start().await;
+    let id = mock.add_page("https://www.rust-lang.org/", "Rust");
+    mock.set_bang_id(&id, "script2");
+    mock.evaluate_to(
+        "let x = 1;\nboom()",
+        json!({
+            "result": { "type": "object", "subtype": "error", "description": "ReferenceError: boom is not defined\n    at <anonymous>:2:1" },
+            "exceptionDetails": {
+                "text": "Uncaught",
+                "lineNumber": 1,
+                "columnNumber": 0,
+                "exception": { "type": "object", "subtype": "error", "description": "ReferenceError: boom is not defined\n    at <anonymous>:2:1" }
+            }
+        }),
+    );
+
+    let output = run(
+        &mock.endpoint(),
+        &[
+            "debugchrome:https://www.rust-lang.org?!id=script2",
+            "--script",
+            "let x = 1;\nboom()",
+        ],
+    )
+    .await;
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Uncaught ReferenceError: boom is not defined (line 2, column 1)"),
+        "{}",
+        stderr
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn injected_scripts_and_bang_id_are_registered_for_new_documents() {
+    let mock = MockCdp::start().await;
+
+    debugchrome(
+        &mock,
+        &[
+            "debugchrome:https://www.rust-lang.org?!id=inject1",
+            "--inject-script",
+            "window.helper = 1;",
+        ],
+    )
+    .await;
+
+    assert!(
+        mock.calls("Runtime.evaluate")
+            .iter()
+            .any(|call| call.params["expression"] == "window.helper = 1;"),
+        "inject script did not run in the current document"
+    );
+    // Detached `--keep-injected` processes register the scripts and hold the page open.
+    assert_eq!(
+        mock.targets()[0].new_document_scripts,
+        ["window.helper = 1;", "window.bangId = \"inject1\";"]
+    );
+
+    // The same command on the same tab leaves the running injector alone.
+    debugchrome(
+        &mock,
+        &[
+            "debugchrome:https://www.rust-lang.org?!id=inject1",
+            "--inject-script",
+            "window.helper = 1;",
+        ],
+    )
+    .await;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(mock.targets().len(), 1);
+    assert_eq!(mock.targets()[0].new_document_scripts.len(), 2);
+
+    // Larger than any one command-line argument may be, even on Linux.
+    let big = format!("window.big = {:?};", "x".repeat(200_000));
+    let file = std::env::temp_dir().join(format!("debugchrome-big-{}.js", std::process::id()));
+    std::fs::write(&file, &big).unwrap();
+    debugchrome(
+        &mock,
+        &[
+            "debugchrome:https://www.rust-lang.org?!id=inject1",
+            "--inject-script-file",
+            file.to_str().unwrap(),
+        ],
+    )
+    .await;
+    let _ = std::fs::remove_file(&file);
+    assert_eq!(mock.targets()[0].new_document_scripts[2], big);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn script_files_are_read_from_where_debugchrome_was_started() {
+    let mock = MockCdp::start().await;
+    let dir = std::env::temp_dir().join(format!("debugchrome-scripts-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("tools")).unwrap();
+    std::fs::write(dir.join("tools/hooks.js"), "window.hooked = 1;").unwrap();
+    std::fs::write(dir.join("tools/check.js"), "document.title").unwrap();
+
+    let mut command = std::process::Command::new(env!("CARGO_BIN_EXE_debugchrome"));
+    command
+        .args([
+            "debugchrome:https://www.rust-lang.org?!id=files1",
+            "--inject-script-file",
+            "tools/hooks.js",
+            "--script-file",
+            "tools/check.js",
+            "--cdp-endpoint",
+            &mock.endpoint(),
+        ])
+        .env_remove("DEBUGCHROME_CDP")
+        .current_dir(&dir);
+    let output = tokio::task::spawn_blocking(move || command.output())
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let evaluated: Vec<_> = mock
+        .calls("Runtime.evaluate")
+        .into_iter()
+        .map(|call| call.params["expression"].clone())
+        .collect();
+    assert!(evaluated.contains(&json!("window.hooked = 1;")));
+    assert!(evaluated.contains(&json!("document.title")));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn tail_prints_console_output_until_the_tab_closes() {
+    let mock = MockCdp::start().await;
+    let id = mock.add_page("https://www.rust-lang.org/", "Rust");
+    mock.set_bang_id(&id, "tail1");
+    let mut command = std::process::Command::new(env!("CARGO_BIN_EXE_debugchrome"));
+    command
+        .args([
+            "--tail",
+            "tail1",
+            "--json",
+            "--cdp-endpoint",
+            &mock.endpoint(),
+        ])
+        .env_remove("DEBUGCHROME_CDP")
+        .stdout(std::process::Stdio::piped());
+    let child = command.spawn().expect("failed to run debugchrome");
+    let output = tokio::task::spawn_blocking(move || child.wait_with_output());
+
+    mock.wait_for("Log.enable", Duration::from_secs(10))
+        .await
+        .expect("--tail never enabled the Log domain");
+    mock.emit(
+        "Runtime.consoleAPICalled",
+        json!({
+            "type": "error",
+            "timestamp": 1_700_000_000_000.0,
+            "args": [{ "type": "string", "value": "boom" }],
+        }),
+        None,
+    );
+    mock.emit(
+        "Inspector.detached",
+        json!({ "reason": "target_closed" }),
+        None,
+    );
+
+    let output = tokio::time::timeout(Duration::from_secs(10), output)
+        .await
+        .expect("--tail kept running after the tab closed")
+        .unwrap()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut lines = stdout.lines();
+    let entry: serde_json::Value = serde_json::from_str(lines.next().unwrap()).unwrap();
+    assert_eq!(entry["target_id"], id.as_str());
+    assert_eq!(
+        (&entry["level"], &entry["text"]),
+        (&json!("error"), &json!("boom"))
+    );
+    let report: serde_json::Value = serde_json::from_str(&lines.collect::<String>()).unwrap();
+    assert_eq!(report["command"], "tail");
+    assert_eq!(report["tabs"][0]["actions"][0]["action"], "tail");
+    assert_eq!(report["tabs"][0]["actions"][0]["value"], 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn only_a_tail_bang_silences_progress_output() {
+    let mock = MockCdp::start().await;
+
+    let output = debugchrome(
+        &mock,
+        &["debugchrome:https://example.com/?q=!tailwind&!id=tw1#!tail"],
+    )
+    .await;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Received URL"), "{}", stdout);
+    assert!(
+        mock.calls("Target.createTarget")[0].params["url"]
+            .as_str()
+            .unwrap()
+            .starts_with("https://example.com/?q=!tailwind")
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn har_records_from_the_first_request_until_the_tab_closes() {
+    let mock = MockCdp::start().await;
+    let dir = std::env::temp_dir().join(format!("debugchrome-har-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let path = dir.join("capture.har");
+
+    let url = format!(
+        "debugchrome:https://www.rust-lang.org/?!id=har1&!har={}",
+        path.display()
+    );
+    let (code, report) = debugchrome_json(&mock.endpoint(), &[&url]).await;
+
+    assert_eq!(code, 0, "{}", report);
+    assert_eq!(
+        mock.calls("Target.createTarget")[0].params["url"],
+        "about:blank"
+    );
+    let methods: Vec<String> = mock.received().into_iter().map(|r| r.method).collect();
+    let enabled = methods.iter().position(|m| m == "Network.enable").unwrap();
+    let navigated = methods.iter().position(|m| m == "Page.navigate").unwrap();
+    assert!(
+        enabled < navigated,
+        "navigated before recording: {:?}",
+        methods
+    );
+    assert!(
+        mock.targets()[0]
+            .url
+            .starts_with("https://www.rust-lang.org/")
+    );
+    let action = &report["tabs"][0]["actions"][0];
+    assert_eq!(
+        (&action["action"], &action["ok"]),
+        (&json!("har"), &json!(true))
+    );
+
+    mock.emit(
+        "Network.requestWillBeSent",
+        json!({
+            "requestId": "R1",
+            "type": "Document",
+            "timestamp": 1.0,
+            "wallTime": 1_700_000_000.0,
+            "request": { "url": "https://www.rust-lang.org/", "method": "GET", "headers": {} },
+        }),
+        None,
+    );
+    mock.emit(
+        "Network.responseReceived",
+        json!({
+            "requestId": "R1",
+            "timestamp": 1.1,
+            "response": { "status": 200, "statusText": "OK", "mimeType": "text/html", "headers": {} },
+        }),
+        None,
+    );
+    mock.emit(
+        "Network.loadingFinished",
+        json!({ "requestId": "R1", "timestamp": 1.2, "encodedDataLength": 1000 }),
+        None,
+    );
+    mock.emit(
+        "Inspector.detached",
+        json!({ "reason": "target_closed" }),
+        None,
+    );
+
+    let deadline = std::time::Instant::now() + Duration::from_secs(10);
+    let har: serde_json::Value = loop {
+        // Read until it parses, in case the recorder is still writing it.
+        if let Ok(Ok(har)) = std::fs::read(&path).map(|bytes| serde_json::from_slice(&bytes)) {
+            break har;
+        }
+        assert!(
+            std::time::Instant::now() < deadline,
+            "no HAR written to {}",
+            path.display()
+        );
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    };
+    assert_eq!(har["log"]["pages"][0]["id"], "har1");
+    let entries = har["log"]["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["request"]["url"], "https://www.rust-lang.org/");
+    assert_eq!(entries[0]["response"]["status"], 200);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn intercept_rules_apply_from_the_first_request() {
+    let mock = MockCdp::start().await;
+    let dir = std::env::temp_dir().join(format!("debugchrome-rules-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("rules.json"),
+        r#"[{ "url": "*/api/*", "fulfill": { "body": "[]", "headers": { "Content-Type": "application/json" } } }]"#,
+    )
+    .unwrap();
+    std::fs::write(dir.join("broken.json"), r#"[{ "url": "*" }]"#).unwrap();
+
+    // Relative rules paths are taken from where debugchrome was started.
+    let open = |rules: &str| {
+        let mut command = std::process::Command::new(env!("CARGO_BIN_EXE_debugchrome"));
+        command
+            .arg(format!(
+                "debugchrome:https://staging.example.com/?!intercept={}",
+                rules
+            ))
+            .args(["--json", "--cdp-endpoint", &mock.endpoint()])
+            .current_dir(&dir)
+            .env_remove("DEBUGCHROME_CDP");
+        tokio::task::spawn_blocking(move || command.output())
+    };
+
+    let output = open("broken.json").await.unwrap().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("intercept rule 1"));
+    assert!(mock.calls("Target.createTarget").is_empty());
+
+    let output = open("rules.json").await.unwrap().unwrap();
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(output.status.code(), Some(0), "{}", report);
+    assert_eq!(
+        mock.calls("Target.createTarget")[0].params["url"],
+        "about:blank"
+    );
+    let methods: Vec<String> = mock.received().into_iter().map(|r| r.method).collect();
+    let enabled = methods.iter().position(|m| m == "Fetch.enable").unwrap();
+    let navigated = methods.iter().position(|m| m == "Page.navigate").unwrap();
+    assert!(
+        enabled < navigated,
+        "navigated before intercepting: {:?}",
+        methods
+    );
+    let action = &report["tabs"][0]["actions"][0];
+    assert_eq!(action["action"], "intercept");
+    assert_eq!(
+        action["value"],
+        json!(dir.join("rules.json").display().to_string())
+    );
+
+    mock.emit(
+        "Fetch.requestPaused",
+        json!({
+            "requestId": "F1",
+            "request": { "url": "https://staging.example.com/api/users", "method": "GET", "headers": {} },
+        }),
+        None,
+    );
+    let fulfilled = mock
+        .wait_for("Fetch.fulfillRequest", Duration::from_secs(10))
+        .await
+        .expect("the detached interceptor did not answer");
+    assert_eq!(fulfilled.params["requestId"], "F1");
+    assert_eq!(fulfilled.params["body"], "W10=");
+    mock.emit(
+        "Inspector.detached",
+        json!({ "reason": "target_closed" }),
+        None,
+    );
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn emulation_is_in_place_before_the_page_loads() {
+    let mock = MockCdp::start().await;
+    let (code, report) = debugchrome_json(
+        &mock.endpoint(),
+        &["debugchrome:https://www.rust-lang.org/?!device=iphone-14&!colorscheme=dark"],
+    )
+    .await;
+
+    assert_eq!(code, 0, "{}", report);
+    assert_eq!(
+        mock.calls("Target.createTarget")[0].params["url"],
+        "about:blank"
+    );
+    let methods: Vec<String> = mock.received().into_iter().map(|r| r.method).collect();
+    let emulated = methods
+        .iter()
+        .position(|m| m == "Emulation.setDeviceMetricsOverride")
+        .unwrap();
+    let navigated = methods.iter().position(|m| m == "Page.navigate").unwrap();
+    assert!(
+        emulated < navigated,
+        "navigated before emulating: {:?}",
+        methods
+    );
+    let metrics = &mock.calls("Emulation.setDeviceMetricsOverride")[0].params;
+    assert_eq!(
+        (&metrics["width"], &metrics["height"], &metrics["mobile"]),
+        (&json!(390), &json!(844), &json!(true))
+    );
+    assert!(
+        mock.calls("Emulation.setUserAgentOverride")[0].params["userAgent"]
+            .as_str()
+            .unwrap()
+            .contains("iPhone")
+    );
+    let action = &report["tabs"][0]["actions"][0];
+    assert_eq!(action["action"], "emulate");
+    assert_eq!(
+        action["value"],
+        json!({ "device": "iphone-14", "color_scheme": "dark" })
+    );
+    mock.emit(
+        "Inspector.detached",
+        json!({ "reason": "target_closed" }),
+        None,
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn storage_exports_imports_and_seeds_new_tabs() {
+    let mock = MockCdp::start().await;
+    let app = mock.add_page("https://app.example.com/dashboard", "App");
+    mock.add_page("https://other.example.org/", "Other");
+    mock.respond(
+        "Network.getAllCookies",
+        Reply::Result(json!({ "cookies": [{
+            "name": "sid", "value": "abc", "domain": "app.example.com", "path": "/",
+            "expires": -1, "httpOnly": true, "secure": true, "session": true,
+        }]})),
+    );
+    mock.respond(
+        "Runtime.evaluate",
+        Reply::Result(json!({ "result": { "type": "object", "value": {
+            "origin": "https://app.example.com",
+            "localStorage": { "token": "t0k3n" },
+            "sessionStorage": { "step": "2" },
+        }}})),
+    );
+    let dir = std::env::temp_dir().join(format!("debugchrome-state-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let path = dir.join("state.json");
+    let file = path.to_str().unwrap();
+
+    let (code, report) = debugchrome_json(
+        &mock.endpoint(),
+        &[
+            "--export-storage",
+            file,
+            "--origin",
+            "https://app.example.com/",
+        ],
+    )
+    .await;
+    assert_eq!(code, 0, "{}", report);
+    assert_eq!(report["cookies"], 1);
+    assert_eq!(report["tabs"].as_array().unwrap().len(), 1, "{}", report);
+    assert_eq!(report["tabs"][0]["target_id"], app);
+    let state: serde_json::Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+    assert_eq!(state["cookies"][0]["name"], "sid");
+    assert!(state["cookies"][0].get("expires").is_none());
+    assert_eq!(state["origins"][0]["localStorage"]["token"], "t0k3n");
+    assert_eq!(state["origins"][0]["sessionStorage"]["step"], "2");
+
+    let (code, report) = debugchrome_json(&mock.endpoint(), &["--import-storage", file]).await;
+    assert_eq!(code, 0, "{}", report);
+    assert_eq!(
+        mock.calls("Network.setCookies")[0].params["cookies"][0]["name"],
+        "sid"
+    );
+    let action = &report["tabs"][0]["actions"][0];
+    assert_eq!(
+        (&action["action"], &action["value"]),
+        (&json!("storage"), &json!("https://app.example.com"))
+    );
+    assert!(mock.calls("Runtime.evaluate").iter().any(|call| {
+        call.params["expression"]
+            .as_str()
+            .unwrap()
+            .contains("sessionStorage.setItem")
+    }));
+
+    // A new tab gets the cookies and the seed script before it loads, and is held until its
+    // document has run the script.
+    let url = format!("debugchrome:https://app.example.com/?!storage={}", file);
+    let endpoint = mock.endpoint();
+    let args = [url.as_str()];
+    let opening = debugchrome_json(&endpoint, &args);
+    tokio::pin!(opening);
+    let (code, report) = loop {
+        tokio::select! {
+            done = &mut opening => break done,
+            _ = tokio::time::sleep(Duration::from_millis(100)) => {
+                mock.emit("Page.domContentEventFired", json!({}), None)
+            }
+        }
+    };
+    assert_eq!(code, 0, "{}", report);
+    let created = mock.calls("Target.createTarget");
+    assert_eq!(created[0].params["url"], "about:blank");
+    let methods: Vec<String> = mock.received().into_iter().map(|r| r.method).collect();
+    let seeded = methods
+        .iter()
+        .rposition(|m| m == "Page.addScriptToEvaluateOnNewDocument")
+        .unwrap();
+    let navigated = methods.iter().position(|m| m == "Page.navigate").unwrap();
+    assert!(
+        seeded < navigated,
+        "navigated before seeding: {:?}",
+        methods
+    );
+    assert_eq!(mock.calls("Network.setCookies").len(), 2);
+    let tab = mock.targets().into_iter().last().unwrap();
+    assert!(
+        tab.new_document_scripts
+            .iter()
+            .any(|script| script.contains("t0k3n"))
+    );
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn profile_bang_creates_persistent_profile_in_data_dir() {
+    let mock = MockCdp::start().await;
+    let data_dir = std::env::temp_dir().join(format!("debugchrome-data-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&data_dir);
+
+    debugchrome(
+        &mock,
+        &[
+            "debugchrome:https://www.rust-lang.org?!id=flow8&!profile=qa",
+            "--data-dir",
+            data_dir.to_str().unwrap(),
+        ],
+    )
+    .await;
+
+    assert!(data_dir.join("profiles").join("qa").is_dir());
+    std::fs::remove_dir_all(&data_dir).unwrap();
+}
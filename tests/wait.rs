@@ -0,0 +1,113 @@
+mod support;
+
+use debugchrome::CdpClient;
+use debugchrome::wait::{WaitCondition, WaitError, wait_for};
+use serde_json::json;
+use std::time::Duration;
+use support::MockCdp;
+
+fn ready(mock: &MockCdp, condition: &WaitCondition) {
+    mock.evaluate_to(
+        &condition.expression(),
+        json!({ "result": { "type": "boolean", "value": true } }),
+    );
+}
+
+#[test]
+fn conditions_parse_and_print_the_same_way() {
+    for text in [
+        "load",
+        "domcontentloaded",
+        "networkidle",
+        "selector:#app > main",
+        "js:window.appReady === true",
+    ] {
+        let condition: WaitCondition = text.parse().unwrap();
+        assert_eq!(condition.to_string(), text);
+    }
+    assert_eq!(
+        "DOMContentLoaded".parse::<WaitCondition>(),
+        Ok(WaitCondition::DomContentLoaded)
+    );
+    for bad in ["", "idle", "selector:", "js:"] {
+        assert!(bad.parse::<WaitCondition>().is_err(), "{}", bad);
+    }
+}
+
+#[tokio::test]
+async fn returns_once_the_condition_holds() {
+    let mock = MockCdp::start().await;
+    let id = mock.add_page("https://example.com/", "Example");
+    let condition = WaitCondition::Selector("#app".into());
+    ready(&mock, &condition);
+    let client = CdpClient::connect_page(&mock.endpoint(), &id)
+        .await
+        .unwrap();
+
+    wait_for(&client.page(), &condition, Duration::from_secs(5))
+        .await
+        .unwrap();
+
+    assert_eq!(mock.calls("Page.setLifecycleEventsEnabled").len(), 1);
+    assert!(mock.calls("Network.enable").is_empty());
+}
+
+#[tokio::test]
+async fn times_out_when_the_condition_never_holds() {
+    let mock = MockCdp::start().await;
+    let id = mock.add_page("https://example.com/", "Example");
+    let client = CdpClient::connect_page(&mock.endpoint(), &id)
+        .await
+        .unwrap();
+
+    let err = wait_for(
+        &client.page(),
+        &WaitCondition::Js("window.appReady".into()),
+        Duration::from_millis(300),
+    )
+    .await
+    .unwrap_err();
+
+    assert!(matches!(err, WaitError::Timeout { .. }), "{}", err);
+    assert!(err.to_string().contains("!wait=js:window.appReady"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn network_idle_waits_for_requests_in_flight() {
+    let mock = MockCdp::start().await;
+    let id = mock.add_page("https://example.com/", "Example");
+    ready(&mock, &WaitCondition::NetworkIdle);
+    let client = CdpClient::connect_page(&mock.endpoint(), &id)
+        .await
+        .unwrap();
+    let waiting = tokio::spawn(async move {
+        wait_for(
+            &client.page(),
+            &WaitCondition::NetworkIdle,
+            Duration::from_secs(10),
+        )
+        .await
+    });
+
+    mock.wait_for("Network.enable", Duration::from_secs(5))
+        .await
+        .expect("Network.enable not sent");
+    mock.emit(
+        "Network.requestWillBeSent",
+        json!({ "requestId": "R1" }),
+        None,
+    );
+    tokio::time::sleep(Duration::from_millis(900)).await;
+    assert!(!waiting.is_finished(), "finished with a request in flight");
+
+    mock.emit(
+        "Network.loadingFinished",
+        json!({ "requestId": "R1" }),
+        None,
+    );
+    tokio::time::timeout(Duration::from_secs(5), waiting)
+        .await
+        .expect("still waiting after the request finished")
+        .unwrap()
+        .unwrap();
+}